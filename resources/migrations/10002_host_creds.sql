@@ -0,0 +1,21 @@
+-- Host credentials used to authenticate sshd AuthorizedKeysCommand callers.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- host_creds table
+-- ---------------------------------------
+-- Each host that retrieves public keys from TMS authenticates using its host_id and secret.
+-- The host_id is the same host name that appears in the user_hosts and pubkeys tables, so
+-- a host can only retrieve keys issued for itself.  Only hashes of the secrets are stored.
+CREATE TABLE IF NOT EXISTS host_creds
+(
+    id                SERIAL PRIMARY KEY,
+    tenant            TEXT REFERENCES tenants(tenant) ON UPDATE CASCADE ON DELETE RESTRICT,
+    host_id           TEXT NOT NULL,
+    host_secret       TEXT NOT NULL,
+    created           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    UNIQUE (tenant, host_id)
+);
+ALTER TABLE host_creds OWNER TO tms;
@@ -0,0 +1,21 @@
+-- One-time client registration codes used when new_clients = "on_approval".
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- client_approvals table
+-- ---------------------------------------
+-- Tenant administrators issue short-lived, one-time codes that allow a new client
+-- to be created in their tenant.  Only hashes of the codes are stored, and each
+-- record is deleted when its code is used to create a client.
+CREATE TABLE IF NOT EXISTS client_approvals
+(
+    id                SERIAL PRIMARY KEY,
+    tenant            TEXT REFERENCES tenants(tenant) ON UPDATE CASCADE ON DELETE RESTRICT,
+    approval_hash     TEXT NOT NULL,
+    expires_at        TIMESTAMPTZ NOT NULL,
+    created           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    UNIQUE (tenant, approval_hash)
+);
+ALTER TABLE client_approvals OWNER TO tms;
@@ -0,0 +1,57 @@
+-- Allow wildcard records in the user_hosts and delegations tables.
+
+SET search_path TO tms;
+
+-- A user_hosts record with tms_user_id = '*' and host_account = '*' links every user
+-- to the host account of the same name, and a delegations record with client_user_id
+-- = '*' applies to all users of a client.  Dependencies are checked by the application
+-- (exact matches take precedence over wildcards) when keys and reservations are created
+-- and every time a key is retrieved.  The foreign keys below keep the database
+-- consistent with those checks.
+
+-- ---------------------------------------
+-- user_mfa references
+-- ---------------------------------------
+-- Wildcard records have no corresponding user_mfa record, so the user_mfa foreign keys
+-- are defined on generated columns that are null for wildcard records.  Null columns
+-- are not checked, while all other records must still reference an existing user_mfa
+-- record and are removed along with it.  The user ids in these tables are never
+-- updated, so only deletes cascade.
+ALTER TABLE user_hosts DROP CONSTRAINT IF EXISTS user_hosts_tenant_tms_user_id_fkey;
+ALTER TABLE user_hosts ADD COLUMN IF NOT EXISTS mfa_user_id TEXT
+    GENERATED ALWAYS AS (NULLIF(tms_user_id, '*')) STORED;
+ALTER TABLE user_hosts ADD CONSTRAINT user_hosts_tenant_mfa_user_id_fkey
+    FOREIGN KEY(tenant, mfa_user_id) REFERENCES user_mfa(tenant, tms_user_id) ON DELETE CASCADE;
+
+ALTER TABLE delegations DROP CONSTRAINT IF EXISTS delegations_tenant_client_user_id_fkey;
+ALTER TABLE delegations ADD COLUMN IF NOT EXISTS mfa_user_id TEXT
+    GENERATED ALWAYS AS (NULLIF(client_user_id, '*')) STORED;
+ALTER TABLE delegations ADD CONSTRAINT delegations_tenant_mfa_user_id_fkey
+    FOREIGN KEY(tenant, mfa_user_id) REFERENCES user_mfa(tenant, tms_user_id) ON DELETE CASCADE;
+
+-- ---------------------------------------
+-- pubkeys references
+-- ---------------------------------------
+-- Keys can be issued on the basis of wildcard records, so a key cannot reference its
+-- user_hosts and delegations records by user id and host account.  Instead, each key
+-- references the records that authorized it when it was created, which are the same
+-- records the dependency checks select.  Removing or reaping either record removes
+-- the key, so a key never outlives the records on which it depends.
+ALTER TABLE pubkeys ADD COLUMN IF NOT EXISTS user_host_id INTEGER
+    REFERENCES user_hosts(id) ON DELETE CASCADE;
+ALTER TABLE pubkeys ADD COLUMN IF NOT EXISTS delegation_id INTEGER
+    REFERENCES delegations(id) ON DELETE CASCADE;
+
+-- Existing keys reference exact records.
+UPDATE pubkeys p SET user_host_id = uh.id FROM user_hosts uh
+    WHERE p.user_host_id IS NULL AND uh.tenant = p.tenant AND uh.tms_user_id = p.client_user_id
+      AND uh.host = p.host AND uh.host_account = p.host_account;
+UPDATE pubkeys p SET delegation_id = d.id FROM delegations d
+    WHERE p.delegation_id IS NULL AND d.tenant = p.tenant AND d.client_id = p.client_id
+      AND d.client_user_id = p.client_user_id;
+ALTER TABLE pubkeys ALTER COLUMN user_host_id SET NOT NULL;
+ALTER TABLE pubkeys ALTER COLUMN delegation_id SET NOT NULL;
+ALTER TABLE pubkeys DROP CONSTRAINT IF EXISTS pubkeys_tenant_client_user_id_host_host_account_fkey;
+ALTER TABLE pubkeys DROP CONSTRAINT IF EXISTS pubkeys_tenant_client_id_client_user_id_fkey;
+CREATE INDEX IF NOT EXISTS pubkeys_user_host_id_idx ON pubkeys (user_host_id);
+CREATE INDEX IF NOT EXISTS pubkeys_delegation_id_idx ON pubkeys (delegation_id);
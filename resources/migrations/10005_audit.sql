@@ -0,0 +1,269 @@
+-- Define audit tables and triggers for all TMS tables.
+--
+-- This migration implements the design in migrations_bak/10002_audit.sql.  All audit
+-- tables have the same format with the same columns, which have the following meanings:
+--
+--  id - Unique record id
+--  refid - Id of record in associated table
+--  tenant - Tenant of record in associated table
+--  refcol - Name of column that changed, or "row" for inserts and deletes
+--  change - Type of change: I = Insert, U = Update, D = Delete
+--  oldvalue - String representation of original value if one existed
+--  newvalue - String representation of new value if there is one
+--  changed - UTC time of change
+--
+-- A single trigger function handles inserts, updates and deletions on each TMS table.
+-- Insert and delete records contain the whole row in json format.  Updates write one
+-- record for each changed column except the updated column, which would be redundant.
+-- Secrets and their hashes are never written to the audit tables: they are removed from
+-- whole row values and only the fact that they changed is recorded on updates.
+--
+-- The audit tables only have a primary key to avoid the extra overhead of index 
+-- management when writing audit records.  Audit records do not reference the audited
+-- tables so that they outlive the records they describe.
+
+SET search_path TO tms;
+
+-- -------------------------------------------------------------
+-- TABLES
+-- -------------------------------------------------------------
+CREATE TABLE IF NOT EXISTS tenants_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE tenants_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS clients_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE clients_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS user_mfa_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE user_mfa_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS user_hosts_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE user_hosts_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS delegations_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE delegations_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS pubkeys_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE pubkeys_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS reservations_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE reservations_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS admin_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE admin_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS hosts_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE hosts_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS host_creds_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE host_creds_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS client_approvals_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE client_approvals_audit OWNER TO tms;
+
+-- -------------------------------------------------------------
+-- TRIGGER FUNCTION
+-- -------------------------------------------------------------
+-- The audit table name is derived from the name of the table that fired the trigger.
+CREATE OR REPLACE FUNCTION audit_function() RETURNS TRIGGER AS $$
+DECLARE
+  audit_table TEXT := TG_TABLE_NAME || '_audit';
+  secret_cols TEXT[] := ARRAY['client_secret', 'admin_secret', 'host_secret', 'approval_hash', 'private_key'];
+  old_row     JSONB;
+  new_row     JSONB;
+  col         TEXT;
+BEGIN
+  IF (TG_OP = 'INSERT') THEN
+    new_row := to_jsonb(NEW) - secret_cols;
+    EXECUTE format('INSERT INTO %I (refid, tenant, refcol, change, newvalue) VALUES ($1, $2, $3, $4, $5)', audit_table)
+      USING NEW.id, new_row->>'tenant', 'row', 'I', new_row::TEXT;
+  ELSIF (TG_OP = 'DELETE') THEN
+    old_row := to_jsonb(OLD) - secret_cols;
+    EXECUTE format('INSERT INTO %I (refid, tenant, refcol, change, oldvalue) VALUES ($1, $2, $3, $4, $5)', audit_table)
+      USING OLD.id, old_row->>'tenant', 'row', 'D', old_row::TEXT;
+  ELSIF (TG_OP = 'UPDATE') THEN
+    old_row := to_jsonb(OLD);
+    new_row := to_jsonb(NEW);
+    FOR col IN SELECT jsonb_object_keys(new_row) LOOP
+      IF col != 'updated' AND (old_row->col) IS DISTINCT FROM (new_row->col) THEN
+        IF col = ANY(secret_cols) THEN
+          EXECUTE format('INSERT INTO %I (refid, tenant, refcol, change) VALUES ($1, $2, $3, $4)', audit_table)
+            USING NEW.id, new_row->>'tenant', col, 'U';
+        ELSE
+          EXECUTE format('INSERT INTO %I (refid, tenant, refcol, change, oldvalue, newvalue) VALUES ($1, $2, $3, $4, $5, $6)', audit_table)
+            USING NEW.id, new_row->>'tenant', col, 'U', old_row->>col, new_row->>col;
+        END IF;
+      END IF;
+    END LOOP;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+ALTER FUNCTION audit_function() OWNER TO tms;
+
+-- -------------------------------------------------------------
+-- TRIGGERS
+-- -------------------------------------------------------------
+DROP TRIGGER IF EXISTS tenants_audit_trigger ON tenants;
+CREATE TRIGGER tenants_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON tenants
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS clients_audit_trigger ON clients;
+CREATE TRIGGER clients_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON clients
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS user_mfa_audit_trigger ON user_mfa;
+CREATE TRIGGER user_mfa_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON user_mfa
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS user_hosts_audit_trigger ON user_hosts;
+CREATE TRIGGER user_hosts_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON user_hosts
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS delegations_audit_trigger ON delegations;
+CREATE TRIGGER delegations_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON delegations
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS pubkeys_audit_trigger ON pubkeys;
+CREATE TRIGGER pubkeys_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON pubkeys
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS reservations_audit_trigger ON reservations;
+CREATE TRIGGER reservations_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON reservations
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS admin_audit_trigger ON admin;
+CREATE TRIGGER admin_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON admin
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS hosts_audit_trigger ON hosts;
+CREATE TRIGGER hosts_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON hosts
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS host_creds_audit_trigger ON host_creds;
+CREATE TRIGGER host_creds_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON host_creds
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS client_approvals_audit_trigger ON client_approvals;
+CREATE TRIGGER client_approvals_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON client_approvals
+FOR EACH ROW EXECUTE FUNCTION audit_function();
@@ -0,0 +1,43 @@
+-- Support for the background reaper that removes expired records.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- reaper_archive table
+-- ---------------------------------------
+-- When archiving is enabled, the reaper copies each expired record it removes from 
+-- the pubkeys, reservations, user_mfa, delegations and user_hosts tables into this
+-- table.  Records are saved in json format so that the archive is not affected by 
+-- changes to the schemas of the reaped tables.  Archived records do not reference 
+-- the tables from which they were removed.
+CREATE TABLE IF NOT EXISTS reaper_archive
+(
+    id            SERIAL PRIMARY KEY,
+    tablename     TEXT NOT NULL,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    record        JSONB NOT NULL,
+    expires_at    TIMESTAMPTZ NOT NULL,
+    archived      TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE reaper_archive OWNER TO tms;
+CREATE INDEX IF NOT EXISTS reaper_archive_tenant_idx ON reaper_archive (tenant, tablename);
+
+-- ---------------------------------------
+-- expiration indexes
+-- ---------------------------------------
+-- The reaper selects expired records in batches by their expiration times.
+CREATE INDEX IF NOT EXISTS pubkeys_expires_at_idx ON pubkeys (expires_at);
+CREATE INDEX IF NOT EXISTS reservations_expires_at_idx ON reservations (expires_at);
+CREATE INDEX IF NOT EXISTS user_mfa_expires_at_idx ON user_mfa (expires_at);
+CREATE INDEX IF NOT EXISTS delegations_expires_at_idx ON delegations (expires_at);
+CREATE INDEX IF NOT EXISTS user_hosts_expires_at_idx ON user_hosts (expires_at);
+
+-- ---------------------------------------
+-- user reference indexes
+-- ---------------------------------------
+-- The reaper only removes an expired user_mfa record when no other record 
+-- references the user.
+CREATE INDEX IF NOT EXISTS pubkeys_client_user_idx ON pubkeys (tenant, client_user_id);
+CREATE INDEX IF NOT EXISTS reservations_client_user_idx ON reservations (tenant, client_user_id);
+CREATE INDEX IF NOT EXISTS delegations_client_user_idx ON delegations (tenant, client_user_id);
@@ -0,0 +1,46 @@
+-- Per-tenant SSH certificate authorities used to sign user certificates.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- tenant_ca table
+-- ---------------------------------------
+-- Each tenant has one CA key pair.  The CA is created with the tenant, or on 
+-- first use for tenants created before CAs were introduced.  Hosts that trust 
+-- the CA public key (see sshd's TrustedUserCAKeys) accept the user certificates
+-- that TMS signs with the CA private key.  The private key is stored in OpenSSH
+-- format encrypted with the server's database encryption key, and the audit
+-- function omits it from the audit records like all other secrets.
+CREATE TABLE IF NOT EXISTS tenant_ca
+(
+    id                     SERIAL PRIMARY KEY,
+    tenant                 TEXT NOT NULL UNIQUE REFERENCES tenants(tenant) ON UPDATE CASCADE ON DELETE RESTRICT,
+    private_key            TEXT NOT NULL,
+    public_key             TEXT NOT NULL,
+    public_key_fingerprint TEXT NOT NULL,
+    key_type               TEXT NOT NULL,
+    created                TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated                TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE tenant_ca OWNER TO tms;
+
+-- ---------------------------------------
+-- tenant_ca_audit table
+-- ---------------------------------------
+CREATE TABLE IF NOT EXISTS tenant_ca_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE tenant_ca_audit OWNER TO tms;
+
+DROP TRIGGER IF EXISTS tenant_ca_audit_trigger ON tenant_ca;
+CREATE TRIGGER tenant_ca_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON tenant_ca
+FOR EACH ROW EXECUTE FUNCTION audit_function();
@@ -0,0 +1,11 @@
+-- Per-tenant policy that limits the key types and lengths users can request.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- tenants table
+-- ---------------------------------------
+-- The allowed_key_types column lists the key types that can be generated or registered 
+-- in the tenant using names such as ed25519, ecdsa-384 and rsa-3072.  NULL allows all
+-- key types that TMS supports.
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS allowed_key_types TEXT[];
@@ -0,0 +1,103 @@
+-- Allow a public key to be used on a set of hosts.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- host_groups table
+-- ---------------------------------------
+-- Each record makes a host a member of a named group of hosts in a tenant, such as
+-- the login nodes of a cluster.  Keys scoped to a group can be used on the group's
+-- current members.
+CREATE TABLE IF NOT EXISTS host_groups
+(
+    id                SERIAL PRIMARY KEY,
+    tenant            TEXT REFERENCES tenants(tenant) ON UPDATE CASCADE ON DELETE RESTRICT,
+    host_group        TEXT NOT NULL,
+    host              TEXT NOT NULL,
+    created           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    UNIQUE (tenant, host_group, host)
+);
+ALTER TABLE host_groups OWNER TO tms;
+
+-- ---------------------------------------
+-- pubkeys table
+-- ---------------------------------------
+-- A key can be used on its host, on the hosts listed for it in the pubkey_hosts table
+-- and on the members of its host_group.  Dependencies are checked for the host on
+-- which the key is used every time the key is retrieved.  The host column remains the
+-- host that identifies the key in the pubkeys APIs.
+ALTER TABLE pubkeys ADD COLUMN IF NOT EXISTS host_group TEXT;
+
+CREATE TABLE IF NOT EXISTS pubkey_hosts
+(
+    id                SERIAL PRIMARY KEY,
+    tenant            TEXT REFERENCES tenants(tenant) ON UPDATE CASCADE ON DELETE RESTRICT,
+    pubkey_id         INTEGER NOT NULL REFERENCES pubkeys(id) ON DELETE CASCADE,
+    host              TEXT NOT NULL,
+    UNIQUE (pubkey_id, host)
+);
+ALTER TABLE pubkey_hosts OWNER TO tms;
+CREATE INDEX IF NOT EXISTS pubkey_hosts_host_idx ON pubkey_hosts (host);
+
+-- ---------------------------------------
+-- reservations table
+-- ---------------------------------------
+-- Reservations can be made for any host on which a key can be used, so they no longer
+-- reference the key by fingerprint and host.  The pubkey_id column still removes a
+-- key's reservations when the key is removed.
+ALTER TABLE reservations ADD COLUMN IF NOT EXISTS pubkey_id INTEGER REFERENCES pubkeys(id) ON DELETE CASCADE;
+UPDATE reservations r SET pubkey_id = p.id FROM pubkeys p
+    WHERE r.pubkey_id IS NULL AND p.public_key_fingerprint = r.public_key_fingerprint AND p.host = r.host;
+ALTER TABLE reservations ALTER COLUMN pubkey_id SET NOT NULL;
+CREATE INDEX IF NOT EXISTS reservations_pubkey_id_idx ON reservations (pubkey_id);
+ALTER TABLE reservations DROP CONSTRAINT IF EXISTS reservations_public_key_fingerprint_host_fkey;
+
+-- A key no longer needs a separate record for each host on which it is used, so each
+-- key has a single record in the pubkeys table.  Generated keys always have unique
+-- fingerprints and registered keys have been checked for uniqueness in all tenants,
+-- so the unique index also prevents concurrent registrations of the same key.
+ALTER TABLE pubkeys DROP CONSTRAINT IF EXISTS pubkeys_public_key_fingerprint_host_key;
+CREATE UNIQUE INDEX IF NOT EXISTS pubkeys_fingerprint_idx ON pubkeys (public_key_fingerprint);
+
+-- ---------------------------------------
+-- audit tables
+-- ---------------------------------------
+-- The host_groups and pubkey_hosts tables determine the hosts on which keys can be
+-- used, so they are audited like all other TMS tables (see 10005_audit.sql).  The
+-- tenant column of pubkey_hosts identifies the tenant of its audit records.
+CREATE TABLE IF NOT EXISTS host_groups_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE host_groups_audit OWNER TO tms;
+
+CREATE TABLE IF NOT EXISTS pubkey_hosts_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE pubkey_hosts_audit OWNER TO tms;
+
+DROP TRIGGER IF EXISTS host_groups_audit_trigger ON host_groups;
+CREATE TRIGGER host_groups_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON host_groups
+FOR EACH ROW EXECUTE FUNCTION audit_function();
+
+DROP TRIGGER IF EXISTS pubkey_hosts_audit_trigger ON pubkey_hosts;
+CREATE TRIGGER pubkey_hosts_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON pubkey_hosts
+FOR EACH ROW EXECUTE FUNCTION audit_function();
@@ -0,0 +1,13 @@
+-- Store the security key attributes of registered FIDO keys.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- pubkeys table
+-- ---------------------------------------
+-- Registered sk-ssh-ed25519@openssh.com and sk-ecdsa-sha2-nistp256@openssh.com keys
+-- record the application string chosen when the key was enrolled and the flags that
+-- become options on the key's authorized_keys line, such as verify-required.  Both
+-- columns are null for all other keys.
+ALTER TABLE pubkeys ADD COLUMN IF NOT EXISTS sk_application TEXT;
+ALTER TABLE pubkeys ADD COLUMN IF NOT EXISTS sk_flags TEXT[];
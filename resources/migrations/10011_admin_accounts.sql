@@ -0,0 +1,10 @@
+-- Allow tenant administrators to be disabled.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- admin table
+-- ---------------------------------------
+-- Disabled administrators cannot authenticate.  Existing administrators,
+-- including the ~~admin user created with each tenant, remain enabled.
+ALTER TABLE admin ADD COLUMN IF NOT EXISTS enabled BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,17 @@
+-- Create the site administrator on existing installations.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- admin table
+-- ---------------------------------------
+-- Only the site administrator, an admin in the default tenant with the
+-- PERM_SITE_ADMIN privilege, can create, delete, wipe or list all tenants.
+-- New installations create the ~~siteadmin user with its own secret.  On
+-- existing installations ~~siteadmin is created disabled and with a secret
+-- hash that no secret matches, so no one can act as the site admin until
+-- an operator runs tms_server --reset-site-admin to set its secret.
+INSERT INTO admin (tenant, admin_user, admin_secret, privilege, enabled, created, updated)
+    SELECT tenant, '~~siteadmin', '!', 'PERM_SITE_ADMIN', FALSE, NOW(), NOW()
+    FROM tenants WHERE tenant = 'default'
+    ON CONFLICT DO NOTHING;
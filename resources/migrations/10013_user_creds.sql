@@ -0,0 +1,84 @@
+-- User credentials used by end users to manage their own records.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- user_creds table
+-- ---------------------------------------
+-- Each user that manages their own MFA, host account, delegation and public key
+-- records authenticates using their tms_user_id and secret.  The tms_user_id is the
+-- same user id that appears in the user_mfa, user_hosts and delegations tables, and
+-- the client_user_id in the pubkeys table, so a user can only access their own
+-- records.  Only hashes of the secrets are stored.
+CREATE TABLE IF NOT EXISTS user_creds
+(
+    id                SERIAL PRIMARY KEY,
+    tenant            TEXT REFERENCES tenants(tenant) ON UPDATE CASCADE ON DELETE RESTRICT,
+    tms_user_id       TEXT NOT NULL,
+    user_secret       TEXT NOT NULL,
+    created           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    UNIQUE (tenant, tms_user_id)
+);
+ALTER TABLE user_creds OWNER TO tms;
+
+-- ---------------------------------------
+-- user_creds auditing
+-- ---------------------------------------
+CREATE TABLE IF NOT EXISTS user_creds_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE user_creds_audit OWNER TO tms;
+
+-- The audit function is redefined to keep user secret hashes out of the audit tables.
+-- -------------------------------------------------------------
+-- The audit table name is derived from the name of the table that fired the trigger.
+CREATE OR REPLACE FUNCTION audit_function() RETURNS TRIGGER AS $$
+DECLARE
+  audit_table TEXT := TG_TABLE_NAME || '_audit';
+  secret_cols TEXT[] := ARRAY['client_secret', 'admin_secret', 'host_secret', 'user_secret', 'approval_hash',
+                           'private_key'];
+  old_row     JSONB;
+  new_row     JSONB;
+  col         TEXT;
+BEGIN
+  IF (TG_OP = 'INSERT') THEN
+    new_row := to_jsonb(NEW) - secret_cols;
+    EXECUTE format('INSERT INTO %I (refid, tenant, refcol, change, newvalue) VALUES ($1, $2, $3, $4, $5)', audit_table)
+      USING NEW.id, new_row->>'tenant', 'row', 'I', new_row::TEXT;
+  ELSIF (TG_OP = 'DELETE') THEN
+    old_row := to_jsonb(OLD) - secret_cols;
+    EXECUTE format('INSERT INTO %I (refid, tenant, refcol, change, oldvalue) VALUES ($1, $2, $3, $4, $5)', audit_table)
+      USING OLD.id, old_row->>'tenant', 'row', 'D', old_row::TEXT;
+  ELSIF (TG_OP = 'UPDATE') THEN
+    old_row := to_jsonb(OLD);
+    new_row := to_jsonb(NEW);
+    FOR col IN SELECT jsonb_object_keys(new_row) LOOP
+      IF col != 'updated' AND (old_row->col) IS DISTINCT FROM (new_row->col) THEN
+        IF col = ANY(secret_cols) THEN
+          EXECUTE format('INSERT INTO %I (refid, tenant, refcol, change) VALUES ($1, $2, $3, $4)', audit_table)
+            USING NEW.id, new_row->>'tenant', col, 'U';
+        ELSE
+          EXECUTE format('INSERT INTO %I (refid, tenant, refcol, change, oldvalue, newvalue) VALUES ($1, $2, $3, $4, $5, $6)', audit_table)
+            USING NEW.id, new_row->>'tenant', col, 'U', old_row->>col, new_row->>col;
+        END IF;
+      END IF;
+    END LOOP;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+ALTER FUNCTION audit_function() OWNER TO tms;
+
+DROP TRIGGER IF EXISTS user_creds_audit_trigger ON user_creds;
+CREATE TRIGGER user_creds_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON user_creds
+FOR EACH ROW EXECUTE FUNCTION audit_function();
@@ -0,0 +1,46 @@
+-- Client certificate mappings used to authenticate clients and hosts with mutual TLS.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- cert_mappings table
+-- ---------------------------------------
+-- When mutual TLS is enabled, a verified client certificate authenticates its caller as
+-- the client or host that one of its names is mapped to.  The cert_subject is either the
+-- common name (CN) of the certificate's subject or one of its subject alternative names.
+-- The subject_type is 'client' or 'host', and the subject_id is the client_id in the
+-- clients table or the host name used in the user_hosts and pubkeys tables.
+CREATE TABLE IF NOT EXISTS cert_mappings
+(
+    id                SERIAL PRIMARY KEY,
+    tenant            TEXT REFERENCES tenants(tenant) ON UPDATE CASCADE ON DELETE RESTRICT,
+    cert_subject      TEXT NOT NULL,
+    subject_type      TEXT NOT NULL CHECK( subject_type IN ('client','host') ),
+    subject_id        TEXT NOT NULL,
+    created           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated           TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    UNIQUE (tenant, cert_subject, subject_type, subject_id)
+);
+ALTER TABLE cert_mappings OWNER TO tms;
+CREATE INDEX IF NOT EXISTS cert_mappings_subject_idx ON cert_mappings (tenant, subject_type, cert_subject);
+
+-- ---------------------------------------
+-- cert_mappings auditing
+-- ---------------------------------------
+CREATE TABLE IF NOT EXISTS cert_mappings_audit
+(
+    id            SERIAL PRIMARY KEY,
+    refid         INTEGER NOT NULL,
+    tenant        TEXT,
+    refcol        TEXT NOT NULL,
+    change        TEXT CHECK( change IN ('I','U','D') ),
+    oldvalue      TEXT,
+    newvalue      TEXT,
+    changed       TIMESTAMPTZ NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+ALTER TABLE cert_mappings_audit OWNER TO tms;
+
+DROP TRIGGER IF EXISTS cert_mappings_audit_trigger ON cert_mappings;
+CREATE TRIGGER cert_mappings_audit_trigger
+AFTER INSERT OR UPDATE OR DELETE ON cert_mappings
+FOR EACH ROW EXECUTE FUNCTION audit_function();
@@ -0,0 +1,11 @@
+-- Record the serial numbers of the user certificates signed for public keys.
+
+SET search_path TO tms;
+
+-- ---------------------------------------
+-- pubkeys table
+-- ---------------------------------------
+-- Keys created with a certificate save the certificate's serial number so that the
+-- certificate can be revoked by serial number when the key is deleted or expires.
+-- Keys without certificates have a null serial number.
+ALTER TABLE pubkeys ADD COLUMN IF NOT EXISTS cert_serial BIGINT;
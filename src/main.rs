@@ -21,6 +21,7 @@ use crate::v1::tms::client_get::GetClientApi;
 use crate::v1::tms::client_list::ListClientApi;
 use crate::v1::tms::client_update_secret::UpdateClientSecretApi;
 use crate::v1::tms::client_update::UpdateClientApi;
+use crate::v1::tms::client_approval_create::CreateClientApprovalApi;
 use crate::v1::tms::pubkeys_create::NewSshKeysApi;
 use crate::v1::tms::pubkeys_retrieve::PublicKeyApi;
 use crate::v1::tms::user_mfa_create::CreateUserMfaApi;
@@ -52,18 +53,48 @@ use crate::v1::tms::hosts_create::CreateHostsApi;
 use crate::v1::tms::hosts_get::GetHostsApi;
 use crate::v1::tms::hosts_delete::DeleteHostsApi;
 use crate::v1::tms::hosts_list::ListHostsApi;
+use crate::v1::tms::host_creds_create::CreateHostCredsApi;
+use crate::v1::tms::host_creds_list::ListHostCredsApi;
+use crate::v1::tms::host_creds_delete::DeleteHostCredsApi;
+use crate::v1::tms::host_creds_update_secret::UpdateHostCredsSecretApi;
 use crate::v1::tms::reservations_get::GetReservationApi;
 use crate::v1::tms::reservations_delete::DeleteReservationApi;
 use crate::v1::tms::reservations_delete_related::DeleteRelatedReservationsApi;
 use crate::v1::tms::reservations_create::CreateReservationsApi;
 use crate::v1::tms::reservations_extend::ExtendReservationsApi;
+use crate::v1::tms::audit_list::ListAuditApi;
+use crate::v1::tms::reaper_stats::GetReaperStatsApi;
+use crate::v1::tms::tenant_ca_get::GetTenantCaApi;
+use crate::v1::tms::pubkeys_register::RegisterPubkeyApi;
+use crate::v1::tms::pubkeys_rotate::RotatePubkeyApi;
+use crate::v1::tms::host_groups_create::CreateHostGroupsApi;
+use crate::v1::tms::host_groups_get::GetHostGroupsApi;
+use crate::v1::tms::host_groups_delete::DeleteHostGroupsApi;
+use crate::v1::tms::pubkeys_krl::GetPubkeysKrlApi;
+use crate::v1::tms::keytypes_get::GetKeyTypesApi;
+use crate::v1::tms::admin_create::CreateAdminApi;
+use crate::v1::tms::admin_get::GetAdminApi;
+use crate::v1::tms::admin_list::ListAdminApi;
+use crate::v1::tms::admin_update::UpdateAdminApi;
+use crate::v1::tms::admin_delete::DeleteAdminApi;
+use crate::v1::tms::admin_update_secret::UpdateAdminSecretApi;
+use crate::v1::tms::admin_reset_secret::ResetAdminSecretApi;
+use crate::v1::tms::user_creds_create::CreateUserCredsApi;
+use crate::v1::tms::user_creds_list::ListUserCredsApi;
+use crate::v1::tms::user_creds_delete::DeleteUserCredsApi;
+use crate::v1::tms::user_creds_update_secret::UpdateUserCredsSecretApi;
+use crate::v1::tms::cert_mappings_create::CreateCertMappingsApi;
+use crate::v1::tms::cert_mappings_list::ListCertMappingsApi;
+use crate::v1::tms::cert_mappings_delete::DeleteCertMappingsApi;
+use crate::v1::tms::key_pool_stats::GetKeyPoolStatsApi;
 use crate::v1::tms::version::VersionApi;
 
 // TMS Utilities
 use crate::utils::config::{TMS_CMD_ARGS, TMS_DIRS, TEST_TENANT, init_log, init_runtime_context,
                            set_directories_and_check_install, prohibit_root_user, RuntimeCtx};
 use crate::utils::errors::Errors;
-use crate::utils::{keygen, db};
+use crate::utils::{keygen, key_pool, db, reaper, ssh_ca};
+use crate::utils::mtls::{MtlsAcceptor, create_ssl_acceptor};
 
 // Modules
 mod utils;
@@ -149,6 +180,12 @@ async fn main() -> Result<(), std::io::Error> {
         println!("Exiting: TMS DB Schema initialized.");
         return Ok(());
     }
+    // If this was a site admin reset run then we are done
+    if TMS_CMD_ARGS.reset_site_admin {
+        block_on(db::reset_site_admin()).expect("Unable to reset the site administrator's secret.");
+        println!("Exiting: Site administrator secret reset.");
+        return Ok(());
+    }
 
     // This is a non-install startup. Perform second stage initialization
     tms_init2();
@@ -161,13 +198,20 @@ async fn main() -> Result<(), std::io::Error> {
     let endpoints = 
         api!(HelloApi, NewSshKeysApi, PublicKeyApi, VersionApi, 
          CreateClientApi, GetClientApi, UpdateClientApi, DeleteClientApi, UpdateClientSecretApi, ListClientApi, 
+         CreateClientApprovalApi,
          CreateUserMfaApi, GetUserMfaApi, UpdateUserMfaApi, DeleteUserMfaApi, ListUserMfaApi,
-         GetPubkeysApi, ListPubkeysApi, DeletePubkeysApi, UpdatePubkeyApi,
+         GetPubkeysApi, ListPubkeysApi, DeletePubkeysApi, UpdatePubkeyApi, RegisterPubkeyApi,
          CreateUserHostsApi, GetUserHostsApi, ListUserHostsApi, DeleteUserHostsApi, UpdateUserHostsApi,
          CreateDelegationsApi, GetDelegationsApi, ListDelegationsApi, DeleteDelegationsApi, UpdateDelegationsApi,
          CreateTenantsApi, GetTenantsApi, ListTenantsApi, DeleteTenantsApi, UpdateTenantsApi, WipeTenantsApi,
          CreateHostsApi, GetHostsApi, DeleteHostsApi, ListHostsApi,
-         GetReservationApi, DeleteReservationApi, CreateReservationsApi, ExtendReservationsApi, DeleteRelatedReservationsApi);
+         CreateHostCredsApi, ListHostCredsApi, DeleteHostCredsApi, UpdateHostCredsSecretApi,
+         GetReservationApi, DeleteReservationApi, CreateReservationsApi, ExtendReservationsApi, DeleteRelatedReservationsApi,
+         ListAuditApi, GetReaperStatsApi, GetTenantCaApi, GetKeyPoolStatsApi,
+         RotatePubkeyApi, CreateHostGroupsApi, GetHostGroupsApi, DeleteHostGroupsApi, GetPubkeysKrlApi,
+         GetKeyTypesApi, CreateAdminApi, GetAdminApi, ListAdminApi, UpdateAdminApi, DeleteAdminApi,
+         UpdateAdminSecretApi, ResetAdminSecretApi, CreateUserCredsApi, ListUserCredsApi, DeleteUserCredsApi, UpdateUserCredsSecretApi,
+         CreateCertMappingsApi, ListCertMappingsApi, DeleteCertMappingsApi);
     let mut api_service = 
         OpenApiService::new(endpoints, "TMS Server", version_str);
     let urls = &RUNTIME_CTX.parms.config.server_urls;
@@ -194,6 +238,21 @@ async fn main() -> Result<(), std::io::Error> {
         // ** HTTPS: We expect the certificate and key to be in the external data directory.
         let key = RUNTIME_CTX.tms_dirs.certs_dir.clone() + TMSS_KEY_FILE;
         let cert = RUNTIME_CTX.tms_dirs.certs_dir.clone() + TMSS_CERT_FILE;
+
+        // ** Mutual TLS: Client certificates are verified against the configured CA bundle.
+        let mtls = &RUNTIME_CTX.parms.config.mtls;
+        if mtls.enabled {
+            let ca = mtls.get_ca_file_path(&RUNTIME_CTX.tms_dirs.certs_dir);
+            let tls_acceptor = create_ssl_acceptor(&cert, &key, &ca, mtls.required)
+                .expect("FAILED to configure mutual TLS.");
+            let acceptor = TcpListener::bind(addr).into_acceptor().await?;
+            return poem::Server::new_with_acceptor(MtlsAcceptor::new(acceptor, tls_acceptor))
+                .name(SERVER_NAME)
+                .idle_timeout(Duration::from_secs(50))
+                .run(app)
+                .await;
+        }
+
         poem::Server::new(
             TcpListener::bind(addr).openssl_tls(
                 OpensslTlsConfig::new()
@@ -249,6 +308,18 @@ fn tms_init2() {
             panic!("Unable to set the {} tenant's enabled flag to match the enable_test_tenant configuration. \
                     Aborting server execution.", tenant);
         });
+
+    // Encrypt the certificate authority keys stored before they were encrypted.
+    let encrypted = block_on(ssh_ca::encrypt_tenant_cas()).unwrap_or_else(|e| {
+        panic!("Unable to encrypt the tenant certificate authority keys: {}. Aborting server execution.", e);
+    });
+    if encrypted > 0 {info!("Encrypted {} tenant certificate authority keys.", encrypted);}
+
+    // Start the background task that removes expired records.
+    reaper::start_reaper();
+
+    // Start the background task that fills the pre-generated key pool.
+    key_pool::start_key_pool();
 }
 
 // ---------------------------------------------------------------------------
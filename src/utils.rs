@@ -7,4 +7,13 @@ pub mod db_init;
 pub mod db_types;
 pub mod db_statements;
 pub mod keygen;
-pub mod mvp;
\ No newline at end of file
+pub mod mvp;
+pub mod addr_match;
+pub mod query_builder;
+pub mod reaper;
+pub mod ssh_ca;
+pub mod key_encryption;
+pub mod key_pool;
+pub mod krl;
+pub mod oidc;
+pub mod mtls;
\ No newline at end of file
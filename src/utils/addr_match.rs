@@ -0,0 +1,209 @@
+#![forbid(unsafe_code)]
+
+use std::net::IpAddr;
+
+use anyhow::{Result, anyhow};
+
+// ***************************************************************************
+//                                Address Patterns
+// ***************************************************************************
+// The addr column in the hosts table specifies the network addresses from which
+// a host is allowed to contact TMS.  These forms are accepted:
+//
+//  Single address:   10.1.2.3 or fd00::1
+//  IPv4 wildcard:    10.1.* or 10.1.2.* (at least 2 segments, the last is *)
+//  Inclusive range:  [10.1.2.3, 10.1.2.200] or [fd00::1, fd00::ff]
+//  CIDR block:       10.1.0.0/16 or fd00::/8
+//
+// Ranges must use addresses of the same family with the first address not
+// greater than the second.
+// ---------------------------------------------------------------------------
+// AddrPattern:
+// ---------------------------------------------------------------------------
+#[derive(Debug, PartialEq)]
+pub enum AddrPattern {
+    Exact(IpAddr),
+    Wildcard(Vec<u8>),      // leading IPv4 octets that must match
+    Range(IpAddr, IpAddr),
+    Cidr(IpAddr, u8),
+}
+
+impl AddrPattern {
+    /** Parse an address pattern as stored in the hosts table.  An error is returned
+     * that describes why the pattern is invalid.
+     */
+    pub fn parse(pattern: &str) -> Result<AddrPattern> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            return Err(anyhow!("Empty host address."));
+        }
+
+        // Range.
+        if pattern.starts_with('[') {
+            return Self::parse_range(pattern);
+        }
+
+        // CIDR block.
+        if let Some((addr, prefix)) = pattern.split_once('/') {
+            return Self::parse_cidr(pattern, addr, prefix);
+        }
+
+        // IPv4 wildcard.
+        if pattern.ends_with('*') {
+            return Self::parse_wildcard(pattern);
+        }
+
+        // Single address.
+        match pattern.parse::<IpAddr>() {
+            Ok(ip) => Ok(AddrPattern::Exact(ip)),
+            Err(_) => Err(anyhow!("Invalid host address '{}'.", pattern)),
+        }
+    }
+
+    /** Determine whether the address satisfies this pattern.  IPv4 addresses
+     * embedded in IPv6 addresses (::ffff:a.b.c.d) are treated as IPv4.
+     */
+    pub fn matches(&self, addr: &IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        match self {
+            AddrPattern::Exact(ip) => ip.to_canonical() == addr,
+            AddrPattern::Wildcard(octets) => match addr {
+                IpAddr::V4(v4) => v4.octets().starts_with(octets),
+                IpAddr::V6(_) => false,
+            },
+            AddrPattern::Range(first, last) => {
+                match (first, last, addr) {
+                    (IpAddr::V4(f), IpAddr::V4(l), IpAddr::V4(a)) => *f <= a && a <= *l,
+                    (IpAddr::V6(f), IpAddr::V6(l), IpAddr::V6(a)) => *f <= a && a <= *l,
+                    _ => false,
+                }
+            },
+            AddrPattern::Cidr(net, prefix) => {
+                match (net, addr) {
+                    (IpAddr::V4(n), IpAddr::V4(a)) => {
+                        let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                        (u32::from(*n) & mask) == (u32::from(a) & mask)
+                    },
+                    (IpAddr::V6(n), IpAddr::V6(a)) => {
+                        let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                        (u128::from(*n) & mask) == (u128::from(a) & mask)
+                    },
+                    _ => false,
+                }
+            },
+        }
+    }
+
+    // ----- Parsing helpers.
+    fn parse_range(pattern: &str) -> Result<AddrPattern> {
+        let inner = match pattern.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(s) => s,
+            None => return Err(anyhow!("Invalid host address range '{}', expected [addr1, addr2].", pattern)),
+        };
+        let (first, last) = match inner.split_once(',') {
+            Some((f, l)) => (f.trim(), l.trim()),
+            None => return Err(anyhow!("Invalid host address range '{}', expected [addr1, addr2].", pattern)),
+        };
+        let first = first.parse::<IpAddr>()
+            .map_err(|_| anyhow!("Invalid first address in host address range '{}'.", pattern))?;
+        let last = last.parse::<IpAddr>()
+            .map_err(|_| anyhow!("Invalid second address in host address range '{}'.", pattern))?;
+
+        // Both addresses must be of the same family and in ascending order.
+        let ordered = match (first, last) {
+            (IpAddr::V4(f), IpAddr::V4(l)) => f <= l,
+            (IpAddr::V6(f), IpAddr::V6(l)) => f <= l,
+            _ => return Err(anyhow!("Host address range '{}' mixes IPv4 and IPv6 addresses.", pattern)),
+        };
+        if !ordered {
+            return Err(anyhow!("The first address in host address range '{}' is greater than the second.", pattern));
+        }
+        Ok(AddrPattern::Range(first, last))
+    }
+
+    fn parse_cidr(pattern: &str, addr: &str, prefix: &str) -> Result<AddrPattern> {
+        let net = addr.parse::<IpAddr>()
+            .map_err(|_| anyhow!("Invalid network address in CIDR block '{}'.", pattern))?;
+        let prefix = prefix.parse::<u8>()
+            .map_err(|_| anyhow!("Invalid prefix length in CIDR block '{}'.", pattern))?;
+        let max_prefix = if net.is_ipv4() {32} else {128};
+        if prefix > max_prefix {
+            return Err(anyhow!("Prefix length in CIDR block '{}' exceeds {}.", pattern, max_prefix));
+        }
+        Ok(AddrPattern::Cidr(net, prefix))
+    }
+
+    fn parse_wildcard(pattern: &str) -> Result<AddrPattern> {
+        // Only the last of 2 to 4 segments can be an asterisk.
+        let segments: Vec<&str> = pattern.split('.').collect();
+        if segments.len() < 2 || segments.len() > 4 {
+            return Err(anyhow!("Invalid wildcard host address '{}', expected 2 to 4 IPv4 segments.", pattern));
+        }
+        let mut octets = Vec::with_capacity(3);
+        for seg in &segments[..segments.len() - 1] {
+            match seg.parse::<u8>() {
+                Ok(o) => octets.push(o),
+                Err(_) => return Err(anyhow!("Invalid segment '{}' in wildcard host address '{}'.", seg, pattern)),
+            }
+        }
+        if segments[segments.len() - 1] != "*" {
+            return Err(anyhow!("Invalid wildcard host address '{}', only the last segment can be *.", pattern));
+        }
+        Ok(AddrPattern::Wildcard(octets))
+    }
+}
+
+// ***************************************************************************
+//                             Public Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// any_addr_matches:
+// ---------------------------------------------------------------------------
+/** Return true if the address satisfies at least one of the patterns.  Patterns
+ * that cannot be parsed never match.
+ */
+pub fn any_addr_matches(patterns: &[String], addr: &IpAddr) -> bool {
+    patterns.iter().any(|p| match AddrPattern::parse(p) {
+        Ok(pattern) => pattern.matches(addr),
+        Err(_) => false,
+    })
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr { s.parse().unwrap() }
+
+    #[test]
+    fn parse_valid_patterns() {
+        assert_eq!(AddrPattern::parse("10.1.2.3").unwrap(), AddrPattern::Exact(ip("10.1.2.3")));
+        assert_eq!(AddrPattern::parse("10.1.*").unwrap(), AddrPattern::Wildcard(vec![10, 1]));
+        assert_eq!(AddrPattern::parse("[10.0.0.1, 10.0.0.9]").unwrap(),
+                   AddrPattern::Range(ip("10.0.0.1"), ip("10.0.0.9")));
+        assert_eq!(AddrPattern::parse("fd00::/8").unwrap(), AddrPattern::Cidr(ip("fd00::"), 8));
+    }
+
+    #[test]
+    fn parse_invalid_patterns() {
+        for p in ["", "10.1", "10.*.2", "*", "10.1.2.3.*", "[10.0.0.9, 10.0.0.1]",
+                  "[10.0.0.1, ::1]", "10.0.0.0/33", "host.example.com"] {
+            assert!(AddrPattern::parse(p).is_err(), "{} should not parse", p);
+        }
+    }
+
+    #[test]
+    fn match_patterns() {
+        assert!(AddrPattern::parse("10.1.*").unwrap().matches(&ip("10.1.200.3")));
+        assert!(!AddrPattern::parse("10.1.*").unwrap().matches(&ip("10.2.0.1")));
+        assert!(AddrPattern::parse("[10.0.0.1,10.0.0.9]").unwrap().matches(&ip("::ffff:10.0.0.5")));
+        assert!(AddrPattern::parse("192.168.0.0/16").unwrap().matches(&ip("192.168.4.4")));
+        assert!(!AddrPattern::parse("192.168.0.0/16").unwrap().matches(&ip("192.169.0.1")));
+        assert!(AddrPattern::parse("fd00::/8").unwrap().matches(&ip("fd12::1")));
+        assert!(AddrPattern::parse("0.0.0.0/0").unwrap().matches(&ip("8.8.8.8")));
+        assert!(!AddrPattern::parse("::1").unwrap().matches(&ip("127.0.0.1")));
+    }
+}
@@ -4,9 +4,13 @@ use poem::Request;
 use sqlx::Row;
 use anyhow::{Result, anyhow};
 
-use log::{error, debug};
+use log::{error, debug, info};
 
-use crate::utils::tms_utils::hash_hex_secret;
+use crate::utils::tms_utils::{hash_secret, verify_secret, is_legacy_hash};
+use crate::utils::oidc::{BEARER_PREFIX, get_claim_name, validate_bearer_token};
+use crate::utils::mtls::{get_peer_cert_names, CERT_SUBJECT_CLIENT, CERT_SUBJECT_HOST};
+use crate::utils::db_statements::GET_CERT_MAPPED_IDS;
+use crate::utils::config::AuthzSpec;
 use crate::RUNTIME_CTX;
 
 // ***************************************************************************
@@ -18,13 +22,17 @@ pub const X_TMS_ADMIN_ID:      &str = "X-TMS-ADMIN-ID";
 pub const X_TMS_ADMIN_SECRET:  &str = "X-TMS-ADMIN-SECRET";
 pub const X_TMS_CLIENT_ID:     &str = "X-TMS-CLIENT-ID";
 pub const X_TMS_CLIENT_SECRET: &str = "X-TMS-CLIENT-SECRET";
+pub const X_TMS_HOST_ID:       &str = "X-TMS-HOST-ID";
+pub const X_TMS_HOST_SECRET:   &str = "X-TMS-HOST-SECRET";
+pub const X_TMS_USER_ID:       &str = "X-TMS-USER-ID";
+pub const X_TMS_USER_SECRET:   &str = "X-TMS-USER-SECRET";
+pub const AUTHORIZATION:       &str = "Authorization";
 
 // The different types of authorizations that can be checked.  Each implemented
 // authz type is configured with a AuthzSpec that is stored in the static
 // AuthzArgs component of RUNTIME_CTX (see config.rs for details).
 #[derive(Debug, PartialEq, Eq, Hash)]
-#[allow(dead_code)]
-pub enum AuthzTypes {ClientOwn, TenantAdmin, TmsctlHost, UserOwn}
+pub enum AuthzTypes {ClientOwn, SiteAdmin, TenantAdmin, TmsctlHost, UserOwn}
 
 // ***************************************************************************
 //                            Result Struct
@@ -90,6 +98,8 @@ impl AuthzResult {
             Some(atype) => {
                 match atype {
                     AuthzTypes::ClientOwn => self.check_client_own(req_id),
+                    AuthzTypes::TmsctlHost => self.check_host_own(req_id),
+                    AuthzTypes::UserOwn => self.check_user_own(req_id),
                     _ => true  // non-client authorization (ex: admin)
                 }
             },
@@ -97,6 +107,31 @@ impl AuthzResult {
         }
     }
 
+    /** Check that administrator credentials were used to authorize a request and that the
+     * admin id used in the credentials is the same as the admin provided in the request.
+     * Unlike check_hdr_id(), which accepts any administrator, this check guarantees that an
+     * admin can only act on its own admin record, such as when rotating its own secret.
+     *  
+     * Return FALSE if the caller is not an admin or the admin ids don't match, return TRUE
+     * otherwise.
+     */
+    pub fn check_admin_own(&self, req_admin_user: &String) -> bool {
+        // Guard for unauthorized calls, which should never happen.
+        if !self.authorized {return false;}
+
+        // Only administrator authorizations identify an admin record.
+        match &self.authz_type {
+            Some(AuthzTypes::SiteAdmin) | Some(AuthzTypes::TenantAdmin) => (),
+            _ => return false,
+        }
+
+        // Make sure the admin id from header and request match.
+        match &self.hdr_id {
+            Some(id) => req_admin_user == id,
+            None => false,
+        }
+    }
+
     /** Check that if client credentials were used to authorize a request that the client id 
      * used in the credentials is the same as that provided in the request.  This consistency
      * check guarentees that client id provided in the http request header are the same as 
@@ -115,6 +150,42 @@ impl AuthzResult {
         if req_client_id == hdr_id {true} // client match
          else {false} // client mismatch
     }
+
+    /** Check that if host credentials were used to authorize a request that the host id
+     * used in the credentials is the same as the host provided in the request.  This 
+     * guarantees that a host can only act on its own behalf, such as when retrieving the
+     * public keys issued for logins to that host.
+     *  
+     * Return FALSE if the host ids don't match, return TRUE otherwise.
+     */
+    fn check_host_own(&self, req_host: &String) -> bool {
+        // Get the authz id passed in as a header.
+        let hdr_id = match &self.hdr_id {
+            Some(id) => id,
+            None => return false,
+        };
+
+        // Make sure the host id from header and request match.
+        req_host == hdr_id
+    }
+
+    /** Check that if user credentials were used to authorize a request that the tms user
+     * id used in the credentials is the same as the user provided in the request.  This
+     * guarantees that end users can only access their own MFA, host account, delegation 
+     * and public key records.
+     *  
+     * Return FALSE if the user ids don't match, return TRUE otherwise.
+     */
+    fn check_user_own(&self, req_user: &String) -> bool {
+        // Get the authz id passed in as a header.
+        let hdr_id = match &self.hdr_id {
+            Some(id) => id,
+            None => return false,
+        };
+
+        // Make sure the user id from header and request match.
+        req_user == hdr_id
+    }
 }
 
 // ***************************************************************************
@@ -170,6 +241,7 @@ pub async fn authorize(http_req: &Request, allowed: &[AuthzTypes]) -> AuthzResul
     for authz_type in allowed {
         let result = match authz_type {
             AuthzTypes::ClientOwn => authorize_by_type(http_req, hdr_tenant, AuthzTypes::ClientOwn).await,
+            AuthzTypes::SiteAdmin => authorize_by_type(http_req, hdr_tenant, AuthzTypes::SiteAdmin).await,
             AuthzTypes::TenantAdmin => authorize_by_type(http_req, hdr_tenant, AuthzTypes::TenantAdmin).await,
             AuthzTypes::TmsctlHost => authorize_by_type(http_req, hdr_tenant, AuthzTypes::TmsctlHost).await,
             AuthzTypes::UserOwn => authorize_by_type(http_req, hdr_tenant, AuthzTypes::UserOwn).await,
@@ -256,6 +328,17 @@ async fn authorize_by_type(http_req: &Request, hdr_tenant: &str, authz_type: Aut
         None => "",
     };
 
+    // Callers that don't pass a secret can authorize using an OIDC bearer token
+    // or a verified client certificate.
+    if hdr_secret.is_empty() {
+        if let Some(token) = get_bearer_token(http_req) {
+            return authorize_by_token(token, hdr_tenant, authz_type, spec).await;
+        }
+        if let Some(cert_names) = get_peer_cert_names(http_req) {
+            return authorize_by_cert(&cert_names, hdr_id, hdr_tenant, authz_type, spec).await;
+        }
+    }
+
     // Do we have the complete set of tenant, id and secret?
     if hdr_tenant.is_empty() || hdr_id.is_empty() || hdr_secret.is_empty() {
         debug!("Missing header information for {} id {}", spec.display_name, hdr_id);
@@ -272,12 +355,150 @@ async fn authorize_by_type(http_req: &Request, hdr_tenant: &str, authz_type: Aut
     };
 
     // Compare the header secret to the hashed secret from the database.
-    let hdr_secret_hash = hash_hex_secret(&hdr_secret.to_string());
-    if hdr_secret_hash == db_secret_hash {
-        AuthzResult::new_authorized(authz_type, hdr_id.to_string(), hdr_tenant.to_string())  // Authorized
-    } else {
-        error!("Invalid secret given for {} {} in tenant {}", spec.display_name, hdr_id, hdr_tenant);
-        AuthzResult::new_unauthorized() // Not authorized
+    match verify_secret(hdr_secret, &db_secret_hash).await {
+        Ok(true) => {
+            // Replace legacy hashes now that we know the secret.
+            if is_legacy_hash(&db_secret_hash) {
+                upgrade_secret_hash(hdr_id, hdr_tenant, hdr_secret, &db_secret_hash, spec).await;
+            }
+            AuthzResult::new_authorized(authz_type, hdr_id.to_string(), hdr_tenant.to_string())  // Authorized
+        },
+        Ok(false) => {
+            error!("Invalid secret given for {} {} in tenant {}", spec.display_name, hdr_id, hdr_tenant);
+            AuthzResult::new_unauthorized() // Not authorized
+        },
+        Err(e) => {
+            error!("Unable to verify secret for {} {} in tenant {}: {}", spec.display_name, hdr_id, hdr_tenant, e);
+            AuthzResult::new_unauthorized() // Not authorized
+        },
+    }
+}
+
+// ---------------------------------------------------------------------------
+// upgrade_secret_hash:
+// ---------------------------------------------------------------------------
+/** Replace a legacy SHA-512 secret hash with an Argon2id hash after the secret
+ * was verified.  Failures are logged but don't affect authorization; the upgrade
+ * is retried on the next successful authorization.
+ */
+async fn upgrade_secret_hash(id: &str, tenant: &str, secret: &str, old_hash: &str, spec: &AuthzSpec<'static>) {
+    let result = match hash_secret(secret).await {
+        Ok(new_hash) => update_authz_secret(id, tenant, &new_hash, old_hash, spec.upgrade_query).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(_) => info!("Upgraded secret hash for {} {} in tenant {}", spec.display_name, id, tenant),
+        Err(e) => error!("Unable to upgrade secret hash for {} {} in tenant {}: {}", spec.display_name, id, tenant, e),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// authorize_by_token:
+// ---------------------------------------------------------------------------
+/** Authorize using a bearer token issued by the tenant's configured identity
+ * provider.  The id taken from the token must belong to an existing subject of
+ * the authz type, which is checked using the same query as secret-based 
+ * authorization.  The result is indistinguishable from a secret-based result.
+ */
+async fn authorize_by_token(token: &str, hdr_tenant: &str, authz_type: AuthzTypes, spec: &AuthzSpec<'static>) -> AuthzResult {
+    // Is this tenant configured for bearer tokens and the authz type?
+    let (cfg, jwks) = match (RUNTIME_CTX.parms.config.oidc.tenants.get(hdr_tenant), RUNTIME_CTX.jwks.get(hdr_tenant)) {
+        (Some(c), Some(j)) => (c, j),
+        _ => {
+            debug!("Bearer tokens are not accepted in tenant {}", hdr_tenant);
+            return AuthzResult::new_unauthorized();
+        },
+    };
+    let claim = match get_claim_name(cfg, &authz_type) {
+        Some(c) => c,
+        None => {
+            debug!("Bearer tokens cannot authorize a {} in tenant {}", spec.display_name, hdr_tenant);
+            return AuthzResult::new_unauthorized();
+        },
+    };
+
+    // Validate the token and get the id it carries.
+    let id = match validate_bearer_token(token, cfg, jwks, claim) {
+        Ok(id) => id,
+        Err(e) => {
+            error!("Invalid bearer token given for {} in tenant {}: {}", spec.display_name, hdr_tenant, e);
+            return AuthzResult::new_unauthorized();
+        },
+    };
+
+    // Make sure the id is known to TMS.
+    match get_authz_secret(&id, hdr_tenant, spec.sql_query).await {
+        Ok(_) => AuthzResult::new_authorized(authz_type, id, hdr_tenant.to_string()),  // Authorized
+        Err(e) => {
+            error!("Unable to authorize {} ID '{}' from bearer token: {}", spec.display_name, id, e);
+            AuthzResult::new_unauthorized()
+        },
+    }
+}
+
+// ---------------------------------------------------------------------------
+// authorize_by_cert:
+// ---------------------------------------------------------------------------
+/** Authorize using the names in the connection's verified client certificate,
+ * which are mapped to clients and hosts in the cert_mappings table.  When the
+ * caller also passes an id header, the id must be mapped to the certificate; 
+ * otherwise the certificate must map to exactly one id.  Clients must also 
+ * exist.  The result is indistinguishable from a secret-based result.
+ */
+async fn authorize_by_cert(cert_names: &[String], hdr_id: &str, hdr_tenant: &str, 
+                           authz_type: AuthzTypes, spec: &AuthzSpec<'static>) -> AuthzResult {
+    // Only clients and hosts can be mapped to certificates.
+    let subject_type = match authz_type {
+        AuthzTypes::ClientOwn => CERT_SUBJECT_CLIENT,
+        AuthzTypes::TmsctlHost => CERT_SUBJECT_HOST,
+        _ => {
+            debug!("Client certificates cannot authorize a {} in tenant {}", spec.display_name, hdr_tenant);
+            return AuthzResult::new_unauthorized();
+        },
+    };
+
+    // Get the ids mapped to the certificate.
+    let ids = match get_cert_mapped_ids(hdr_tenant, subject_type, cert_names).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            error!("Unable to retrieve {} ids mapped to client certificate {:?}: {}", spec.display_name, cert_names, e);
+            return AuthzResult::new_unauthorized();
+        },
+    };
+    let id = 
+        if !hdr_id.is_empty() {
+            if ids.iter().any(|id| id == hdr_id) {hdr_id.to_string()}
+            else {
+                error!("The client certificate {:?} is not mapped to {} {} in tenant {}", 
+                       cert_names, spec.display_name, hdr_id, hdr_tenant);
+                return AuthzResult::new_unauthorized();
+            }
+        } else if ids.len() == 1 {ids[0].clone()}
+        else {
+            debug!("The client certificate {:?} is mapped to {} {} ids in tenant {}", 
+                   cert_names, ids.len(), spec.display_name, hdr_tenant);
+            return AuthzResult::new_unauthorized();
+        };
+
+    // Make sure the client is known to TMS.
+    if authz_type == AuthzTypes::ClientOwn {
+        if let Err(e) = get_authz_secret(&id, hdr_tenant, spec.sql_query).await {
+            error!("Unable to authorize {} ID '{}' from client certificate: {}", spec.display_name, id, e);
+            return AuthzResult::new_unauthorized();
+        }
+    }
+    AuthzResult::new_authorized(authz_type, id, hdr_tenant.to_string())  // Authorized
+}
+
+// ---------------------------------------------------------------------------
+// get_bearer_token:
+// ---------------------------------------------------------------------------
+/** Get the token from the Authorization header if the header uses the Bearer scheme. */
+fn get_bearer_token(http_req: &Request) -> Option<&str> {
+    let value = http_req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    match value.strip_prefix(BEARER_PREFIX) {
+        Some(token) if !token.trim().is_empty() => Some(token.trim()),
+        _ => None,
     }
 }
 
@@ -342,4 +563,44 @@ async fn get_authz_secret(id: &str, tenant: &str, sql_query: &str) -> Result<Str
     }
 }
 
+// ---------------------------------------------------------------------------
+// get_cert_mapped_ids:
+// ---------------------------------------------------------------------------
+async fn get_cert_mapped_ids(tenant: &str, subject_type: &str, cert_names: &[String]) -> Result<Vec<String>> {
+    // Get a connection to the db and start a transaction.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    let rows = sqlx::query(GET_CERT_MAPPED_IDS)
+        .bind(tenant)
+        .bind(subject_type)
+        .bind(cert_names)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+// ---------------------------------------------------------------------------
+// update_authz_secret:
+// ---------------------------------------------------------------------------
+async fn update_authz_secret(id: &str, tenant: &str, new_hash: &str, old_hash: &str, sql_query: &str) -> Result<u64> {
+    // Get a connection to the db and start a transaction.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
 
+    // The update query takes the new hash, id, tenant and old hash.  Matching
+    // the old hash avoids overwriting a secret that changed in the meantime.
+    let result = sqlx::query(sql_query)
+        .bind(new_hash)
+        .bind(id)
+        .bind(tenant)
+        .bind(old_hash)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
@@ -15,6 +15,7 @@ use users::get_effective_uid;
 use poem::web::{Data};
 use sqlx::{Pool, Postgres};
 use clap::{Parser};
+use jsonwebtoken::jwk::JwkSet;
 // See https://users.rust-lang.org/t/relationship-between-std-futures-futures-and-tokio/38077
 // for a cogent explanation on dealing with futures and async programming in Rust.  More 
 // background can be found at https://rust-lang.github.io/async-book/.
@@ -23,10 +24,15 @@ use futures::executor::block_on;
 // TMS Utilities
 use crate::utils::{tms_utils, db_init, errors::Errors};
 use crate::v1::tms::pubkeys_get::RespGetPubkeys;
-use super::db_statements::{GET_CLIENT_SECRET, GET_ADMIN_SECRET};
-use super::authz::{AuthzTypes, X_TMS_ADMIN_ID, X_TMS_ADMIN_SECRET, X_TMS_CLIENT_ID, X_TMS_CLIENT_SECRET};
+use super::db_statements::{GET_CLIENT_SECRET, GET_ADMIN_SECRET, GET_HOST_SECRET, GET_SITE_ADMIN_SECRET,
+                           GET_USER_SECRET, UPGRADE_CLIENT_SECRET, UPGRADE_ADMIN_SECRET, UPGRADE_HOST_SECRET,
+                           UPGRADE_USER_SECRET};
+use super::authz::{AuthzTypes, X_TMS_ADMIN_ID, X_TMS_ADMIN_SECRET, X_TMS_CLIENT_ID, X_TMS_CLIENT_SECRET,
+                   X_TMS_HOST_ID, X_TMS_HOST_SECRET, X_TMS_USER_ID, X_TMS_USER_SECRET};
 
 use super::tms_utils::get_absolute_path;
+use super::keygen::validate_key_specs;
+use super::oidc::load_jwks;
 
 // ***************************************************************************
 //                                Constants
@@ -44,6 +50,7 @@ const LOG4RS_CONFIG_FILE   : &str = "/log4rs.yml"; // relative to config dir
 const TMS_CONFIG_FILE      : &str = "/tms.toml";   // relative to config dir
 const CERT_PEM_FILE        : &str = "/cert.pem";   // relative to certs dir
 const KEY_PEM_FILE         : &str = "/key.pem";    // relative to certs dir
+const DB_ENCRYPTION_KEY_FILE: &str = "/db_encryption.key"; // relative to certs dir
 
 // Networking.
 const DEFAULT_HTTP_ADDR    : &str = "https://localhost";
@@ -55,10 +62,19 @@ pub const DEFAULT_TENANT   : &str = "default";
 pub const TEST_TENANT      : &str = "test";
 
 // Admin table constants.
-#[allow(dead_code)]
 pub const ADMIN_ID_PREFIX  : &str = "~~"; // literal value repeated in next line
 pub const DEFAULT_ADMIN_ID : &str = concat!("~~", "admin"); // admin ids always start with prefix
 pub const PERM_ADMIN       : &str = "PERM_ADMIN";
+pub const ADMIN_PRIVILEGES : [&str; 2] = [PERM_ADMIN, PERM_SITE_ADMIN];
+
+// The site administrator is an admin in the default tenant with the site admin privilege.
+// Only site admins can create, delete, wipe or list all tenants.  These literal values
+// are repeated in the site admin queries in db_statements.rs.
+pub const SITE_ADMIN_ID    : &str = concat!("~~", "siteadmin");
+pub const PERM_SITE_ADMIN  : &str = "PERM_SITE_ADMIN";
+
+// Wildcard user and host account in the user_hosts and delegations tables.
+pub const WILDCARD         : &str = "*";
 
 // New client creation is allowed by default.
 pub const NEW_CLIENTS_ALLOW: &str = "allow";
@@ -66,6 +82,14 @@ pub const NEW_CLIENTS_DISALLOW: &str = "disallow";
 pub const NEW_CLIENTS_ON_APPROVAL: &str = "on_approval";
 pub const DEFAULT_NEW_CLIENTS: &str = NEW_CLIENTS_ALLOW;
 
+// Reaper defaults.
+const DEFAULT_REAPER_INTERVAL_MINUTES: u32 = 60;
+const DEFAULT_REAPER_GRACE_MINUTES   : u32 = 7 * 24 * 60;
+const DEFAULT_REAPER_BATCH_SIZE      : u32 = 1000;
+
+// Mutual TLS defaults.
+const DEFAULT_MTLS_CA_FILE: &str = "ca.pem"; // relative to certs dir
+
 // Env variable names
 const ENV_TMS_ROOT_DIR     : &str = "TMS_ROOT_DIR";
 const ENV_TMS_DB_HOST       : &str = "TMS_DB_HOST";
@@ -179,6 +203,12 @@ pub struct TmsCmdArgs {
     /// Display the version and exit
     #[arg(short, long)]
     pub version: bool,
+    /// Set a new secret for the site administrator, enable it, display the secret and exit.
+    ///
+    /// Use after upgrading an installation that predates the site administrator
+    /// or when the site administrator's secret is lost.
+    #[arg(long)]
+    pub reset_site_admin: bool,
     /// Specify TMS root install directory.
     ///
     /// This directory contains all the files TMS uses during execution.
@@ -207,6 +237,7 @@ pub struct AuthzSpec<'a>{
     pub secret: &'a str,       // HTTP header for secret used to authorize
     pub display_name: &'a str, // User-friendly name of subject being authorized
     pub sql_query: &'a str,    // SQL query with required signature for secret retrieval 
+    pub upgrade_query: &'a str, // SQL query with required signature for legacy hash replacement
 }
 
 #[derive(Debug)]
@@ -224,6 +255,21 @@ pub struct RuntimeCtx {
     pub authz: &'static AuthzArgs,
     pub tms_cmd_args: &'static TmsCmdArgs,
     pub tms_dirs: &'static TmsDirs,
+    pub jwks: HashMap<String, JwkSet>,
+    pub db_encryption_key: DbEncryptionKey,
+}
+
+// ---------------------------------------------------------------------------
+// DbEncryptionKey:
+// ---------------------------------------------------------------------------
+// The AES-256 key that encrypts private keys stored in the database, such as the
+// tenant CA keys.  The key is never logged.
+pub struct DbEncryptionKey(pub [u8; 32]);
+
+impl std::fmt::Debug for DbEncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DbEncryptionKey(<redacted>)")
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -237,7 +283,15 @@ pub struct Config {
     pub enable_mvp: bool,
     pub enable_test_tenant: bool,
     pub new_clients: String,
-    pub server_urls: Vec<String>
+    pub server_urls: Vec<String>,
+    #[serde(default)]
+    pub reaper: ReaperConfig,
+    #[serde(default)]
+    pub key_pool: KeyPoolConfig,
+    #[serde(default)]
+    pub oidc: OidcConfig,
+    #[serde(default)]
+    pub mtls: MtlsConfig,
 }
 
 impl Config {
@@ -249,20 +303,20 @@ impl Config {
     // Validation beyond type checking.
     fn validate(&self) -> Result<()> {
         match self.new_clients.as_str() {
-            NEW_CLIENTS_ALLOW => Ok(()),
-            NEW_CLIENTS_DISALLOW => Ok(()),
-            NEW_CLIENTS_ON_APPROVAL =>  {
-                let msg = "The new_clients 'on_approval' setting is not implemented yet.";
-                error!("{}", msg);
-                Err(anyhow!(msg))
-            },           
+            NEW_CLIENTS_ALLOW => (),
+            NEW_CLIENTS_DISALLOW => (),
+            NEW_CLIENTS_ON_APPROVAL => (),
             other => {
                 let msg = format!("Invalid value '{}' assigned to the new_clients configuration setting.  \
-                                          Currently supported values are: 'allow', 'disallow'.", other);
+                                          Currently supported values are: 'allow', 'disallow', 'on_approval'.", other);
                 error!("{}", msg);
-                Err(anyhow!(msg))
+                return Err(anyhow!(msg));
             },
         } 
+        self.reaper.validate()?;
+        self.key_pool.validate()?;
+        self.oidc.validate()?;
+        self.mtls.validate(&self.http_addr)
     }
 }
 
@@ -275,7 +329,155 @@ impl Default for Config {
             enable_mvp: false,
             enable_test_tenant: false,
             new_clients: DEFAULT_NEW_CLIENTS.to_string(),
-            server_urls: vec![DEFAULT_SVR_URL.to_string()]
+            server_urls: vec![DEFAULT_SVR_URL.to_string()],
+            reaper: ReaperConfig::default(),
+            key_pool: KeyPoolConfig::default(),
+            oidc: OidcConfig::default(),
+            mtls: MtlsConfig::default(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ReaperConfig:
+// ---------------------------------------------------------------------------
+// The [reaper] section of tms.toml.  Every setting is optional.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ReaperConfig {
+    pub enabled: bool,
+    pub interval_minutes: u32,
+    pub grace_minutes: u32,
+    pub batch_size: u32,
+    pub archive: bool,
+}
+
+impl ReaperConfig {
+    // Validation beyond type checking.
+    fn validate(&self) -> Result<()> {
+        if self.interval_minutes == 0 || self.batch_size == 0 {
+            let msg = "The reaper interval_minutes and batch_size configuration settings must be greater than zero.".to_string();
+            error!("{}", msg);
+            return Err(anyhow!(msg));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ReaperConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: DEFAULT_REAPER_INTERVAL_MINUTES,
+            grace_minutes: DEFAULT_REAPER_GRACE_MINUTES,
+            batch_size: DEFAULT_REAPER_BATCH_SIZE,
+            archive: false,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// KeyPoolConfig:
+// ---------------------------------------------------------------------------
+// The [key_pool] section of tms.toml.  The sizes table maps key type names,
+// such as rsa-4096, to the number of pre-generated keys kept for that type.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct KeyPoolConfig {
+    pub enabled: bool,
+    pub sizes: HashMap<String, u32>,
+}
+
+impl KeyPoolConfig {
+    // Validation beyond type checking.
+    fn validate(&self) -> Result<()> {
+        let key_types: Vec<String> = self.sizes.keys().cloned().collect();
+        if let Err(e) = validate_key_specs(&key_types) {
+            let msg = format!("Invalid key_pool sizes configuration setting: {}", e);
+            error!("{}", msg);
+            return Err(anyhow!(msg));
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// OidcConfig:
+// ---------------------------------------------------------------------------
+// The [oidc] section of tms.toml.  The tenants table maps tenant names to the
+// identity provider whose bearer tokens are accepted in that tenant.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OidcConfig {
+    pub tenants: HashMap<String, OidcTenantConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcTenantConfig {
+    pub issuer: String,
+    pub audience: String,
+    pub jwks_file: Option<String>,
+    pub jwks_url: Option<String>,
+    pub client_claim: Option<String>,
+    pub admin_claim: Option<String>,
+}
+
+impl OidcConfig {
+    // Validation beyond type checking.
+    fn validate(&self) -> Result<()> {
+        for (tenant, cfg) in &self.tenants {
+            let msg = 
+                if cfg.issuer.is_empty() || cfg.audience.is_empty() {
+                    format!("The oidc issuer and audience configuration settings must be set for tenant {}.", tenant)
+                } else if cfg.jwks_file.is_some() == cfg.jwks_url.is_some() {
+                    format!("Exactly one of the oidc jwks_file or jwks_url configuration settings must be set for tenant {}.", tenant)
+                } else if cfg.client_claim.is_none() && cfg.admin_claim.is_none() {
+                    format!("At least one of the oidc client_claim or admin_claim configuration settings must be set for tenant {}.", tenant)
+                } else {continue;};
+            error!("{}", msg);
+            return Err(anyhow!(msg));
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// MtlsConfig:
+// ---------------------------------------------------------------------------
+// The [mtls] section of tms.toml.  The ca_file is the CA bundle used to verify
+// client certificates; relative paths are relative to the certs directory.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MtlsConfig {
+    pub enabled: bool,
+    pub required: bool,
+    pub ca_file: String,
+}
+
+impl MtlsConfig {
+    // Validation beyond type checking.
+    fn validate(&self, http_addr: &str) -> Result<()> {
+        if self.enabled && !http_addr.starts_with("https") {
+            let msg = "Mutual TLS can only be enabled when http_addr uses https.".to_string();
+            error!("{}", msg);
+            return Err(anyhow!(msg));
+        }
+        Ok(())
+    }
+
+    // Get the CA bundle's path.
+    pub fn get_ca_file_path(&self, certs_dir: &str) -> String {
+        if self.ca_file.starts_with('/') || self.ca_file.starts_with('~') {self.ca_file.clone()}
+        else {format!("{}/{}", certs_dir, self.ca_file)}
+    }
+}
+
+impl Default for MtlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            required: false,
+            ca_file: DEFAULT_MTLS_CA_FILE.to_string(),
         }
     }
 }
@@ -323,6 +525,12 @@ pub fn set_directories_and_check_install() {
                     ERROR: Option --schema-only may not be used along with --install. \n\
                   ***********************************************************************\n");
     }
+    // Check that --reset-site-admin is used on an existing installation
+    if TMS_CMD_ARGS.reset_site_admin && (TMS_CMD_ARGS.schema_only || TMS_CMD_ARGS.install) {
+        panic!("\n***********************************************************************\n\
+                    ERROR: Option --reset-site-admin may not be used along with --install or --schema-only. \n\
+                  ***********************************************************************\n");
+    }
     // Construct root_dir path and perform checks
     let root_dir = get_root_dir();
     let root_path = Path::new(&root_dir);
@@ -728,24 +936,49 @@ fn get_parms() -> Result<Parms> {
 // ---------------------------------------------------------------------------
 fn init_authz_args() -> AuthzArgs {
     // Create the authz specs for each authz validation type.
-    // Note that only two of the four types are currently implemented.
     let client_spec = AuthzSpec {
         id: X_TMS_CLIENT_ID, 
         secret: X_TMS_CLIENT_SECRET, 
         display_name: "client",
         sql_query: GET_CLIENT_SECRET,
+        upgrade_query: UPGRADE_CLIENT_SECRET,
     };
     let admin_spec = AuthzSpec {
         id: X_TMS_ADMIN_ID, 
         secret: X_TMS_ADMIN_SECRET, 
         display_name: "admin",
         sql_query: GET_ADMIN_SECRET,
+        upgrade_query: UPGRADE_ADMIN_SECRET,
+    };
+    let site_admin_spec = AuthzSpec {
+        id: X_TMS_ADMIN_ID, 
+        secret: X_TMS_ADMIN_SECRET, 
+        display_name: "site admin",
+        sql_query: GET_SITE_ADMIN_SECRET,
+        upgrade_query: UPGRADE_ADMIN_SECRET,
+    };
+    let host_spec = AuthzSpec {
+        id: X_TMS_HOST_ID, 
+        secret: X_TMS_HOST_SECRET, 
+        display_name: "host",
+        sql_query: GET_HOST_SECRET,
+        upgrade_query: UPGRADE_HOST_SECRET,
+    };
+    let user_spec = AuthzSpec {
+        id: X_TMS_USER_ID, 
+        secret: X_TMS_USER_SECRET, 
+        display_name: "user",
+        sql_query: GET_USER_SECRET,
+        upgrade_query: UPGRADE_USER_SECRET,
     };
 
     // Create and fill in the hashmap of authz specs.
     let mut args = AuthzArgs {specs: HashMap::new()};
     args.specs.insert(AuthzTypes::ClientOwn, client_spec);
+    args.specs.insert(AuthzTypes::SiteAdmin, site_admin_spec);
     args.specs.insert(AuthzTypes::TenantAdmin, admin_spec);
+    args.specs.insert(AuthzTypes::TmsctlHost, host_spec);
+    args.specs.insert(AuthzTypes::UserOwn, user_spec);
     args
 }
 
@@ -779,8 +1012,53 @@ pub fn init_runtime_context() -> RuntimeCtx {
     info!("Connecting to DB URL: {}", db_url);
     let db :Pool<Postgres> = block_on(db_init::init_db(db_url.as_str()));
 
+    // Load the signing keys of each tenant's identity provider.
+    let jwks = load_jwks(&parms.config.oidc).expect("FAILED to load OIDC signing keys.");
+
+    // Load or create the key that encrypts private keys stored in the database.
+    let db_encryption_key = load_db_encryption_key(&TMS_DIRS.certs_dir)
+        .expect("FAILED to load the database encryption key.");
+
     // Return the runtime context.
-    RuntimeCtx {parms, db, authz: &AUTHZ_ARGS, tms_cmd_args: &TMS_CMD_ARGS, tms_dirs: &TMS_DIRS}
+    RuntimeCtx {parms, db, authz: &AUTHZ_ARGS, tms_cmd_args: &TMS_CMD_ARGS, tms_dirs: &TMS_DIRS, jwks,
+                db_encryption_key}
+}
+
+// ---------------------------------------------------------------------------
+// load_db_encryption_key:
+// ---------------------------------------------------------------------------
+/** Read the hex encoded database encryption key from the certs directory.  The
+ * key is generated when the file does not exist, such as during installation,
+ * and is only readable by its owner.  The file must be backed up with the
+ * database, since the private keys in the database cannot be used without it.
+ */
+fn load_db_encryption_key(certs_dir: &str) -> Result<DbEncryptionKey> {
+    use rand::RngCore;
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let path = certs_dir.to_string() + DB_ENCRYPTION_KEY_FILE;
+    if !Path::new(&path).exists() {
+        let mut key = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut key);
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path)
+            .map_err(|e| anyhow!("Unable to create database encryption key file {}: {}", path, e))?;
+        file.write_all(hex::encode(key).as_bytes())
+            .map_err(|e| anyhow!("Unable to write database encryption key file {}: {}", path, e))?;
+        info!("Created database encryption key file {}.", path);
+    }
+
+    // The key file must not be readable by others.
+    let meta = fs::metadata(&path).map_err(|e| anyhow!("Unable to read metadata for {}: {}", path, e))?;
+    if meta.permissions().mode() & 0o077 != 0 {
+        return Err(anyhow!("The database encryption key file must only be accessible by its owner: {}", path));
+    }
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Unable to read database encryption key file {}: {}", path, e))?;
+    let key: [u8; 32] = hex::decode(contents.trim()).ok()
+        .and_then(|k| k.try_into().ok())
+        .ok_or_else(|| anyhow!("The database encryption key file must contain 32 hex encoded bytes: {}", path))?;
+    Ok(DbEncryptionKey(key))
 }
 
 // ***************************************************************************
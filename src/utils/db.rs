@@ -4,21 +4,28 @@ use anyhow::{Result, anyhow};
 use log::{info, warn};
 use std::io::{self, Write};
 use chrono::{Utc, DateTime};
-use sqlx::Row;
+use sqlx::{Postgres, Row, Transaction};
 
 use futures::executor::block_on;
 use crate::utils::tms_utils::{timestamp_utc, timestamp_utc_secs_to_str, timestamp_str_to_datetime,
-                              create_hex_secret, hash_hex_secret, MAX_TMS_UTC_STR};
-use crate::utils::db_statements::{INSERT_DELEGATIONS, INSERT_STD_TENANTS, INSERT_USER_HOSTS, INSERT_USER_MFA};
-use crate::utils::config::{DEFAULT_TENANT, TEST_TENANT, DEFAULT_ADMIN_ID, PERM_ADMIN, TMS_CMD_ARGS, DB_TRUE};
+                              create_hex_secret, hash_secret, MAX_TMS_UTC_STR};
+use crate::utils::db_statements::{INSERT_DELEGATIONS, INSERT_HOSTS, INSERT_HOST_CREDS, INSERT_STD_TENANTS, INSERT_USER_HOSTS, INSERT_USER_MFA};
+use crate::utils::config::{DEFAULT_TENANT, TEST_TENANT, DEFAULT_ADMIN_ID, PERM_ADMIN, SITE_ADMIN_ID, PERM_SITE_ADMIN,
+                           TMS_CMD_ARGS, DB_TRUE, WILDCARD};
+use crate::utils::ssh_ca::insert_tenant_ca;
 use log::error;
 
 use crate::RUNTIME_CTX;
 
-use super::db_statements::{GET_DELEGATION_ACTIVE, GET_DELEGATION_EXISTS, GET_RESERVATION_FOR_EXTEND,
+use super::db_statements::{COUNT_OTHER_ENABLED_ADMINS, RESET_SITE_ADMIN, GET_DELEGATION_ACTIVE, GET_DELEGATION_EXISTS, GET_RESERVATION_FOR_EXTEND,
                            GET_USER_HOST_ACTIVE, GET_USER_HOST_EXISTS, GET_USER_MFA_ACTIVE,
-                           GET_USER_MFA_EXISTS, INSERT_ADMIN, INSERT_CLIENTS, IS_TENANT_ENABLED,
+                           GET_USER_MFA_EXISTS, GET_TENANT_KEY_TYPES, HOST_GROUP_EXISTS, INSERT_ADMIN, INSERT_CLIENTS,
+                           INSERT_PUBKEYS, INSERT_PUBKEY_HOSTS, IS_TENANT_ENABLED,
                            SELECT_PUBKEY_HOST_ACCOUNT, UPDATE_TENANTS_ENABLED_INTERNAL};
+use super::db_types::{KeyScope, PubkeyInput};
+
+// The maximum number of hosts that can be listed in a key's scope.
+pub const MAX_KEY_SCOPE_HOSTS: usize = 256;
 
 /** Multiple Query Transactions
  * 
@@ -66,7 +73,7 @@ pub async fn create_std_tenants() -> Result<u64> {
 
     // Create admin user ids.
     let dft_key_str = create_hex_secret();
-    let dft_key_hash = hash_hex_secret(&dft_key_str);
+    let dft_key_hash = hash_secret(&dft_key_str).await?;
     let _dft_admin_result = sqlx::query(INSERT_ADMIN)
         .bind(DEFAULT_TENANT)
         .bind(DEFAULT_ADMIN_ID)
@@ -78,7 +85,7 @@ pub async fn create_std_tenants() -> Result<u64> {
         .await?;
 
     let tst_key_str = create_hex_secret();
-    let tst_key_hash = hash_hex_secret(&tst_key_str);
+    let tst_key_hash = hash_secret(&tst_key_str).await?;
     let _tst_admin_result = sqlx::query(INSERT_ADMIN)
         .bind(TEST_TENANT)
         .bind(DEFAULT_ADMIN_ID)
@@ -89,12 +96,29 @@ pub async fn create_std_tenants() -> Result<u64> {
         .execute(&mut *tx)
         .await?;
 
+    // Create the site admin, which is the only admin that can manage tenants.
+    let site_key_str = create_hex_secret();
+    let site_key_hash = hash_secret(&site_key_str).await?;
+    let _site_admin_result = sqlx::query(INSERT_ADMIN)
+        .bind(DEFAULT_TENANT)
+        .bind(SITE_ADMIN_ID)
+        .bind(&site_key_hash)
+        .bind(PERM_SITE_ADMIN)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+    // Create the tenants' certificate authorities.
+    insert_tenant_ca(&mut tx, DEFAULT_TENANT, now).await?;
+    insert_tenant_ca(&mut tx, TEST_TENANT, now).await?;
+
     // Commit the transaction.
     tx.commit().await?;
 
     // --- MOST IMPORTANT ---
-    // One time printout of the admin secrets for the two tenants.
-    print_admin_secret_message(&dft_key_str, &tst_key_str)?;
+    // One time printout of the admin secrets for the two tenants and the site admin.
+    print_admin_secret_message(&dft_key_str, &tst_key_str, &site_key_str)?;
 
     // Return the number of tenant insertions that took place.
     Ok(dft_result.rows_affected() + tst_result.rows_affected())
@@ -104,11 +128,11 @@ pub async fn create_std_tenants() -> Result<u64> {
 // print_admin_secret_message:
 // ---------------------------------------------------------------------------
 /** Print one-time message to stdout that contains the admin_user and admin_secret
- * for the two standard tenents.  This only happens when the --install option was
- * specified and this program terminates after installation with the secret 
- * information visible to user.
+ * for the two standard tenents and the site admin.  This only happens when the 
+ * --install option was specified and this program terminates after installation
+ * with the secret information visible to user.
  */
-fn print_admin_secret_message(dft_key_str: &String, tst_key_str: &String) -> Result<()> {
+fn print_admin_secret_message(dft_key_str: &String, tst_key_str: &String, site_key_str: &str) -> Result<()> {
     // Compile time literal concatenation.
     let prefix = concat!(
         "\n***************************************************************************",
@@ -134,6 +158,10 @@ fn print_admin_secret_message(dft_key_str: &String, tst_key_str: &String) -> Res
         "\n**** Administrator ID: " + DEFAULT_ADMIN_ID + "                                         ****" +
         "\n**** Password: " + tst_key_str + "        ****" +
         "\n****                                                                   ****" +
+        "\n**** Tenant: " + DEFAULT_TENANT + "                                                   ****" +
+        "\n**** Site Administrator ID: " + SITE_ADMIN_ID + "                                ****" +
+        "\n**** Password: " + site_key_str + "        ****" +
+        "\n****                                                                   ****" +
         "\n***************************************************************************" +
         "\n***************************************************************************\n\n";
 
@@ -142,6 +170,55 @@ fn print_admin_secret_message(dft_key_str: &String, tst_key_str: &String) -> Res
     Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// reset_site_admin:
+// ---------------------------------------------------------------------------
+/** This method should only be called when the --reset-site-admin option is
+ * specified.  It gives the site admin a new secret and enables it, which is how
+ * installations that predate the site admin activate it, and prints the secret.
+ */
+pub async fn reset_site_admin() -> Result<()> {
+    let site_key_str = create_hex_secret();
+    let site_key_hash = hash_secret(&site_key_str).await?;
+    sqlx::query(RESET_SITE_ADMIN)
+        .bind(DEFAULT_TENANT)
+        .bind(SITE_ADMIN_ID)
+        .bind(&site_key_hash)
+        .bind(PERM_SITE_ADMIN)
+        .bind(timestamp_utc())
+        .execute(&RUNTIME_CTX.db)
+        .await?;
+    info!("Secret reset for site admin {}.", SITE_ADMIN_ID);
+
+    print_site_admin_secret_message(&site_key_str)
+}
+
+// ---------------------------------------------------------------------------
+// print_site_admin_secret_message:
+// ---------------------------------------------------------------------------
+/** Print one-time message to stdout that contains the site admin's new secret. */
+fn print_site_admin_secret_message(site_key_str: &str) -> Result<()> {
+    let msg = concat!(
+        "\n***************************************************************************",
+        "\n***************************************************************************",
+        "\n**** Below is the new password of the site administrator.  The        ****",
+        "\n**** password is NOT saved by TMS, only a hash of it is saved.  Please ****",
+        "\n**** store the password permanently in a safe place.                   ****",
+        "\n****                                                                   ****",
+        "\n****        THIS IS THE ONLY TIME THIS PASSWORD IS SHOWN.              ****",
+        "\n****                                                                   ****").to_string() +
+        "\n**** Tenant: " + DEFAULT_TENANT + "                                                   ****" +
+        "\n**** Site Administrator ID: " + SITE_ADMIN_ID + "                                ****" +
+        "\n**** Password: " + site_key_str + "        ****" +
+        "\n****                                                                   ****" +
+        "\n***************************************************************************" +
+        "\n***************************************************************************\n\n";
+
+    // Write the one-time message to the terminal.
+    io::stdout().write_all(msg.as_bytes())?;
+    Ok(())
+}
+
 // ---------------------------------------------------------------------------
 // check_test_data:
 // ---------------------------------------------------------------------------
@@ -167,10 +244,11 @@ async fn create_test_data() -> Result<bool> {
     const TEST_APP: &str = "testapp1";
     const TEST_APP_VERS: &str = "1.0";
     const TEST_CLIENT: &str = "testclient1";
-    let   test_secret: String = hash_hex_secret(&"secret1".to_string());
+    let   test_secret: String = hash_secret("secret1").await?;
     const TEST_USER: &str = "testuser1";
     const TEST_HOST: &str = "testhost1";
     const TEST_HOST_ACCOUNT: &str = "testhostaccount1";
+    let   test_host_secret: String = hash_secret("hostsecret1").await?;
 
     // Max expires_at
     let max_tms_utc = DateTime::parse_from_rfc3339(MAX_TMS_UTC_STR).unwrap().with_timezone(&Utc);
@@ -228,6 +306,28 @@ async fn create_test_data() -> Result<bool> {
         .execute(&mut *tx)
         .await?;
 
+    // -------- Populate hosts (local IPv4 and IPv6 addresses)
+    for addr in ["127.0.0.1", "::1"] {
+        sqlx::query(INSERT_HOSTS)
+            .bind(TEST_TENANT)
+            .bind(TEST_HOST)
+            .bind(addr)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // -------- Populate host_creds
+    sqlx::query(INSERT_HOST_CREDS)
+        .bind(TEST_TENANT)
+        .bind(TEST_HOST)
+        .bind(test_host_secret)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
     // Commit the transaction.
     tx.commit().await?;
     
@@ -242,8 +342,15 @@ async fn create_test_data() -> Result<bool> {
  * active.  Active means that the records exist in their respective tables, are
  * enabled and have not expired.
  * 
- * We return as soon as we encounter any dependency that cannot be fulfilled or
- * any other type of error.  The database transaction is read-only, so exiting
+ * Wildcard records are honored.  A user_hosts record with both tms_user_id and
+ * host_account set to "*" links every user to the host account of the same name,
+ * and a delegations record with client_user_id set to "*" applies to all users of
+ * the client.  When both an exact and a wildcard record exist, the exact record
+ * is used.  The user_mfa record must always exist for the actual user.
+ * 
+ * On success, the earliest expiration time of the three dependency records is
+ * returned.  We return as soon as we encounter any dependency that cannot be 
+ * fulfilled or any other type of error.  The database transaction is read-only, so exiting
  * abruptly causes the transaction to roll back, which frees up the database 
  * just as commit.
  * 
@@ -253,19 +360,44 @@ async fn create_test_data() -> Result<bool> {
 pub async fn check_pubkey_dependencies(tenant: &String, client_id: &String, 
                                         client_user_id: &String, host: &String, 
                                         host_account: &String)
-    -> Result<()>
+    -> Result<DateTime<Utc>>
 {
     // Get a connection to the db and start a transaction.
     let mut tx = RUNTIME_CTX.db.begin().await?;
 
+    // Run all checks using the new transaction.
+    let expires_at = check_pubkey_dependencies_in_tx(&mut tx, tenant, client_id, client_user_id, host, host_account).await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    // All checks passed.
+    Ok(expires_at)
+}
+
+// ---------------------------------------------------------------------------
+// check_pubkey_dependencies_in_tx:
+// ---------------------------------------------------------------------------
+/** Perform the same checks as check_pubkey_dependencies() but using the 
+ * caller's transaction.  This allows callers that already hold row locks,
+ * such as public key retrieval, to validate dependencies without acquiring
+ * a second database connection.  The caller is responsible for committing
+ * or rolling back the transaction.
+ */
+pub async fn check_pubkey_dependencies_in_tx(tx: &mut Transaction<'_, Postgres>,
+                                             tenant: &String, client_id: &String, 
+                                             client_user_id: &String, host: &String, 
+                                             host_account: &String)
+    -> Result<DateTime<Utc>>
+{
     // -------- Check user_mfa dependency
     let mfa_row = sqlx::query(GET_USER_MFA_ACTIVE)
         .bind(client_user_id)
         .bind(tenant)
-        .fetch_optional(&mut *tx)
+        .fetch_optional(&mut **tx)
         .await?;
 
-    match mfa_row {
+    let mfa_expires_at = match mfa_row {
         Some(row) => {
             // Unpack row.
             let expires_at: DateTime<Utc> = row.get(0);
@@ -286,6 +418,7 @@ pub async fn check_pubkey_dependencies(tenant: &String, client_id: &String,
                 error!("{}", msg);
                 return Result::Err(anyhow!(msg));
             }
+            expires_at
         },
         None => {
             let msg = format!("Required user MFA record not found for user ID {} in tenant {}.",
@@ -301,21 +434,23 @@ pub async fn check_pubkey_dependencies(tenant: &String, client_id: &String,
         .bind(tenant)
         .bind(host)
         .bind(host_account)
-        .fetch_optional(&mut *tx)
+        .fetch_optional(&mut **tx)
         .await?;
 
-        match host_row {
+        let host_expires_at = match host_row {
             Some(row) => {
                 // Unpack row.
                 let expires_at: DateTime<Utc> = row.get(0);
+                let matched_user: String = row.get(1);
     
                 // Check whether the user host mapping has expired.
                 if expires_at < timestamp_utc() {
-                    let msg = format!("Required user host record for user {}@{} with account {} on host {} expired at {}.",
-                                              client_user_id, tenant, host_account, host, expires_at);
+                    let msg = format!("Required {}user host record for user {}@{} with account {} on host {} expired at {}.",
+                                              wildcard_label(&matched_user), client_user_id, tenant, host_account, host, expires_at);
                     error!("{}", msg);
                     return Result::Err(anyhow!(msg));
                 }
+                expires_at
             },
             None => {
                 let msg = format!("Required user host record not found for user {}@{} with account {} on host {}.",
@@ -330,22 +465,24 @@ pub async fn check_pubkey_dependencies(tenant: &String, client_id: &String,
         .bind(tenant)
         .bind(client_id)
         .bind(client_user_id)
-        .fetch_optional(&mut *tx)
+        .fetch_optional(&mut **tx)
         .await?;
 
-        match delg_row {
+        let delg_expires_at = match delg_row {
             Some(row) => {
                 // Unpack row.
                 let expires_at: DateTime<Utc> = row.get(0);
+                let matched_user: String = row.get(1);
     
                 // Check whether the delegation has expired.
                 if expires_at < timestamp_utc() {
-                    let msg = format!("Required delegation record for client {} and client_user_id {} \
+                    let msg = format!("Required {}delegation record for client {} and client_user_id {} \
                                               in tenant {} expired at {}.",
-                                              client_id, client_user_id, tenant, expires_at);
+                                              wildcard_label(&matched_user), client_id, client_user_id, tenant, expires_at);
                     error!("{}", msg);
                     return Result::Err(anyhow!(msg));
                 }
+                expires_at
             },
             None => {
                 let msg = format!("Required delegation record not found for client {} and client_user_id {} in tenant {}.",
@@ -355,11 +492,16 @@ pub async fn check_pubkey_dependencies(tenant: &String, client_id: &String,
             }
         };
     
-    // Commit the transaction.
-    tx.commit().await?;
-
     // All checks passed.
-    Ok(())
+    Ok(mfa_expires_at.min(host_expires_at).min(delg_expires_at))
+}
+
+// ---------------------------------------------------------------------------
+// wildcard_label:
+// ---------------------------------------------------------------------------
+/** Qualify dependency messages when the matched record is a wildcard record. */
+fn wildcard_label(matched_user: &str) -> &'static str {
+    if matched_user == WILDCARD {"wildcard "} else {""}
 }
 
 // ---------------------------------------------------------------------------
@@ -582,3 +724,143 @@ pub async fn is_tenant_enabled(tenant: &String) -> Result<bool>
         },
     }
 }
+
+// ---------------------------------------------------------------------------
+// get_tenant_allowed_key_types:
+// ---------------------------------------------------------------------------
+/** Get the key types that can be generated or registered in the tenant.  None
+ * means that the tenant has no policy and all supported key types are allowed.
+ */
+pub async fn get_tenant_allowed_key_types(tenant: &String) -> Result<Option<Vec<String>>>
+{
+    // Select the tenant's key type policy.
+    let result = sqlx::query(GET_TENANT_KEY_TYPES)
+        .bind(tenant)
+        .fetch_optional(&RUNTIME_CTX.db)
+        .await?;
+
+    // We may have found the tenant.
+    match result {
+        Some(row) => {
+            Ok(row.get(0))
+        },
+        None => {
+            Err(anyhow!("NOT_FOUND"))
+        },
+    }
+}
+
+// ---------------------------------------------------------------------------
+// check_key_scope:
+// ---------------------------------------------------------------------------
+/** Validate the additional hosts and the host group on which a key for the 
+ * given host can be used.  Duplicate hosts and the key's own host are removed
+ * from the list.  The host group must have members in the tenant when the key
+ * is created, but the key can be used on whichever hosts are members when the
+ * key is retrieved.  Invalid scopes result in a "BAD_REQUEST:" error.
+ */
+pub async fn check_key_scope(tenant: &String, host: &String, hosts: &Option<Vec<String>>,
+                             host_group: &Option<String>) -> Result<KeyScope>
+{
+    // Normalize the host list.
+    let mut scope_hosts: Vec<String> = vec!();
+    for h in hosts.iter().flatten() {
+        let h = h.trim();
+        if h.is_empty() {
+            return Err(anyhow!("BAD_REQUEST: Empty host names cannot be part of a key's scope."));
+        }
+        if h != host && !scope_hosts.iter().any(|s| s == h) {
+            scope_hosts.push(h.to_string());
+        }
+    }
+    if scope_hosts.len() > MAX_KEY_SCOPE_HOSTS {
+        return Err(anyhow!("BAD_REQUEST: A key's scope cannot include more than {} hosts.", MAX_KEY_SCOPE_HOSTS));
+    }
+
+    // Make sure the host group exists.
+    let scope_group = match host_group.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(g) => {
+            let exists: bool = sqlx::query_scalar(HOST_GROUP_EXISTS)
+                .bind(tenant)
+                .bind(g)
+                .fetch_one(&RUNTIME_CTX.db)
+                .await?;
+            if !exists {
+                return Err(anyhow!("BAD_REQUEST: Host group {} not found in tenant {}.", g, tenant));
+            }
+            Some(g.to_string())
+        }
+    };
+
+    Ok(KeyScope {hosts: scope_hosts, host_group: scope_group})
+}
+
+// ---------------------------------------------------------------------------
+// insert_pubkey_in_tx:
+// ---------------------------------------------------------------------------
+/** Insert a public key and the hosts in its scope using the caller's 
+ * transaction.  The id of the new pubkeys record is returned.
+ */
+pub async fn insert_pubkey_in_tx(tx: &mut Transaction<'_, Postgres>, rec: &PubkeyInput) -> Result<i32>
+{
+    // Insert the key.
+    let row = sqlx::query(INSERT_PUBKEYS)
+        .bind(&rec.tenant)
+        .bind(&rec.client_id)
+        .bind(&rec.client_user_id)
+        .bind(&rec.host)
+        .bind(&rec.host_account)
+        .bind(&rec.public_key_fingerprint)
+        .bind(&rec.public_key)
+        .bind(&rec.key_type)
+        .bind(rec.key_bits)
+        .bind(rec.max_uses)
+        .bind(rec.remaining_uses)
+        .bind(rec.initial_ttl_minutes)
+        .bind(rec.expires_at)
+        .bind(rec.created)
+        .bind(rec.updated)
+        .bind(&rec.scope.host_group)
+        .bind(rec.sk.as_ref().map(|sk| &sk.application))
+        .bind(rec.sk.as_ref().map(|sk| &sk.flags))
+        .bind(rec.cert_serial)
+        .fetch_one(&mut **tx)
+        .await?;
+    let pubkey_id: i32 = row.get(0);
+
+    // Insert the additional hosts on which the key can be used.
+    if !rec.scope.hosts.is_empty() {
+        sqlx::query(INSERT_PUBKEY_HOSTS)
+            .bind(&rec.tenant)
+            .bind(pubkey_id)
+            .bind(&rec.scope.hosts)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    Ok(pubkey_id)
+}
+
+// ---------------------------------------------------------------------------
+// check_not_last_admin_in_tx:
+// ---------------------------------------------------------------------------
+/** Make sure that a tenant will still have an enabled admin if the specified
+ * admin is disabled or deleted.  A "LAST_ADMIN:" error is returned when the 
+ * admin is the tenant's only enabled admin.  The other admins are locked until
+ * the caller's transaction completes.
+ */
+pub async fn check_not_last_admin_in_tx(tx: &mut Transaction<'_, Postgres>, tenant: &String, admin_user: &String) 
+    -> Result<()>
+{
+    let others: i64 = sqlx::query_scalar(COUNT_OTHER_ENABLED_ADMINS)
+        .bind(tenant)
+        .bind(admin_user)
+        .fetch_one(&mut **tx)
+        .await?;
+    if others < 1 {
+        return Err(anyhow!("LAST_ADMIN: Admin {} is the only enabled admin in tenant {}.", admin_user, tenant));
+    }
+
+    Ok(())
+}
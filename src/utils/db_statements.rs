@@ -1,7 +1,9 @@
 // This file contains all SQL statements issued by TMS.
 #![forbid(unsafe_code)]
 
-pub const PLACEHOLDER: &str = "${PLACEHOLDER}";
+// Statements whose constraints depend on the request, such as the list queries
+// with their optional filters and pagination, are SELECT statements without a
+// WHERE clause that are completed using TmsQuery (see query_builder.rs).
 
 // ========================= tenants table =========================
 pub const INSERT_STD_TENANTS: &str = concat!(
@@ -15,14 +17,14 @@ pub const INSERT_TENANT: &str = concat!(
 );
 
 pub const GET_TENANT: &str = concat!(
-    "SELECT id, tenant, enabled, created, updated ",
+    "SELECT id, tenant, enabled, created, updated, allowed_key_types ",
     "FROM tenants WHERE tenant = $1"
 );
 
 // Secret elided.
 pub const LIST_TENANTS: &str = concat!(
-    "SELECT id, tenant, enabled, created, updated ",
-    "FROM tenants ORDER BY id",
+    "SELECT id, tenant, enabled, created, updated, allowed_key_types ",
+    "FROM tenants",
 );
 
 pub const UPDATE_TENANTS_ENABLED: &str = concat!(
@@ -30,6 +32,12 @@ pub const UPDATE_TENANTS_ENABLED: &str = concat!(
 );
 
 // Used to enforce enable_test_tenant configuation value at start up.
+pub const UPDATE_TENANTS_KEY_TYPES: &str = 
+    "UPDATE tenants SET allowed_key_types = $1, updated = $2 WHERE tenant = $3";
+
+pub const GET_TENANT_KEY_TYPES: &str = 
+    "SELECT allowed_key_types FROM tenants WHERE tenant = $1";
+
 pub const UPDATE_TENANTS_ENABLED_INTERNAL: &str = concat!(
     "UPDATE tenants SET enabled = $1 WHERE tenant = $2"
 );
@@ -83,7 +91,25 @@ pub const DELETE_HOSTS_FOR_TENANT: &str = concat!(
     "DELETE FROM hosts WHERE tenant = $1"
 );
 
+pub const DELETE_HOST_GROUPS_FOR_TENANT: &str = 
+    "DELETE FROM host_groups WHERE tenant = $1";
+
+pub const DELETE_HOST_CREDS_FOR_TENANT: &str = 
+    "DELETE FROM host_creds WHERE tenant = $1";
+
+pub const DELETE_CLIENT_APPROVALS_FOR_TENANT: &str = 
+    "DELETE FROM client_approvals WHERE tenant = $1";
+
+pub const DELETE_USER_CREDS_FOR_TENANT: &str = 
+    "DELETE FROM user_creds WHERE tenant = $1";
+
+pub const DELETE_CERT_MAPPINGS_FOR_TENANT: &str = 
+    "DELETE FROM cert_mappings WHERE tenant = $1";
+
 // --- Standard delete begins here (and wipe continues)
+pub const DELETE_TENANT_CA_FOR_TENANT: &str = 
+    "DELETE FROM tenant_ca WHERE tenant = $1";
+
 pub const DELETE_ADMINS_FOR_TENANT: &str = concat!(
     "DELETE FROM admin WHERE tenant = $1"
 );
@@ -105,9 +131,9 @@ pub const GET_CLIENT: &str = concat!(
 );
 
 // Secret elided.
-pub const LIST_CLIENTS_TEMPLATE: &str = concat!(
+pub const LIST_CLIENTS: &str = concat!(
     "SELECT id, tenant, app_name, app_version, client_id, enabled, created, updated ",
-    "FROM clients WHERE tenant = $1 ${PLACEHOLDER} ORDER BY tenant, client_id",
+    "FROM clients",
 );
 
 // Conforms to the signature required for secret retrieval queries as defined by 
@@ -116,6 +142,11 @@ pub const GET_CLIENT_SECRET: &str = concat!(
     "SELECT client_secret FROM clients WHERE client_id = $1 AND tenant = $2",
 );
 
+// Conforms to the signature required for legacy hash replacement queries as defined
+// by update_authz_secret() in authz.rs.
+pub const UPGRADE_CLIENT_SECRET: &str = 
+    "UPDATE clients SET client_secret = $1 WHERE client_id = $2 AND tenant = $3 AND client_secret = $4";
+
 pub const UPDATE_CLIENT_APP_VERSION: &str = concat!(
     "UPDATE clients SET app_version = $1, updated = $2 WHERE client_id = $3 AND tenant = $4"
 );
@@ -168,7 +199,7 @@ pub const DELETE_USER_MFA: &str = concat!(
 // Secret elided.
 pub const LIST_USER_MFA: &str = concat!(
     "SELECT id, tenant, tms_user_id, expires_at, enabled, created, updated ",
-    "FROM user_mfa WHERE tenant = $1 ORDER BY tenant, tms_user_id",
+    "FROM user_mfa",
 );
 
 // ========================= user_hosts table =======================
@@ -187,13 +218,20 @@ pub const GET_USER_HOST: &str = concat!(
     "FROM user_hosts WHERE id = $1 AND tenant = $2"
 );
 
+// A wildcard mapping (tms_user_id = '*' and host_account = '*') links every user to 
+// the host account with the same name as the user.  An exact match always takes 
+// precedence over the wildcard mapping, which is why exact matches are sorted first.
 pub const GET_USER_HOST_ACTIVE: &str = concat!(
-    "SELECT expires_at ",
-    "FROM user_hosts WHERE tms_user_id = $1 AND tenant = $2 AND host = $3 AND host_account = $4"
+    "SELECT expires_at, tms_user_id ",
+    "FROM user_hosts WHERE tenant = $2 AND host = $3 AND ",
+    "((tms_user_id = $1 AND host_account = $4) OR (tms_user_id = '*' AND host_account = '*' AND $1 = $4)) ",
+    "ORDER BY (tms_user_id = '*') LIMIT 1"
 );
 
 pub const GET_USER_HOST_EXISTS: &str = concat!(
-    "SELECT 1 FROM user_hosts WHERE tms_user_id = $1 AND tenant = $2 AND host = $3 AND host_account = $4"
+    "SELECT 1 FROM user_hosts WHERE tenant = $2 AND host = $3 AND ",
+    "((tms_user_id = $1 AND host_account = $4) OR (tms_user_id = '*' AND host_account = '*' AND $1 = $4)) ",
+    "LIMIT 1"
 );
 
 pub const DELETE_USER_HOST: &str = concat!(
@@ -202,7 +240,7 @@ pub const DELETE_USER_HOST: &str = concat!(
 
 pub const LIST_USER_HOSTS: &str = concat!(
     "SELECT id, tenant, tms_user_id, host, host_account, expires_at, created, updated ",
-    "FROM user_hosts WHERE tenant = $1 ORDER BY tenant, tms_user_id, host, host_account",
+    "FROM user_hosts",
 );
 
 pub const UPDATE_USER_HOST_EXPIRY: &str = concat!(
@@ -226,18 +264,21 @@ pub const GET_DELEGATION: &str = concat!(
     "FROM delegations WHERE id = $1 AND tenant = $2"
 );
 
+// A wildcard delegation (client_user_id = '*') applies to all users.  An exact 
+// match always takes precedence over the wildcard delegation.
 pub const GET_DELEGATION_ACTIVE: &str = concat!(
-    "SELECT expires_at ",
-    "FROM delegations WHERE tenant = $1 AND client_id = $2 AND client_user_id = $3"
+    "SELECT expires_at, client_user_id ",
+    "FROM delegations WHERE tenant = $1 AND client_id = $2 AND client_user_id IN ($3, '*') ",
+    "ORDER BY (client_user_id = '*') LIMIT 1"
 );
 
 pub const GET_DELEGATION_EXISTS: &str = concat!(
-    "SELECT 1 FROM delegations WHERE tenant = $1 AND client_id = $2 AND client_user_id = $3"
+    "SELECT 1 FROM delegations WHERE tenant = $1 AND client_id = $2 AND client_user_id IN ($3, '*') LIMIT 1"
 );
 
 pub const LIST_DELEGATIONS: &str = concat!(
     "SELECT id, tenant, client_id, client_user_id, expires_at, created, updated ",
-    "FROM delegations WHERE tenant = $1 ORDER BY tenant, client_id, client_user_id",
+    "FROM delegations",
 );
 
 pub const DELETE_DELEGATION: &str = concat!(
@@ -250,15 +291,47 @@ pub const UPDATE_DELEGATION_EXPIRY: &str = concat!(
 );
 
 // ========================= pubkeys table =========================
+// A key can be used on its own host, on the hosts listed for it in pubkey_hosts
+// and on the current members of its host group.  The condition matches keys in 
+// whose scope the host parameter lies.
+macro_rules! host_in_key_scope {
+    ($host:literal) => { concat!(
+        "(pubkeys.host = ", $host, " ",
+        "OR EXISTS (SELECT 1 FROM pubkey_hosts ph WHERE ph.pubkey_id = pubkeys.id AND ph.host = ", $host, ") ",
+        "OR EXISTS (SELECT 1 FROM host_groups hg WHERE hg.tenant = pubkeys.tenant ",
+        "AND hg.host_group = pubkeys.host_group AND hg.host = ", $host, "))",
+    )};
+}
+
+// The key references the user_hosts and delegations records selected by the
+// dependency checks, so exact matches again take precedence over wildcards.  The 
+// insert fails if either record no longer exists.
 pub const INSERT_PUBKEYS: &str = concat!(
     "INSERT INTO pubkeys (tenant, client_id, client_user_id, host, host_account, public_key_fingerprint, public_key, ",
-    "key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, created, updated) ", 
-    "VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
+    "key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, created, updated, host_group, ",
+    "sk_application, sk_flags, cert_serial, user_host_id, delegation_id) ", 
+    "VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, ",
+    "(SELECT id FROM user_hosts WHERE tenant = $1 AND host = $4 AND ",
+    "((tms_user_id = $3 AND host_account = $5) OR (tms_user_id = '*' AND host_account = '*' AND $3 = $5)) ",
+    "ORDER BY (tms_user_id = '*') LIMIT 1), ",
+    "(SELECT id FROM delegations WHERE tenant = $1 AND client_id = $2 AND client_user_id IN ($3, '*') ",
+    "ORDER BY (client_user_id = '*') LIMIT 1)) RETURNING id",
 );
 
+pub const INSERT_PUBKEY_HOSTS: &str = 
+    "INSERT INTO pubkey_hosts (tenant, pubkey_id, host) SELECT $1, $2, UNNEST($3::TEXT[]) ON CONFLICT DO NOTHING";
+
+// Locks the pubkey row so that the use count can be checked and decremented atomically.
+// Fingerprints are unique, so at most one row is selected.
 pub const SELECT_PUBKEY: &str = concat!(
-    "SELECT public_key, remaining_uses, expires_at FROM pubkeys ",
-    "WHERE host_account = $1 AND host = $2 AND public_key_fingerprint = $3",
+    "SELECT id, public_key, max_uses, remaining_uses, expires_at, tenant, client_id, client_user_id, sk_flags ",
+    "FROM pubkeys WHERE host_account = $1 AND ", host_in_key_scope!("$2"), " ",
+    "AND public_key_fingerprint = $3 AND tenant = $4 FOR UPDATE",
+);
+
+pub const DECREMENT_REMAINING_USES: &str = concat!(
+    "UPDATE pubkeys SET remaining_uses = remaining_uses - 1, updated = $1 ",
+    "WHERE id = $2 AND remaining_uses > 0",
 );
 
 pub const SELECT_PUBKEY_FOR_UPDATE: &str = concat!(
@@ -273,19 +346,22 @@ pub const SELECT_PUBKEY_HOST_ACCOUNT: &str = concat!(
 
 pub const SELECT_PUBKEY_RESERVATION_INFO: &str = concat!(
     "SELECT remaining_uses, expires_at, host_account FROM pubkeys ",
-    "WHERE client_id = $1 AND tenant = $2 AND host = $3 AND public_key_fingerprint = $4",
+    "WHERE client_id = $1 AND tenant = $2 AND ", host_in_key_scope!("$3"), " ",
+    "AND public_key_fingerprint = $4",
 );
 
-pub const GET_PUBKEY_TEMPLATE: &str = concat!(
+pub const GET_PUBKEY: &str = concat!(
     "SELECT id, tenant, client_id, client_user_id, host, host_account, public_key_fingerprint, public_key, ",
-    "key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, created, updated ",
-    "FROM pubkeys WHERE id = $1 AND tenant = $2 ${PLACEHOLDER}",
+    "key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, created, updated, host_group, ",
+    "ARRAY(SELECT ph.host FROM pubkey_hosts ph WHERE ph.pubkey_id = pubkeys.id ORDER BY ph.host), ",
+    "sk_application, sk_flags FROM pubkeys",
 );
 
-pub const LIST_PUBKEYS_TEMPLATE: &str = concat!(
+pub const LIST_PUBKEYS: &str = concat!(
     "SELECT id, tenant, client_id, client_user_id, host, host_account, public_key_fingerprint, public_key, ",
-    "key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, created, updated ",
-    "FROM pubkeys WHERE tenant = $1 ${PLACEHOLDER} ORDER BY tenant, client_user_id, host, host_account",
+    "key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, created, updated, host_group, ",
+    "ARRAY(SELECT ph.host FROM pubkey_hosts ph WHERE ph.pubkey_id = pubkeys.id ORDER BY ph.host), ",
+    "sk_application, sk_flags FROM pubkeys",
 );
 
 pub const UPDATE_MAX_USES: &str = concat!(
@@ -293,16 +369,85 @@ pub const UPDATE_MAX_USES: &str = concat!(
     "WHERE client_id = $4 AND tenant = $5 AND host = $6 AND public_key_fingerprint = $7",
 );
 
+// Locks the pubkey row so that it cannot change while it's rotated.
+pub const SELECT_PUBKEY_FOR_ROTATE: &str = concat!(
+    "SELECT client_user_id, host_account, key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, ",
+    "host_group, ARRAY(SELECT ph.host FROM pubkey_hosts ph WHERE ph.pubkey_id = pubkeys.id ORDER BY ph.host) ",
+    "FROM pubkeys WHERE client_id = $1 AND tenant = $2 AND host = $3 AND public_key_fingerprint = $4 ",
+    "FOR UPDATE",
+);
+
 pub const UPDATE_EXPIRES_AT: &str = concat!(
     "UPDATE pubkeys SET expires_at = $1, updated = $2 ",
     "WHERE client_id = $3 AND tenant = $4 AND host = $5 AND public_key_fingerprint = $6",
 );
 
+pub const UPDATE_ROTATED_PUBKEY: &str = concat!(
+    "UPDATE pubkeys SET expires_at = $1, remaining_uses = $2, updated = $3 ",
+    "WHERE client_id = $4 AND tenant = $5 AND host = $6 AND public_key_fingerprint = $7",
+);
+
+// The key's user is only constrained ($5 not null) when users delete their own keys.
 pub const DELETE_PUBKEY: &str = concat!(
-    "DELETE FROM pubkeys WHERE client_id = $1 AND tenant = $2 AND host = $3 AND public_key_fingerprint = $4"
+    "DELETE FROM pubkeys WHERE client_id = $1 AND tenant = $2 AND host = $3 AND public_key_fingerprint = $4 ",
+    "AND ($5::TEXT IS NULL OR client_user_id = $5)"
+);
+
+// The fingerprints of the keys in tenant $1 that expired or were deleted between $2 and
+// $3, optionally restricted to the keys that could be used on host $4.  Deleted keys,
+// including those removed by the reaper, are found in the pubkeys audit table.  Its
+// records do not include the hosts listed in a key's scope, which are found in the
+// audit records of the key's pubkey_hosts rows that were deleted along with the key.
+// Fingerprints that still have an unexpired key are never revoked.
+pub const LIST_REVOKED_PUBKEYS: &str = concat!(
+    "SELECT DISTINCT revoked.fp FROM (",
+    "SELECT public_key_fingerprint AS fp FROM pubkeys ",
+    "WHERE tenant = $1 AND expires_at >= $2 AND expires_at < $3 ",
+    "AND ($4::TEXT IS NULL OR ", host_in_key_scope!("$4"), ") ",
+    "UNION ",
+    "SELECT a.oldvalue::JSONB->>'public_key_fingerprint' FROM pubkeys_audit a ",
+    "WHERE a.tenant = $1 AND a.change = 'D' AND a.refcol = 'row' AND a.changed >= $2 ",
+    "AND ($4::TEXT IS NULL OR a.oldvalue::JSONB->>'host' = $4 ",
+    "OR EXISTS (SELECT 1 FROM host_groups hg WHERE hg.tenant = a.tenant ",
+    "AND hg.host_group = a.oldvalue::JSONB->>'host_group' AND hg.host = $4) ",
+    "OR EXISTS (SELECT 1 FROM pubkey_hosts_audit ha WHERE ha.tenant = a.tenant ",
+    "AND ha.change = 'D' AND ha.refcol = 'row' AND ha.oldvalue::JSONB->>'pubkey_id' = a.refid::TEXT ",
+    "AND ha.oldvalue::JSONB->>'host' = $4))",
+    ") revoked WHERE revoked.fp IS NOT NULL ",
+    "AND NOT EXISTS (SELECT 1 FROM pubkeys p WHERE p.public_key_fingerprint = revoked.fp AND p.expires_at >= $3) ",
+    "ORDER BY revoked.fp",
+);
+
+// The serial numbers of the certificates signed for the same keys.  Each certificate
+// has its own serial number, so serials are revoked even when the key's fingerprint 
+// still has an unexpired record.
+pub const LIST_REVOKED_CERT_SERIALS: &str = concat!(
+    "SELECT DISTINCT revoked.serial FROM (",
+    "SELECT cert_serial AS serial FROM pubkeys ",
+    "WHERE tenant = $1 AND expires_at >= $2 AND expires_at < $3 ",
+    "AND ($4::TEXT IS NULL OR ", host_in_key_scope!("$4"), ") ",
+    "UNION ",
+    "SELECT (a.oldvalue::JSONB->>'cert_serial')::BIGINT FROM pubkeys_audit a ",
+    "WHERE a.tenant = $1 AND a.change = 'D' AND a.refcol = 'row' AND a.changed >= $2 ",
+    "AND ($4::TEXT IS NULL OR a.oldvalue::JSONB->>'host' = $4 ",
+    "OR EXISTS (SELECT 1 FROM host_groups hg WHERE hg.tenant = a.tenant ",
+    "AND hg.host_group = a.oldvalue::JSONB->>'host_group' AND hg.host = $4) ",
+    "OR EXISTS (SELECT 1 FROM pubkey_hosts_audit ha WHERE ha.tenant = a.tenant ",
+    "AND ha.change = 'D' AND ha.refcol = 'row' AND ha.oldvalue::JSONB->>'pubkey_id' = a.refid::TEXT ",
+    "AND ha.oldvalue::JSONB->>'host' = $4))",
+    ") revoked WHERE revoked.serial IS NOT NULL ",
+    "ORDER BY revoked.serial",
 );
 
 // ========================= admin table ===========================
+// Sets the site admin's secret and enables it, creating the site admin if necessary.
+pub const RESET_SITE_ADMIN: &str = concat!(
+    "INSERT INTO admin (tenant, admin_user, admin_secret, privilege, enabled, created, updated) ",
+    "VALUES ($1, $2, $3, $4, TRUE, $5, $5) ON CONFLICT (tenant, admin_user) DO UPDATE ",
+    "SET admin_secret = EXCLUDED.admin_secret, privilege = EXCLUDED.privilege, enabled = TRUE, ",
+    "updated = EXCLUDED.updated",
+);
+
 pub const INSERT_ADMIN: &str = concat!(
     "INSERT INTO admin (tenant, admin_user, admin_secret, privilege, created, updated) ",
     "VALUES ($1, $2, $3, $4, $5, $6)",
@@ -311,7 +456,56 @@ pub const INSERT_ADMIN: &str = concat!(
 // Conforms to the signature required for secret retrieval queries as defined by 
 // get_authz_secret() in authz.rs.
 pub const GET_ADMIN_SECRET: &str = concat!(
-    "SELECT admin_secret FROM admin WHERE admin_user = $1 AND tenant = $2",
+    "SELECT admin_secret FROM admin WHERE admin_user = $1 AND tenant = $2 AND enabled = TRUE",
+);
+
+// Conforms to the signature required for secret retrieval queries as defined by 
+// get_authz_secret() in authz.rs.  Site admins are always in the default tenant.
+pub const GET_SITE_ADMIN_SECRET: &str = concat!(
+    "SELECT admin_secret FROM admin WHERE admin_user = $1 AND tenant = $2 AND enabled = TRUE ",
+    "AND tenant = 'default' AND privilege = 'PERM_SITE_ADMIN'",
+);
+
+// Conforms to the signature required for legacy hash replacement queries as defined
+// by update_authz_secret() in authz.rs.
+pub const UPGRADE_ADMIN_SECRET: &str = 
+    "UPDATE admin SET admin_secret = $1 WHERE admin_user = $2 AND tenant = $3 AND admin_secret = $4";
+
+pub const GET_ADMIN: &str = concat!(
+    "SELECT id, tenant, admin_user, privilege, enabled, created, updated ",
+    "FROM admin WHERE admin_user = $1 AND tenant = $2",
+);
+
+pub const LIST_ADMINS: &str = concat!(
+    "SELECT id, tenant, admin_user, privilege, enabled, created, updated ",
+    "FROM admin",
+);
+
+// The last parameter is true when site admin records can be changed.
+pub const UPDATE_ADMIN_ENABLED: &str = concat!(
+    "UPDATE admin SET enabled = $1, updated = $2 WHERE admin_user = $3 AND tenant = $4 ",
+    "AND (privilege <> 'PERM_SITE_ADMIN' OR $5)",
+);
+
+pub const UPDATE_ADMIN_SECRET: &str = concat!(
+    "UPDATE admin SET admin_secret = $1, updated = $2 WHERE admin_user = $3 AND tenant = $4 ",
+    "AND (privilege <> 'PERM_SITE_ADMIN' OR $5)",
+);
+
+pub const DELETE_ADMIN: &str = concat!(
+    "DELETE FROM admin WHERE admin_user = $1 AND tenant = $2 ",
+    "AND (privilege <> 'PERM_SITE_ADMIN' OR $3)",
+);
+
+// Count the enabled admins in a tenant other than the one specified.  Only other
+// site admins are counted when the specified admin is a site admin.  The rows are
+// locked so that concurrent requests cannot disable or delete the last admin.
+pub const COUNT_OTHER_ENABLED_ADMINS: &str = concat!(
+    "SELECT COUNT(*) FROM (SELECT a.id FROM admin a ",
+    "LEFT JOIN admin t ON t.tenant = a.tenant AND t.admin_user = $2 ",
+    "WHERE a.tenant = $1 AND a.admin_user != $2 AND a.enabled = TRUE ",
+    "AND (t.privilege IS DISTINCT FROM 'PERM_SITE_ADMIN' OR a.privilege = 'PERM_SITE_ADMIN') ",
+    "FOR UPDATE OF a) x",
 );
 
 // ========================= hosts table ===========================
@@ -331,14 +525,36 @@ pub const DELETE_HOST: &str = concat!(
 
 pub const LIST_HOSTS: &str = concat!(
     "SELECT id, tenant, host, addr, created, updated ",
-    "FROM hosts WHERE tenant = $1 ORDER BY tenant, host, addr",
+    "FROM hosts",
+);
+
+pub const GET_HOST_ADDRS: &str = 
+    "SELECT addr FROM hosts WHERE tenant = $1 AND host = $2";
+
+// ====================== host_groups table ========================
+pub const INSERT_HOST_GROUP_MEMBERS: &str = concat!(
+    "INSERT INTO host_groups (tenant, host_group, host, created, updated) ",
+    "SELECT $1, $2, UNNEST($3::TEXT[]), $4, $4 ON CONFLICT DO NOTHING",
 );
 
+pub const GET_HOST_GROUP_MEMBERS: &str = 
+    "SELECT host FROM host_groups WHERE tenant = $1 AND host_group = $2 ORDER BY host";
+
+pub const HOST_GROUP_EXISTS: &str = 
+    "SELECT EXISTS (SELECT 1 FROM host_groups WHERE tenant = $1 AND host_group = $2)";
+
+pub const DELETE_HOST_GROUP_MEMBERS: &str = 
+    "DELETE FROM host_groups WHERE tenant = $1 AND host_group = $2 AND host = ANY($3::TEXT[])";
+
 // ==================== reservations table =========================
+// The reservation references the key in whose scope the host lies.  No key 
+// leaves pubkey_id NULL, which the NOT NULL constraint rejects.
 pub const INSERT_RESERVATIONS: &str = concat!(
     "INSERT INTO reservations (resid, parent_resid, tenant, client_id, client_user_id, ", 
-    "host, public_key_fingerprint, expires_at, created, updated) ",
-    "VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+    "host, public_key_fingerprint, expires_at, created, updated, pubkey_id) ",
+    "VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, ",
+    "(SELECT id FROM pubkeys WHERE tenant = $3 AND client_id = $4 AND public_key_fingerprint = $7 ",
+    "AND ", host_in_key_scope!("$6"), "))",
 );
 
 pub const GET_RESERVATION: &str = concat!(
@@ -359,3 +575,304 @@ pub const DELETE_RESERVATION: &str = concat!(
 pub const DELETE_RELATED_RESERVATIONS: &str = concat!(
     "DELETE FROM reservations WHERE (resid = $1 OR parent_resid = $2) AND client_id = $3 AND tenant = $4"
 );
+
+// ===================== client_approvals table ====================
+pub const INSERT_CLIENT_APPROVAL: &str = concat!(
+    "INSERT INTO client_approvals (tenant, approval_hash, expires_at, created, updated) ",
+    "VALUES ($1, $2, $3, $4, $5)",
+);
+
+// Approval codes can only be used once, so they are deleted when consumed.
+pub const CONSUME_CLIENT_APPROVAL: &str = concat!(
+    "DELETE FROM client_approvals ",
+    "WHERE tenant = $1 AND approval_hash = $2 AND expires_at > $3 RETURNING id",
+);
+
+// ========================= host_creds table ======================
+pub const INSERT_HOST_CREDS: &str = concat!(
+    "INSERT INTO host_creds (tenant, host_id, host_secret, created, updated) ",
+    "VALUES ($1, $2, $3, $4, $5)",
+);
+
+// Conforms to the signature required for secret retrieval queries as defined by 
+// get_authz_secret() in authz.rs.
+pub const GET_HOST_SECRET: &str = 
+    "SELECT host_secret FROM host_creds WHERE host_id = $1 AND tenant = $2";
+
+// Conforms to the signature required for legacy hash replacement queries as defined
+// by update_authz_secret() in authz.rs.
+pub const UPGRADE_HOST_SECRET: &str = 
+    "UPDATE host_creds SET host_secret = $1 WHERE host_id = $2 AND tenant = $3 AND host_secret = $4";
+
+// Secret elided.
+pub const LIST_HOST_CREDS: &str = concat!(
+    "SELECT id, tenant, host_id, created, updated ",
+    "FROM host_creds",
+);
+
+pub const UPDATE_HOST_SECRET: &str = 
+    "UPDATE host_creds SET host_secret = $1, updated = $2 WHERE host_id = $3 AND tenant = $4";
+
+pub const DELETE_HOST_CREDS: &str = 
+    "DELETE FROM host_creds WHERE host_id = $1 AND tenant = $2";
+
+// ========================= user_creds table ======================
+pub const INSERT_USER_CREDS: &str = concat!(
+    "INSERT INTO user_creds (tenant, tms_user_id, user_secret, created, updated) ",
+    "VALUES ($1, $2, $3, $4, $5)",
+);
+
+// Conforms to the signature required for secret retrieval queries as defined by 
+// get_authz_secret() in authz.rs.
+pub const GET_USER_SECRET: &str = 
+    "SELECT user_secret FROM user_creds WHERE tms_user_id = $1 AND tenant = $2";
+
+// Conforms to the signature required for legacy hash replacement queries as defined
+// by update_authz_secret() in authz.rs.
+pub const UPGRADE_USER_SECRET: &str = 
+    "UPDATE user_creds SET user_secret = $1 WHERE tms_user_id = $2 AND tenant = $3 AND user_secret = $4";
+
+// Secret elided.
+pub const LIST_USER_CREDS: &str = concat!(
+    "SELECT id, tenant, tms_user_id, created, updated ",
+    "FROM user_creds",
+);
+
+pub const UPDATE_USER_SECRET: &str = 
+    "UPDATE user_creds SET user_secret = $1, updated = $2 WHERE tms_user_id = $3 AND tenant = $4";
+
+pub const DELETE_USER_CREDS: &str = 
+    "DELETE FROM user_creds WHERE tms_user_id = $1 AND tenant = $2";
+
+// ========================= cert_mappings table ===================
+pub const INSERT_CERT_MAPPING: &str = concat!(
+    "INSERT INTO cert_mappings (tenant, cert_subject, subject_type, subject_id, created, updated) ",
+    "VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+);
+
+// The ids of the subjects of type $2 to which any of the certificate names in $3 are mapped.
+pub const GET_CERT_MAPPED_IDS: &str = concat!(
+    "SELECT DISTINCT subject_id FROM cert_mappings ",
+    "WHERE tenant = $1 AND subject_type = $2 AND cert_subject = ANY($3)",
+);
+
+pub const LIST_CERT_MAPPINGS: &str = concat!(
+    "SELECT id, tenant, cert_subject, subject_type, subject_id, created, updated ",
+    "FROM cert_mappings",
+);
+
+pub const DELETE_CERT_MAPPING: &str = 
+    "DELETE FROM cert_mappings WHERE id = $1 AND tenant = $2";
+
+// ========================= tenant_ca table =======================
+// Existing CAs are never replaced.
+pub const INSERT_TENANT_CA: &str = concat!(
+    "INSERT INTO tenant_ca (tenant, private_key, public_key, public_key_fingerprint, key_type, created, updated) ",
+    "VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (tenant) DO NOTHING",
+);
+
+pub const GET_TENANT_CA: &str = 
+    "SELECT private_key, public_key, public_key_fingerprint, key_type FROM tenant_ca WHERE tenant = $1";
+
+// CA private keys that were stored before they were encrypted (see key_encryption.rs).
+pub const LIST_UNENCRYPTED_TENANT_CAS: &str = 
+    "SELECT tenant, private_key FROM tenant_ca WHERE private_key NOT LIKE 'aes256gcm:%'";
+
+pub const UPDATE_TENANT_CA_PRIVATE_KEY: &str = concat!(
+    "UPDATE tenant_ca SET private_key = $1, updated = $2 ",
+    "WHERE tenant = $3 AND private_key = $4",
+);
+
+// ========================= audit tables ==========================
+// The FROM clause naming an audit table, such as pubkeys_audit, is added with TmsQuery.
+pub const LIST_AUDIT_COLUMNS: &str = 
+    "SELECT id, refid, tenant, refcol, change, oldvalue, newvalue, changed";
+
+// ========================= reaper ================================
+// Each reaper statement removes at most $2 records that expired before $1 and
+// returns the number of records removed in each tenant.  The archive statements
+// also copy the removed records to the reaper_archive table.  Records locked by 
+// in-flight requests are skipped and picked up on a later run.
+//
+// Deleting a record cascades to the reaped records that reference it, which may
+// not have expired and would not be archived.  An expired record is therefore
+// only removed when no other reaped record references it; the second macro
+// argument is the condition, on the candidate record r, that checks this.
+macro_rules! reap_expired {
+    ($table:literal, $unreferenced:expr) => { concat!(
+        "WITH reaped AS (DELETE FROM ", $table, " WHERE id IN (SELECT r.id FROM ", $table, " r ",
+        "WHERE r.expires_at < $1", $unreferenced, " ORDER BY r.id LIMIT $2 FOR UPDATE SKIP LOCKED) RETURNING tenant) ",
+        "SELECT tenant, COUNT(*) FROM reaped GROUP BY tenant",
+    )};
+}
+
+macro_rules! archive_expired {
+    ($table:literal, $unreferenced:expr) => { concat!(
+        "WITH reaped AS (DELETE FROM ", $table, " WHERE id IN (SELECT r.id FROM ", $table, " r ",
+        "WHERE r.expires_at < $1", $unreferenced, " ORDER BY r.id LIMIT $2 FOR UPDATE SKIP LOCKED) RETURNING *), ",
+        "archived AS (INSERT INTO reaper_archive (tablename, refid, tenant, record, expires_at) ",
+        "SELECT '", $table, "', id, tenant, to_jsonb(reaped), expires_at FROM reaped RETURNING tenant) ",
+        "SELECT tenant, COUNT(*) FROM archived GROUP BY tenant",
+    )};
+}
+
+// Reservations are not referenced by other records.  The pubkey_hosts records of
+// a key are part of the key and are removed with it.
+macro_rules! pubkeys_unreferenced {() => {
+    " AND NOT EXISTS (SELECT 1 FROM reservations c WHERE c.pubkey_id = r.id)"
+}}
+macro_rules! delegations_unreferenced {() => {
+    " AND NOT EXISTS (SELECT 1 FROM pubkeys c WHERE c.delegation_id = r.id)"
+}}
+macro_rules! user_hosts_unreferenced {() => {
+    " AND NOT EXISTS (SELECT 1 FROM pubkeys c WHERE c.user_host_id = r.id)"
+}}
+macro_rules! user_mfa_unreferenced {() => { concat!(
+    " AND NOT EXISTS (SELECT 1 FROM user_hosts c WHERE c.tenant = r.tenant AND c.tms_user_id = r.tms_user_id)",
+    " AND NOT EXISTS (SELECT 1 FROM delegations c WHERE c.tenant = r.tenant AND c.client_user_id = r.tms_user_id)",
+    " AND NOT EXISTS (SELECT 1 FROM pubkeys c WHERE c.tenant = r.tenant AND c.client_user_id = r.tms_user_id)",
+    " AND NOT EXISTS (SELECT 1 FROM reservations c WHERE c.tenant = r.tenant AND c.client_user_id = r.tms_user_id)",
+)}}
+
+pub const REAP_RESERVATIONS: &str = reap_expired!("reservations", "");
+pub const REAP_PUBKEYS: &str = reap_expired!("pubkeys", pubkeys_unreferenced!());
+pub const REAP_DELEGATIONS: &str = reap_expired!("delegations", delegations_unreferenced!());
+pub const REAP_USER_HOSTS: &str = reap_expired!("user_hosts", user_hosts_unreferenced!());
+pub const REAP_USER_MFA: &str = reap_expired!("user_mfa", user_mfa_unreferenced!());
+
+pub const ARCHIVE_RESERVATIONS: &str = archive_expired!("reservations", "");
+pub const ARCHIVE_PUBKEYS: &str = archive_expired!("pubkeys", pubkeys_unreferenced!());
+pub const ARCHIVE_DELEGATIONS: &str = archive_expired!("delegations", delegations_unreferenced!());
+pub const ARCHIVE_USER_HOSTS: &str = archive_expired!("user_hosts", user_hosts_unreferenced!());
+pub const ARCHIVE_USER_MFA: &str = archive_expired!("user_mfa", user_mfa_unreferenced!());
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+// These tests run the statements against the database named by the
+// TMS_TEST_DATABASE_URL environment variable, which is initialized like a TMS
+// database (see deployment/postgres/tms_init_db.sh), after applying the migrations.
+// All changes are rolled back.  The tests are skipped when the variable is not set.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, Utc};
+    use sqlx::{Connection, PgConnection};
+
+    const TEST_DATABASE_URL: &str = "TMS_TEST_DATABASE_URL";
+
+    async fn connect() -> Option<PgConnection> {
+        let url = std::env::var(TEST_DATABASE_URL).ok()?;
+        let mut conn = PgConnection::connect(&url).await.expect("Failed to connect to Postgres");
+        let migrations = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/migrations"));
+        sqlx::migrate::Migrator::new(migrations).await.expect("Migration failed")
+            .run(&mut conn).await.expect("Migration run error");
+        sqlx::query("SET search_path TO tms").execute(&mut conn).await.unwrap();
+        Some(conn)
+    }
+
+    #[tokio::test]
+    async fn revoke_deleted_multi_host_key() {
+        let mut conn = match connect().await {
+            Some(c) => c,
+            None => {eprintln!("{} not set, skipping test.", TEST_DATABASE_URL); return;}
+        };
+        let mut tx = conn.begin().await.unwrap();
+
+        // Create a key for host1 that is also scoped to host2, then delete it.
+        let setup = [
+            "INSERT INTO tenants (tenant, enabled) VALUES ('krltest', TRUE)",
+            "INSERT INTO clients (tenant, app_name, app_version, client_id, client_secret, enabled) \
+             VALUES ('krltest', 'app', '1', 'client1', 'secret', TRUE)",
+            "INSERT INTO user_mfa (tenant, tms_user_id, enabled) VALUES ('krltest', 'user1', TRUE)",
+            "INSERT INTO user_hosts (tenant, tms_user_id, host, host_account) \
+             VALUES ('krltest', 'user1', 'host1', 'acct1')",
+            "INSERT INTO delegations (tenant, client_id, client_user_id) VALUES ('krltest', 'client1', 'user1')",
+            "INSERT INTO pubkeys (tenant, client_id, client_user_id, host, host_account, public_key_fingerprint, \
+             public_key, key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, \
+             user_host_id, delegation_id, cert_serial) \
+             SELECT 'krltest', 'client1', 'user1', 'host1', 'acct1', 'SHA256:krltest', 'ssh-ed25519 AAAA', \
+             'ed25519', 256, 10, 10, 60, NOW() + INTERVAL '1 hour', uh.id, d.id, 42 \
+             FROM user_hosts uh, delegations d WHERE uh.tenant = 'krltest' AND d.tenant = 'krltest'",
+            "INSERT INTO pubkey_hosts (tenant, pubkey_id, host) \
+             SELECT 'krltest', id, 'host2' FROM pubkeys WHERE public_key_fingerprint = 'SHA256:krltest'",
+            "DELETE FROM pubkeys WHERE public_key_fingerprint = 'SHA256:krltest'",
+        ];
+        for stmt in setup {
+            sqlx::query(stmt).execute(&mut *tx).await.unwrap();
+        }
+
+        // The key is revoked on its host and on the host in its scope, but not elsewhere.
+        let now = Utc::now();
+        let (from, to) = (now - TimeDelta::hours(1), now + TimeDelta::hours(1));
+        for (host, revoked) in [("host1", true), ("host2", true), ("host3", false)] {
+            let fps: Vec<String> = sqlx::query_scalar(LIST_REVOKED_PUBKEYS)
+                .bind("krltest").bind(from).bind(to).bind(host)
+                .fetch_all(&mut *tx).await.unwrap();
+            assert_eq!(fps.contains(&"SHA256:krltest".to_string()), revoked, "fingerprint on {}", host);
+            let serials: Vec<i64> = sqlx::query_scalar(LIST_REVOKED_CERT_SERIALS)
+                .bind("krltest").bind(from).bind(to).bind(host)
+                .fetch_all(&mut *tx).await.unwrap();
+            assert_eq!(serials.contains(&42), revoked, "certificate serial on {}", host);
+        }
+        tx.rollback().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reap_only_unreferenced_records() {
+        let mut conn = match connect().await {
+            Some(c) => c,
+            None => {eprintln!("{} not set, skipping test.", TEST_DATABASE_URL); return;}
+        };
+        let mut tx = conn.begin().await.unwrap();
+
+        // An unexpired key that depends on an expired user, host and delegation.
+        let setup = [
+            "INSERT INTO tenants (tenant, enabled) VALUES ('reaptest', TRUE)",
+            "INSERT INTO clients (tenant, app_name, app_version, client_id, client_secret, enabled) \
+             VALUES ('reaptest', 'app', '1', 'client1', 'secret', TRUE)",
+            "INSERT INTO user_mfa (tenant, tms_user_id, enabled, expires_at) \
+             VALUES ('reaptest', 'user1', TRUE, NOW() - INTERVAL '1 day')",
+            "INSERT INTO user_hosts (tenant, tms_user_id, host, host_account, expires_at) \
+             VALUES ('reaptest', 'user1', 'host1', 'acct1', NOW() - INTERVAL '1 day')",
+            "INSERT INTO delegations (tenant, client_id, client_user_id, expires_at) \
+             VALUES ('reaptest', 'client1', 'user1', NOW() - INTERVAL '1 day')",
+            "INSERT INTO pubkeys (tenant, client_id, client_user_id, host, host_account, public_key_fingerprint, \
+             public_key, key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, \
+             user_host_id, delegation_id) \
+             SELECT 'reaptest', 'client1', 'user1', 'host1', 'acct1', 'SHA256:reaptest', 'ssh-ed25519 AAAA', \
+             'ed25519', 256, 10, 10, 60, NOW() + INTERVAL '1 hour', uh.id, d.id \
+             FROM user_hosts uh, delegations d WHERE uh.tenant = 'reaptest' AND d.tenant = 'reaptest'",
+        ];
+        for stmt in setup {
+            sqlx::query(stmt).execute(&mut *tx).await.unwrap();
+        }
+
+        // Run the archive statements in the reaper's order and return the records removed.
+        async fn reap(tx: &mut sqlx::PgConnection) -> i64 {
+            let mut removed = 0;
+            for stmt in [ARCHIVE_RESERVATIONS, ARCHIVE_PUBKEYS, ARCHIVE_DELEGATIONS, ARCHIVE_USER_HOSTS, ARCHIVE_USER_MFA] {
+                let rows: Vec<(Option<String>, i64)> = sqlx::query_as(stmt)
+                    .bind(Utc::now()).bind(100i64)
+                    .fetch_all(&mut *tx).await.unwrap();
+                removed += rows.iter().filter(|r| r.0.as_deref() == Some("reaptest")).map(|r| r.1).sum::<i64>();
+            }
+            removed
+        }
+
+        // Nothing is removed while the key is referenced and unexpired.
+        assert_eq!(reap(&mut tx).await, 0);
+        let keys: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pubkeys WHERE tenant = 'reaptest'")
+            .fetch_one(&mut *tx).await.unwrap();
+        assert_eq!(keys, 1);
+
+        // Once the key expires, it and the records it depends on are removed and archived.
+        sqlx::query("UPDATE pubkeys SET expires_at = NOW() - INTERVAL '1 day' WHERE tenant = 'reaptest'")
+            .execute(&mut *tx).await.unwrap();
+        assert_eq!(reap(&mut tx).await, 4);
+        let archived: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reaper_archive WHERE tenant = 'reaptest'")
+            .fetch_one(&mut *tx).await.unwrap();
+        assert_eq!(archived, 4);
+        tx.rollback().await.unwrap();
+    }
+}
@@ -26,6 +26,10 @@ pub struct Pubkey {
     pub expires_at: DateTime<Utc>,
     pub created: DateTime<Utc>,
     pub updated: DateTime<Utc>,
+    pub host_group: Option<String>,
+    pub hosts: Vec<String>,
+    pub sk_application: Option<String>,
+    pub sk_flags: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,14 +49,37 @@ pub struct PubkeyInput {
     pub expires_at: DateTime<Utc>,
     pub created: DateTime<Utc>,
     pub updated: DateTime<Utc>,
+    pub scope: KeyScope,
+    pub sk: Option<SkKeyInfo>,
+    pub cert_serial: Option<i64>,
+}
+
+// The hosts other than its own host on which a key can be used.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct KeyScope {
+    pub hosts: Vec<String>,
+    pub host_group: Option<String>,
+}
+
+// The attributes of a registered FIDO security key.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SkKeyInfo {
+    pub application: String,
+    pub flags: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 #[allow(dead_code)]
 pub struct PubkeyRetrieval {
+    pub id: i32,
     pub public_key: String,
+    pub max_uses: i32,
     pub remaining_uses: i32,
     pub expires_at: DateTime<Utc>,
+    pub tenant: String,
+    pub client_id: String,
+    pub client_user_id: String,
+    pub sk_flags: Option<Vec<String>>,
 }
 
 impl Pubkey {
@@ -74,12 +101,16 @@ impl Pubkey {
         expires_at: DateTime<Utc>,
         created: DateTime<Utc>,
         updated: DateTime<Utc>,
+        host_group: Option<String>,
+        hosts: Vec<String>,
+        sk_application: Option<String>,
+        sk_flags: Option<Vec<String>>,
     ) 
     -> Pubkey {
         Pubkey {
             id, tenant, client_id, client_user_id, host, host_account, public_key_fingerprint, 
             public_key, key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, 
-            expires_at, created, updated
+            expires_at, created, updated, host_group, hosts, sk_application, sk_flags
         }
     }
 }
@@ -102,24 +133,35 @@ impl PubkeyInput {
         expires_at: DateTime<Utc>,
         created: DateTime<Utc>,
         updated: DateTime<Utc>,
+        scope: KeyScope,
+        sk: Option<SkKeyInfo>,
+        cert_serial: Option<i64>,
     ) 
     -> PubkeyInput {
         PubkeyInput {
             tenant, client_id, client_user_id, host, host_account, public_key_fingerprint, public_key, 
-            key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, created, updated
+            key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, expires_at, created, updated,
+            scope, sk, cert_serial
         }
     }
 }
 
 impl PubkeyRetrieval {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        id: i32,
         public_key: String,
+        max_uses: i32,
         remaining_uses: i32,
         expires_at: DateTime<Utc>,
+        tenant: String,
+        client_id: String,
+        client_user_id: String,
+        sk_flags: Option<Vec<String>>,
     )
     -> PubkeyRetrieval {
         PubkeyRetrieval {
-            public_key, remaining_uses, expires_at,
+            id, public_key, max_uses, remaining_uses, expires_at, tenant, client_id, client_user_id, sk_flags,
         }
     }
 }
@@ -387,6 +429,7 @@ pub struct Tenant {
     pub enabled: bool,
     pub created: DateTime<Utc>,
     pub updated: DateTime<Utc>,
+    pub allowed_key_types: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -406,10 +449,11 @@ impl Tenant {
         enabled: bool,
         created: DateTime<Utc>,
         updated: DateTime<Utc>,
+        allowed_key_types: Option<Vec<String>>,
     ) 
     -> Tenant {
         Tenant {
-            id, tenant, enabled, created, updated
+            id, tenant, enabled, created, updated, allowed_key_types
         }
     }
 }
@@ -430,6 +474,38 @@ impl TenantInput {
     }
 }
 
+// ---------------------------------------------------------------------------
+// admin:
+// ---------------------------------------------------------------------------
+// The admin_secret is never retrieved with the other admin fields.
+#[derive(Debug, Deserialize)]
+pub struct Admin {
+    pub id: i32,
+    pub tenant: String,
+    pub admin_user: String,
+    pub privilege: String,
+    pub enabled: bool,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+impl Admin {
+    pub fn new(
+        id: i32,
+        tenant: String,
+        admin_user: String,
+        privilege: String,
+        enabled: bool,
+        created: DateTime<Utc>,
+        updated: DateTime<Utc>,
+    )
+    -> Admin {
+        Admin {
+            id, tenant, admin_user, privilege, enabled, created, updated
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // hosts:
 // ---------------------------------------------------------------------------
@@ -564,3 +640,117 @@ impl ReservationInput {
     }
 }
 
+
+// ---------------------------------------------------------------------------
+// host_creds:
+// ---------------------------------------------------------------------------
+#[derive(Debug, Deserialize)]
+pub struct HostCredInput {
+    pub tenant: String,
+    pub host_id: String,
+    pub host_secret: String,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+impl HostCredInput {
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn new(
+        tenant: String,
+        host_id: String,
+        host_secret: String,
+        created: DateTime<Utc>,
+        updated: DateTime<Utc>,
+    ) 
+    -> HostCredInput {
+        HostCredInput {
+            tenant, host_id, host_secret, created, updated
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// user_creds:
+// ---------------------------------------------------------------------------
+#[derive(Debug, Deserialize)]
+pub struct UserCredInput {
+    pub tenant: String,
+    pub tms_user_id: String,
+    pub user_secret: String,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+impl UserCredInput {
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn new(
+        tenant: String,
+        tms_user_id: String,
+        user_secret: String,
+        created: DateTime<Utc>,
+        updated: DateTime<Utc>,
+    ) 
+    -> UserCredInput {
+        UserCredInput {
+            tenant, tms_user_id, user_secret, created, updated
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// cert_mappings:
+// ---------------------------------------------------------------------------
+#[derive(Debug, Deserialize)]
+pub struct CertMappingInput {
+    pub tenant: String,
+    pub cert_subject: String,
+    pub subject_type: String,
+    pub subject_id: String,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+impl CertMappingInput {
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn new(
+        tenant: String,
+        cert_subject: String,
+        subject_type: String,
+        subject_id: String,
+        created: DateTime<Utc>,
+        updated: DateTime<Utc>,
+    ) 
+    -> CertMappingInput {
+        CertMappingInput {
+            tenant, cert_subject, subject_type, subject_id, created, updated
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// client_approvals:
+// ---------------------------------------------------------------------------
+#[derive(Debug, Deserialize)]
+pub struct ClientApprovalInput {
+    pub tenant: String,
+    pub approval_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+impl ClientApprovalInput {
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn new(
+        tenant: String,
+        approval_hash: String,
+        expires_at: DateTime<Utc>,
+        created: DateTime<Utc>,
+        updated: DateTime<Utc>,
+    ) 
+    -> ClientApprovalInput {
+        ClientApprovalInput {
+            tenant, approval_hash, expires_at, created, updated
+        }
+    }
+}
@@ -0,0 +1,269 @@
+#![forbid(unsafe_code)]
+
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use hkdf::Hkdf;
+use rand::RngCore;
+use rsa::Oaep;
+use sha2::Sha256;
+use ssh_key::{HashAlg, LineEnding, PrivateKey, PublicKey};
+use ssh_key::public::KeyData;
+
+// ***************************************************************************
+//                          Private Key Encryption
+// ***************************************************************************
+// Private keys generated by TMS can be returned encrypted so that they never
+// leave the server in plaintext.  Two modes are supported:
+//
+//  passphrase - The key is returned as a passphrase protected OpenSSH private
+//               key (aes256-ctr with bcrypt-pbkdf key derivation), which ssh
+//               and ssh-keygen can use directly.
+//
+//  envelope   - The OpenSSH private key is encrypted with a random AES-256-GCM
+//               key that is itself encrypted to a recipient public key supplied
+//               by the client.  For ssh-ed25519 recipients, the AES key is
+//               derived with HKDF-SHA256 from an X25519 exchange between an
+//               ephemeral key and the recipient's key converted to its
+//               Montgomery form, as done by age.  For ssh-rsa recipients, the
+//               AES key is encrypted with RSA-OAEP using SHA-256.
+//
+// The envelope scheme name is the AES-GCM associated data.
+//
+// Private keys that TMS keeps, such as the tenant certificate authority keys,
+// are encrypted at rest with AES-256-GCM using the server's database encryption
+// key, which never leaves the server's certs directory.  The stored value is the
+// prefix followed by the base64 encoded nonce and ciphertext, and the purpose of
+// the value is the associated data.
+pub const SCHEME_X25519: &str = "x25519-hkdf-sha256-aes256gcm";
+pub const SCHEME_RSA_OAEP: &str = "rsa-oaep-sha256-aes256gcm";
+
+// Prefix of values encrypted at rest.
+pub const AT_REST_PREFIX: &str = "aes256gcm:";
+
+// HKDF context for envelope keys.
+const ENVELOPE_HKDF_INFO: &[u8] = b"tms-private-key-envelope";
+
+// Limits on client supplied values.
+const MIN_PASSPHRASE_LEN: usize = 8;
+const MIN_RSA_RECIPIENT_BITS: usize = 2048;
+
+// ---------------------------------------------------------------------------
+// KeyEnvelope:
+// ---------------------------------------------------------------------------
+// Binary fields are base64 encoded.
+#[derive(Debug)]
+pub struct KeyEnvelope {
+    pub scheme: String,
+    pub recipient_fingerprint: String,
+    pub encapsulated_key: String,  // ephemeral X25519 public key or RSA-OAEP encrypted AES key
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+// ***************************************************************************
+//                             Public Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// encrypt_with_passphrase:
+// ---------------------------------------------------------------------------
+/** Return the OpenSSH private key protected by the passphrase. */
+pub fn encrypt_with_passphrase(private_key: &str, passphrase: &str) -> Result<String> {
+    if passphrase.len() < MIN_PASSPHRASE_LEN {
+        return Err(anyhow!("BAD_REQUEST: The passphrase must have at least {} characters.", MIN_PASSPHRASE_LEN));
+    }
+
+    let prvkey = PrivateKey::from_openssh(private_key)
+        .map_err(|e| anyhow!("Unable to parse private key: {}", e))?;
+    let mut rng = rand::rngs::OsRng;
+    let encrypted = prvkey.encrypt(&mut rng, passphrase)
+        .map_err(|e| anyhow!("Unable to encrypt private key: {}", e))?;
+    match encrypted.to_openssh(LineEnding::LF) {
+        Ok(k) => Ok(k.to_string()),
+        Err(e) => Err(anyhow!("Failure to convert private key to SSH format: {}", e)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// encrypt_to_recipient:
+// ---------------------------------------------------------------------------
+/** Encrypt the private key to the recipient's OpenSSH public key, which must
+ * be an ed25519 key or an rsa key with at least 2048 bits.
+ */
+pub fn encrypt_to_recipient(private_key: &str, recipient: &str) -> Result<KeyEnvelope> {
+    let recipient = PublicKey::from_openssh(recipient.trim())
+        .map_err(|e| anyhow!("BAD_REQUEST: Unable to parse recipient public key: {}", e))?;
+    let mut rng = rand::rngs::OsRng;
+
+    // Get the content encryption key and how it is conveyed to the recipient.
+    let (scheme, encapsulated_key, cek) = match recipient.key_data() {
+        KeyData::Ed25519(key) => {
+            let recipient_point = CompressedEdwardsY(key.0).decompress()
+                .ok_or_else(|| anyhow!("BAD_REQUEST: Invalid ed25519 recipient public key."))?
+                .to_montgomery();
+            let mut ephemeral_secret = [0u8; 32];
+            rng.fill_bytes(&mut ephemeral_secret);
+            let ephemeral_point = MontgomeryPoint::mul_base_clamped(ephemeral_secret);
+            let shared = recipient_point.mul_clamped(ephemeral_secret);
+            let cek = derive_x25519_key(&ephemeral_point, &recipient_point, &shared)?;
+            (SCHEME_X25519, ephemeral_point.to_bytes().to_vec(), cek)
+        },
+        KeyData::Rsa(key) => {
+            let rsa_key = rsa::RsaPublicKey::try_from(key)
+                .map_err(|e| anyhow!("BAD_REQUEST: Invalid rsa recipient public key: {}", e))?;
+            if rsa::traits::PublicKeyParts::size(&rsa_key) * 8 < MIN_RSA_RECIPIENT_BITS {
+                return Err(anyhow!("BAD_REQUEST: Rsa recipient keys must have at least {} bits.",
+                                   MIN_RSA_RECIPIENT_BITS));
+            }
+            let mut cek = [0u8; 32];
+            rng.fill_bytes(&mut cek);
+            let wrapped = rsa_key.encrypt(&mut rng, Oaep::new::<Sha256>(), &cek)
+                .map_err(|e| anyhow!("Unable to encrypt envelope key: {}", e))?;
+            (SCHEME_RSA_OAEP, wrapped, cek)
+        },
+        _ => return Err(anyhow!("BAD_REQUEST: Unsupported recipient key algorithm {}, use an ed25519 or rsa key.",
+                                recipient.algorithm())),
+    };
+
+    // Encrypt the private key.
+    let mut nonce = [0u8; 12];
+    rng.fill_bytes(&mut nonce);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&cek));
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce),
+                                    Payload {msg: private_key.as_bytes(), aad: scheme.as_bytes()})
+        .map_err(|e| anyhow!("Unable to encrypt private key: {}", e))?;
+
+    Ok(KeyEnvelope {
+        scheme: scheme.to_string(),
+        recipient_fingerprint: recipient.fingerprint(HashAlg::Sha256).to_string(),
+        encapsulated_key: BASE64.encode(encapsulated_key),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// encrypt_at_rest:
+// ---------------------------------------------------------------------------
+/** Encrypt a value stored in the database with the server's key. */
+pub fn encrypt_at_rest(key: &[u8; 32], plaintext: &str, purpose: &str) -> Result<String> {
+    let mut nonce = [0u8; 12];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce),
+                                    Payload {msg: plaintext.as_bytes(), aad: purpose.as_bytes()})
+        .map_err(|e| anyhow!("Unable to encrypt {}: {}", purpose, e))?;
+
+    let mut data = nonce.to_vec();
+    data.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", AT_REST_PREFIX, BASE64.encode(data)))
+}
+
+// ---------------------------------------------------------------------------
+// decrypt_at_rest:
+// ---------------------------------------------------------------------------
+/** Decrypt a value that was encrypted with encrypt_at_rest() for the same purpose. */
+pub fn decrypt_at_rest(key: &[u8; 32], stored: &str, purpose: &str) -> Result<String> {
+    let encoded = stored.strip_prefix(AT_REST_PREFIX)
+        .ok_or_else(|| anyhow!("The stored {} is not encrypted.", purpose))?;
+    let data = BASE64.decode(encoded)
+        .map_err(|e| anyhow!("Unable to decode {}: {}", purpose, e))?;
+    if data.len() < 12 {
+        return Err(anyhow!("The stored {} is truncated.", purpose));
+    }
+    let (nonce, ciphertext) = data.split_at(12);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), Payload {msg: ciphertext, aad: purpose.as_bytes()})
+        .map_err(|_| anyhow!("Unable to decrypt {}, the database encryption key may have changed.", purpose))?;
+    String::from_utf8(plaintext).map_err(|e| anyhow!("Invalid {}: {}", purpose, e))
+}
+
+// ***************************************************************************
+//                             Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// derive_x25519_key:
+// ---------------------------------------------------------------------------
+// The HKDF salt binds the key to both public keys.
+fn derive_x25519_key(ephemeral: &MontgomeryPoint, recipient: &MontgomeryPoint,
+                     shared: &MontgomeryPoint) -> Result<[u8; 32]> {
+    if shared.to_bytes() == [0u8; 32] {
+        return Err(anyhow!("BAD_REQUEST: Invalid ed25519 recipient public key."));
+    }
+    let mut salt = Vec::with_capacity(64);
+    salt.extend_from_slice(ephemeral.as_bytes());
+    salt.extend_from_slice(recipient.as_bytes());
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes());
+    let mut key = [0u8; 32];
+    hkdf.expand(ENVELOPE_HKDF_INFO, &mut key)
+        .map_err(|e| anyhow!("Unable to derive envelope key: {}", e))?;
+    Ok(key)
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha512};
+    use crate::utils::keygen::{generate_key, KeyType};
+
+    #[test]
+    fn passphrase_round_trip() {
+        let key = generate_key(KeyType::Ed25519, 256).unwrap();
+        assert!(encrypt_with_passphrase(&key.private_key, "short").is_err());
+
+        let encrypted = encrypt_with_passphrase(&key.private_key, "correct horse").unwrap();
+        let parsed = PrivateKey::from_openssh(&encrypted).unwrap();
+        assert!(parsed.is_encrypted());
+        let decrypted = parsed.decrypt("correct horse").unwrap();
+        assert_eq!(decrypted.public_key().to_openssh().unwrap(), key.public_key);
+    }
+
+    #[test]
+    fn envelope_round_trip() {
+        let key = generate_key(KeyType::Ed25519, 256).unwrap();
+        let recipient = generate_key(KeyType::Ed25519, 256).unwrap();
+        let envelope = encrypt_to_recipient(&key.private_key, &recipient.public_key).unwrap();
+        assert_eq!(envelope.scheme, SCHEME_X25519);
+        assert_eq!(envelope.recipient_fingerprint, recipient.public_key_fingerprint);
+
+        // The recipient's X25519 secret is derived from its ed25519 seed.
+        let recipient_key = PrivateKey::from_openssh(&recipient.private_key).unwrap();
+        let seed = recipient_key.key_data().ed25519().unwrap().private.to_bytes();
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&Sha512::digest(seed)[..32]);
+        let recipient_point = MontgomeryPoint::mul_base_clamped(secret);
+
+        let ephemeral: [u8; 32] = BASE64.decode(&envelope.encapsulated_key).unwrap().try_into().unwrap();
+        let ephemeral = MontgomeryPoint(ephemeral);
+        let shared = ephemeral.mul_clamped(secret);
+        let cek = derive_x25519_key(&ephemeral, &recipient_point, &shared).unwrap();
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&cek));
+        let nonce = BASE64.decode(&envelope.nonce).unwrap();
+        let plaintext = cipher.decrypt(Nonce::from_slice(&nonce),
+                                       Payload {msg: &BASE64.decode(&envelope.ciphertext).unwrap(),
+                                                aad: SCHEME_X25519.as_bytes()}).unwrap();
+        assert_eq!(String::from_utf8(plaintext).unwrap(), key.private_key);
+    }
+
+    #[test]
+    fn at_rest_round_trip() {
+        let key = [7u8; 32];
+        let stored = encrypt_at_rest(&key, "secret value", "test value").unwrap();
+        assert!(stored.starts_with(AT_REST_PREFIX));
+        assert!(!stored.contains("secret value"));
+        assert_eq!(decrypt_at_rest(&key, &stored, "test value").unwrap(), "secret value");
+
+        // The key, the purpose and the prefix must all match.
+        assert!(decrypt_at_rest(&[8u8; 32], &stored, "test value").is_err());
+        assert!(decrypt_at_rest(&key, &stored, "other value").is_err());
+        assert!(decrypt_at_rest(&key, "secret value", "test value").is_err());
+    }
+}
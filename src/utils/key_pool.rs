@@ -0,0 +1,182 @@
+#![forbid(unsafe_code)]
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use log::{error, info};
+use tokio::sync::Notify;
+
+use crate::utils::keygen::{self, GeneratedKeyObj, KeyType, get_key_specs, key_spec, parse_key_spec};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                                 Key Pool
+// ***************************************************************************
+// Key generation is CPU bound and rsa key generation can take seconds, so keys
+// are always generated on tokio's blocking thread pool rather than on the async
+// worker threads.  When the key pool is enabled, a background task also keeps
+// a configured number of pre-generated keys of each key type on hand so that
+// requests for those key types are served without waiting.  Keys are taken
+// from the pool in the order they were generated and each key is handed out
+// at most once.  Pooled keys only exist in memory and are lost on restart.
+//
+// The refill task waits this long between attempts when a pool is full or
+// when key generation fails.
+const REFILL_WAIT_SECS: u64 = 60;
+
+// ---------------------------------------------------------------------------
+// KeyPool:
+// ---------------------------------------------------------------------------
+#[derive(Default)]
+struct KeyPool {
+    keys: VecDeque<GeneratedKeyObj>,
+    hits: u64,    // requests served from the pool
+    misses: u64,  // requests that found the pool empty
+}
+
+// The metrics of one key type's pool.
+#[derive(Debug)]
+pub struct KeyPoolStats {
+    pub key_type: String,
+    pub target: u32,
+    pub depth: u32,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+lazy_static! {
+    static ref KEY_POOLS: Mutex<HashMap<String, KeyPool>> = Mutex::new(HashMap::new());
+    static ref KEY_TAKEN: Notify = Notify::new();
+}
+
+// ***************************************************************************
+//                             Public Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// start_key_pool:
+// ---------------------------------------------------------------------------
+/** Start the task that fills the key pools if the pool is enabled in the
+ * configuration.
+ */
+pub fn start_key_pool() {
+    let config = &RUNTIME_CTX.parms.config.key_pool;
+    if !config.enabled {
+        info!("The pre-generated key pool is disabled.");
+        return;
+    }
+    info!("Starting the pre-generated key pool with sizes {:?}.", config.sizes);
+
+    tokio::spawn(async move {
+        loop {
+            fill_pools(&config.sizes).await;
+
+            // Wait until a key is taken or the wait period elapses.
+            let _ = tokio::time::timeout(Duration::from_secs(REFILL_WAIT_SECS), KEY_TAKEN.notified()).await;
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// get_key:
+// ---------------------------------------------------------------------------
+/** Return a key of the given type and length, taken from the key pool when
+ * one is available and generated on the blocking thread pool otherwise.
+ */
+pub async fn get_key(key_type: KeyType, key_bits: i32) -> Result<GeneratedKeyObj> {
+    // Try the pool first.
+    let config = &RUNTIME_CTX.parms.config.key_pool;
+    let spec = key_spec(key_type, key_bits);
+    if config.enabled && config.sizes.get(&spec).is_some_and(|size| *size > 0) {
+        let pooled = {
+            let mut pools = KEY_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+            let pool = pools.entry(spec).or_default();
+            let key = pool.keys.pop_front();
+            if key.is_some() {pool.hits += 1;} else {pool.misses += 1;}
+            key
+        };
+        KEY_TAKEN.notify_one();
+        if let Some(key) = pooled {
+            return Ok(key);
+        }
+    }
+
+    // Generate the key without blocking an async worker thread.
+    generate_key_blocking(key_type, key_bits).await
+}
+
+// ---------------------------------------------------------------------------
+// get_key_pool_stats:
+// ---------------------------------------------------------------------------
+/** Return the metrics of each configured key pool in key type order. */
+pub fn get_key_pool_stats() -> Vec<KeyPoolStats> {
+    let config = &RUNTIME_CTX.parms.config.key_pool;
+    let pools = KEY_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+    get_key_specs().into_iter()
+        .filter_map(|spec| {
+            let target = *config.sizes.get(&spec)?;
+            let pool = pools.get(&spec);
+            Some(KeyPoolStats {
+                depth: pool.map_or(0, |p| p.keys.len() as u32),
+                hits: pool.map_or(0, |p| p.hits),
+                misses: pool.map_or(0, |p| p.misses),
+                key_type: spec,
+                target,
+            })
+        })
+        .collect()
+}
+
+// ***************************************************************************
+//                             Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// fill_pools:
+// ---------------------------------------------------------------------------
+/** Add keys to the pools that are below their target size, one key per pool
+ * in turn, until all pools are full.  Generation errors end the fill attempt.
+ */
+async fn fill_pools(sizes: &HashMap<String, u32>) {
+    loop {
+        let mut added = false;
+        for (spec, target) in sizes {
+            // Skip full pools.
+            let depth = {
+                let pools = KEY_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+                pools.get(spec).map_or(0, |p| p.keys.len() as u32)
+            };
+            if depth >= *target {continue;}
+
+            // Generate the key and add it to the pool.
+            let key = match parse_key_spec(spec) {
+                Ok((key_type, key_bits)) => generate_key_blocking(key_type, key_bits).await,
+                Err(e) => Err(e),
+            };
+            match key {
+                Ok(key) => {
+                    let mut pools = KEY_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+                    pools.entry(spec.clone()).or_default().keys.push_back(key);
+                    added = true;
+                },
+                Err(e) => {
+                    error!("Unable to add a {} key to the key pool: {}", spec, e);
+                    return;
+                }
+            }
+        }
+        if !added {break;}
+    }
+}
+
+// ---------------------------------------------------------------------------
+// generate_key_blocking:
+// ---------------------------------------------------------------------------
+async fn generate_key_blocking(key_type: KeyType, key_bits: i32) -> Result<GeneratedKeyObj> {
+    match tokio::task::spawn_blocking(move || keygen::generate_key(key_type, key_bits)).await {
+        Ok(result) => result,
+        Err(e) => Err(anyhow!("Key generation task failed: {}", e)),
+    }
+}
@@ -3,13 +3,16 @@
 use core::panic;
 use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 
 use anyhow::{Result, anyhow};
 use log::error;
 use serde::Deserialize;
 use lazy_static::lazy_static;
 
-use ssh_key::{Algorithm, HashAlg, EcdsaCurve, PrivateKey};
+use ssh_key::{Algorithm, HashAlg, EcdsaCurve, PrivateKey, PublicKey};
+use ssh_key::public::KeyData;
+use ssh_key::private::RsaKeypair;
 
 /* Generate ed25519, ecdsa or rsa keys using a native rust implementation and
  * the OS hardware-based random number generator.  See the sshkeytest code
@@ -23,15 +26,31 @@ use ssh_key::{Algorithm, HashAlg, EcdsaCurve, PrivateKey};
 // ***************************************************************************
 //                             Static Variables
 // ***************************************************************************
-// The number of bit used to create each key are hardcoded in TMS.
+// The key lengths in bits that TMS generates for each key type, the first 
+// length being the default.  Key types not in the map are never generated.
 lazy_static! {
-    static ref KEY_LEN_MAP: HashMap<KeyType, i32> = get_key_len_map();
+    static ref KEY_LEN_MAP: HashMap<KeyType, Vec<i32>> = get_key_len_map();
 }
 
+// The smallest rsa key accepted from clients.
+pub const MIN_RSA_KEY_BITS: i32 = 2048;
+
+// The generated key types in the order they are listed to users.
+const GENERATED_KEY_TYPES: [KeyType; 3] = [KeyType::Ed25519, KeyType::Ecdsa, KeyType::Rsa];
+
+// The FIDO security key types that clients can register but TMS never generates.
+const SK_KEY_TYPES: [KeyType; 2] = [KeyType::Ed25519Sk, KeyType::EcdsaSk];
+
+// The length of the FIDO security key types, which each use a single curve.
+const SK_KEY_BITS: i32 = 256;
+
+// The authorized_keys options that can be set on security keys.
+pub const SK_KEY_FLAGS: [&str; 2] = ["no-touch-required", "verify-required"];
+
 // ***************************************************************************
 //                                Enums
 // ***************************************************************************
-#[derive(Debug, Eq, PartialEq, Hash, Deserialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize)]
 pub enum KeyType { 
     Dsa,
     Ecdsa,
@@ -55,6 +74,22 @@ impl fmt::Display for KeyType {
     }
 }
 
+// Convert the string representation back to the enum.
+impl FromStr for KeyType {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dsa"        => Ok(KeyType::Dsa),
+            "ecdsa"      => Ok(KeyType::Ecdsa),
+            "ecdsa-sk"   => Ok(KeyType::EcdsaSk),
+            "ed25519"    => Ok(KeyType::Ed25519),
+            "ed25519-sk" => Ok(KeyType::Ed25519Sk),
+            "rsa"        => Ok(KeyType::Rsa),
+            _ => Err(anyhow!("Unknown key type: {}", s)),
+        }
+    }
+}
+
 // ***************************************************************************
 //                            KeygenConfig Structs
 // ***************************************************************************
@@ -77,6 +112,37 @@ impl GeneratedKeyObj {
     }
 }
 
+// ---------------------------------------------------------------------------
+// ParsedPubkeyObj:
+// ---------------------------------------------------------------------------
+// A public key supplied by a client rather than generated by TMS.  Security 
+// keys also carry the application string chosen when the key was enrolled.
+#[derive(Debug)]
+pub struct ParsedPubkeyObj {
+    pub public_key: String,
+    pub public_key_fingerprint: String,
+    pub key_type: String,
+    pub key_bits: i32,
+    pub sk_application: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// KeyTypeInfo:
+// ---------------------------------------------------------------------------
+// What TMS supports for a key type.  Generated key types can also be registered,
+// rsa keys of any length of at least min_key_bits.
+#[derive(Debug)]
+pub struct KeyTypeInfo {
+    pub key_type: KeyType,
+    pub key_bits: Vec<i32>,
+    pub default_key_bits: Option<i32>,
+    pub min_key_bits: Option<i32>,
+    pub curves: Vec<String>,
+    pub generated: bool,
+    pub registered: bool,
+    pub key_specs: Vec<String>,
+}
+
 // ***************************************************************************
 //                               Functions
 // ***************************************************************************
@@ -88,28 +154,238 @@ pub fn init_keygen() {
     // Get the bit length any key type to force map initialization. 
     // Should never fail, but if it does fail, we abort execution.
     let key_type = KeyType::Ed25519;
-    let bitlen = *KEY_LEN_MAP.get(&key_type)
+    let bitlen = *KEY_LEN_MAP.get(&key_type).and_then(|v| v.first())
         .unwrap_or_else(|| panic!("Unable to determine bit length for key type {}.", key_type));
 }
 
+// ---------------------------------------------------------------------------
+// parse_key_type:
+// ---------------------------------------------------------------------------
+/** Convert a key type requested by a user into a generated key type.  A 
+ * missing, empty or DEFAULT key type selects ed25519.  Matching ignores case.
+ */
+pub fn parse_key_type(key_type: &Option<String>) -> Result<KeyType> {
+    let key_type_upper = match key_type {
+        Some(k) => k.trim().to_uppercase(),
+        None => String::new(),
+    };
+    match key_type_upper.as_str() {
+        "" | "DEFAULT" => Ok(KeyType::Ed25519),
+        "ED25519" => Ok(KeyType::Ed25519),
+        "ECDSA" => Ok(KeyType::Ecdsa),
+        "RSA" => Ok(KeyType::Rsa),
+        _ => Err(anyhow!("Unsupported key type: {}. The supported key types are RSA, ECDSA and ED25519.", 
+                         key_type_upper)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// get_key_type_info:
+// ---------------------------------------------------------------------------
+/** Describe each supported key type, generated types first, using the same 
+ * tables that key generation, key registration and tenant policies use. 
+ */
+pub fn get_key_type_info() -> Vec<KeyTypeInfo> {
+    let mut info = vec!();
+    for key_type in GENERATED_KEY_TYPES {
+        if let Ok(lengths) = get_key_lengths(key_type) {
+            let mut key_bits = lengths.clone();
+            key_bits.sort();
+            info.push(KeyTypeInfo {
+                key_type,
+                curves: key_curves(key_type, &key_bits),
+                key_specs: key_bits.iter().map(|bits| key_spec(key_type, *bits)).collect(),
+                key_bits,
+                default_key_bits: lengths.first().copied(),
+                min_key_bits: if key_type == KeyType::Rsa {Some(MIN_RSA_KEY_BITS)} else {None},
+                generated: true,
+                registered: true,
+            });
+        }
+    }
+    for key_type in SK_KEY_TYPES {
+        info.push(KeyTypeInfo {
+            key_type,
+            key_bits: vec![SK_KEY_BITS],
+            default_key_bits: None,
+            min_key_bits: None,
+            curves: key_curves(key_type, &[SK_KEY_BITS]),
+            generated: false,
+            registered: true,
+            key_specs: vec![key_spec(key_type, SK_KEY_BITS)],
+        });
+    }
+    info
+}
+
+// ---------------------------------------------------------------------------
+// get_key_bits:
+// ---------------------------------------------------------------------------
+/** Return the requested key length if TMS generates keys of that length for
+ * the key type, or the key type's default length if no length is requested.
+ */
+pub fn get_key_bits(key_type: KeyType, key_bits: Option<i32>) -> Result<i32> {
+    let lengths = get_key_lengths(key_type)?;
+    match key_bits {
+        None => Ok(lengths[0]),
+        Some(bits) if lengths.contains(&bits) => Ok(bits),
+        Some(bits) => Err(anyhow!("Unsupported key length for {} keys: {}. The supported lengths are {:?}.", 
+                                  key_type, bits, lengths)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// get_key_specs:
+// ---------------------------------------------------------------------------
+/** Return the names of all key type and length combinations that TMS 
+ * generates, such as ed25519, ecdsa-384 and rsa-3072.  These names are used
+ * in tenant key type policies.
+ */
+pub fn get_key_specs() -> Vec<String> {
+    let mut specs = vec!();
+    for key_type in GENERATED_KEY_TYPES {
+        if let Ok(lengths) = get_key_lengths(key_type) {
+            let mut lengths = lengths.clone();
+            lengths.sort();
+            for bits in lengths {
+                specs.push(key_spec(key_type, bits));
+            }
+        }
+    }
+    specs
+}
+
+// ---------------------------------------------------------------------------
+// key_spec:
+// ---------------------------------------------------------------------------
+/** Return the policy name of a key type and length.  Key types generated with
+ * a single length are named by type alone. 
+ */
+pub fn key_spec(key_type: KeyType, key_bits: i32) -> String {
+    match get_key_lengths(key_type) {
+        Ok(lengths) if lengths.len() > 1 => format!("{}-{}", key_type, key_bits),
+        _ => key_type.to_string(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// parse_key_spec:
+// ---------------------------------------------------------------------------
+/** Return the key type and length named by a key spec. */
+pub fn parse_key_spec(spec: &str) -> Result<(KeyType, i32)> {
+    for key_type in GENERATED_KEY_TYPES {
+        for bits in get_key_lengths(key_type)? {
+            if key_spec(key_type, *bits) == spec {
+                return Ok((key_type, *bits));
+            }
+        }
+    }
+    Err(anyhow!("Unsupported key type: {}. The supported key types are {:?}.", spec, get_key_specs()))
+}
+
+// ---------------------------------------------------------------------------
+// get_policy_key_specs:
+// ---------------------------------------------------------------------------
+/** Return the key specs that can appear in tenant key type policies, which 
+ * are the generated key specs followed by the security key types that can 
+ * only be registered, such as ed25519-sk.
+ */
+pub fn get_policy_key_specs() -> Vec<String> {
+    let mut specs = get_key_specs();
+    specs.extend(SK_KEY_TYPES.iter().map(|key_type| key_type.to_string()));
+    specs
+}
+
+// ---------------------------------------------------------------------------
+// validate_key_specs:
+// ---------------------------------------------------------------------------
+/** Check that each name in a list of generated key types, such as the key 
+ * pool sizes, is a supported key spec.
+ */
+pub fn validate_key_specs(specs: &[String]) -> Result<()> {
+    check_key_specs(specs, get_key_specs())
+}
+
+// ---------------------------------------------------------------------------
+// validate_policy_key_specs:
+// ---------------------------------------------------------------------------
+/** Check that each name in a tenant key type policy is a supported key spec. */
+pub fn validate_policy_key_specs(specs: &[String]) -> Result<()> {
+    check_key_specs(specs, get_policy_key_specs())
+}
+
+// ---------------------------------------------------------------------------
+// check_sk_flags:
+// ---------------------------------------------------------------------------
+/** Validate the authorized_keys options requested for a key and return them 
+ * sorted without duplicates.  Only security keys can have flags.
+ */
+pub fn check_sk_flags(key_type: KeyType, flags: &Option<Vec<String>>) -> Result<Vec<String>> {
+    let mut flags = match flags {
+        Some(f) => f.iter().map(|flag| flag.trim().to_string()).collect::<Vec<String>>(),
+        None => return Ok(vec!()),
+    };
+    if flags.is_empty() {return Ok(flags);}
+    if !SK_KEY_TYPES.contains(&key_type) {
+        return Err(anyhow!("Security key flags cannot be set on {} keys.", key_type));
+    }
+    if let Some(flag) = flags.iter().find(|flag| !SK_KEY_FLAGS.contains(&flag.as_str())) {
+        return Err(anyhow!("Unsupported security key flag: {}. The supported flags are {:?}.", 
+                           flag, SK_KEY_FLAGS));
+    }
+    flags.sort();
+    flags.dedup();
+    Ok(flags)
+}
+
+// ---------------------------------------------------------------------------
+// check_key_allowed:
+// ---------------------------------------------------------------------------
+/** Check that a key's type and length are allowed by a tenant's key type 
+ * policy, where no policy allows all supported keys.  Client supplied rsa keys 
+ * can have any length, so they are checked as if they had the largest length 
+ * that TMS generates that does not exceed their actual length.
+ */
+pub fn check_key_allowed(allowed: &Option<Vec<String>>, key_type: KeyType, key_bits: i32) -> Result<()> {
+    let allowed = match allowed {
+        Some(a) => a,
+        None => return Ok(()),
+    };
+
+    // Round client supplied rsa key lengths down to a generated length.
+    let mut policy_bits = key_bits;
+    if key_type == KeyType::Rsa {
+        if let Some(bits) = get_key_lengths(key_type)?.iter().filter(|b| **b <= key_bits).max() {
+            policy_bits = *bits;
+        }
+    }
+
+    let spec = key_spec(key_type, policy_bits);
+    if allowed.contains(&spec) {Ok(())}
+    else {Err(anyhow!("Key type {} is not allowed in this tenant. The allowed key types are {:?}.", spec, allowed))}
+}
+
 // ---------------------------------------------------------------------------
 // generate_key:
 // ---------------------------------------------------------------------------
-pub fn generate_key(key_type: KeyType) -> Result<GeneratedKeyObj> {
+/** Generate a key pair of the given type and length.  The length must be 
+ * one that get_key_bits() accepts for the key type.
+ */
+pub fn generate_key(key_type: KeyType, key_bits: i32) -> Result<GeneratedKeyObj> {
 
     // --------------------- Generate Key ---------------------
     // --------------------------------------------------------
     // Generate the private key based on the key type.
+    let key_bits = get_key_bits(key_type, Some(key_bits))?;
     let gen_result = match key_type {
         KeyType::Ed25519 => gen_private_key(Algorithm::Ed25519),
         KeyType::Ecdsa   => {
-            let curve = EcdsaCurve::NistP521;
-            gen_private_key(Algorithm::Ecdsa {curve})
-        },
-        KeyType::Rsa     => {
-            let hash = Some(HashAlg::Sha256);
-            gen_private_key(Algorithm::Rsa {hash})
+            match ecdsa_curve(key_bits) {
+                Some(curve) => gen_private_key(Algorithm::Ecdsa {curve}),
+                None => Err(anyhow!("Unsupported key length for {} keys: {}", key_type, key_bits)),
+            }
         },
+        KeyType::Rsa     => gen_rsa_private_key(key_bits),
         _ => {Err(anyhow!("Algorithm not supported: {}", key_type.to_string()))},
     };
 
@@ -150,16 +426,6 @@ pub fn generate_key(key_type: KeyType) -> Result<GeneratedKeyObj> {
 
     // -------------------------- Package Results ----------------------------
     // -----------------------------------------------------------------------
-    // Get the bit length for this key type. This should never fail, but if it does just return 0.
-    let bitlen = *KEY_LEN_MAP.get(&key_type)
-        .unwrap_or(&(0_i32));
-
-    // Substitute the a value for fixed length keys that we generate.
-    let mut key_bits = bitlen;
-    if key_bits == 0 && key_type == KeyType::Ed25519 {
-        key_bits = 256;
-    }
-
     // Return a newly populated key object.
     Ok(GeneratedKeyObj::new(ssh_prvkey, 
                             ssh_pubkey, 
@@ -169,6 +435,74 @@ pub fn generate_key(key_type: KeyType) -> Result<GeneratedKeyObj> {
                         ))
 }
 
+// ---------------------------------------------------------------------------
+// parse_public_key:
+// ---------------------------------------------------------------------------
+/** Parse a public key in OpenSSH format and calculate its fingerprint.  Only
+ * the algorithms that TMS generates and their FIDO security key variants are
+ * accepted and rsa keys must have at least MIN_RSA_KEY_BITS bits.  The key's
+ * comment is not saved.
+ */
+pub fn parse_public_key(openssh: &str) -> Result<ParsedPubkeyObj> {
+    // Parse the key.
+    let mut pubkey = match PublicKey::from_openssh(openssh.trim()) {
+        Ok(k) => k,
+        Err(e) => return Err(anyhow!("Unable to parse OpenSSH public key: {}", e)),
+    };
+
+    // Check the algorithm and key length.
+    let (key_type, key_bits) = match pubkey.algorithm() {
+        Algorithm::Ed25519 => (KeyType::Ed25519, 256),
+        Algorithm::Ecdsa {curve} => {
+            let bits = match curve {
+                EcdsaCurve::NistP256 => 256,
+                EcdsaCurve::NistP384 => 384,
+                EcdsaCurve::NistP521 => 521,
+            };
+            (KeyType::Ecdsa, bits)
+        },
+        Algorithm::Rsa {..} => {
+            let bits = pubkey.key_data().rsa()
+                .and_then(|k| k.n.as_positive_bytes())
+                .map(|n| match n.first() {
+                    Some(b) => (n.len() * 8) as i32 - b.leading_zeros() as i32,
+                    None => 0,
+                })
+                .unwrap_or(0);
+            if bits < MIN_RSA_KEY_BITS {
+                return Err(anyhow!("Weak {} bit rsa key rejected, at least {} bits are required.", 
+                                   bits, MIN_RSA_KEY_BITS));
+            }
+            (KeyType::Rsa, bits)
+        },
+        Algorithm::SkEd25519 => (KeyType::Ed25519Sk, SK_KEY_BITS),
+        Algorithm::SkEcdsaSha2NistP256 => (KeyType::EcdsaSk, SK_KEY_BITS),
+        alg => return Err(anyhow!("Algorithm not supported: {}", alg)),
+    };
+
+    // Security keys are bound to the application chosen at enrollment.
+    let sk_application = match pubkey.key_data() {
+        KeyData::SkEd25519(k) => Some(k.application().to_string()),
+        KeyData::SkEcdsaSha2NistP256(k) => Some(k.application().to_string()),
+        _ => None,
+    };
+
+    // Get the ssh formatted public key without its comment.
+    pubkey.set_comment("");
+    let ssh_pubkey = match pubkey.to_openssh() {
+        Ok(k) => k,
+        Err(e) => return Err(anyhow!("Failure to convert public key to SSH format: {}", e)),
+    };
+
+    Ok(ParsedPubkeyObj {
+        public_key: ssh_pubkey,
+        public_key_fingerprint: pubkey.fingerprint(HashAlg::Sha256).to_string(),
+        key_type: key_type.to_string(),
+        key_bits,
+        sk_application,
+    })
+}
+
 // ---------------------------------------------------------------------------
 // gen_private_key:
 // ---------------------------------------------------------------------------
@@ -188,18 +522,159 @@ fn gen_private_key(algorithm: Algorithm) -> Result<PrivateKey> {
     }
 }
 
+// ---------------------------------------------------------------------------
+// gen_rsa_private_key:
+// ---------------------------------------------------------------------------
+fn gen_rsa_private_key(key_bits: i32) -> Result<PrivateKey> {
+    
+    // Use operating system's random number generator.
+    let mut rng = rand::rngs::OsRng;
+
+    // Generate the private key with the requested modulus length.
+    match RsaKeypair::random(&mut rng, key_bits as usize) {
+        Ok(k) => Ok(PrivateKey::from(k)),
+        Err(e) => {
+            let msg = format!("Unable to generate {} bit rsa key: {}", key_bits, e);
+            Err(anyhow!(msg))
+        }
+    }
+}
+
 // ***************************************************************************
 //                            Private Functions
 // ***************************************************************************
 // One-time initialization routine that defines the bit lengths used for the various key types.
-fn get_key_len_map() -> HashMap<KeyType, i32> {
+fn get_key_len_map() -> HashMap<KeyType, Vec<i32>> {
     // Create the key type bit length mappings.
     let mut key_len_map = HashMap::new();
-    key_len_map.insert(KeyType::Dsa, 1024);    // required length
-    key_len_map.insert(KeyType::Ecdsa, 521);   // max allowed
-    key_len_map.insert(KeyType::EcdsaSk, 0);   // ssh-keygen ignored
-    key_len_map.insert(KeyType::Ed25519, 0);   // ssh-keygen ignored
-    key_len_map.insert(KeyType::Ed25519Sk, 0); // ssh-keygen ignored
-    key_len_map.insert(KeyType::Rsa, 4096);    // ssh-keygen default is 3072
+    key_len_map.insert(KeyType::Ed25519, vec![256]);           // fixed length
+    key_len_map.insert(KeyType::Ecdsa, vec![521, 384, 256]);   // nist curves
+    key_len_map.insert(KeyType::Rsa, vec![4096, 3072, 2048]);  // ssh-keygen default is 3072
     key_len_map
-}
\ No newline at end of file
+}
+
+// Get the NIST curve of an ecdsa key length.
+fn ecdsa_curve(key_bits: i32) -> Option<EcdsaCurve> {
+    match key_bits {
+        256 => Some(EcdsaCurve::NistP256),
+        384 => Some(EcdsaCurve::NistP384),
+        521 => Some(EcdsaCurve::NistP521),
+        _   => None,
+    }
+}
+
+// Get the names of the curves used by the lengths of an elliptic curve key type.
+fn key_curves(key_type: KeyType, key_bits: &[i32]) -> Vec<String> {
+    match key_type {
+        KeyType::Ecdsa | KeyType::EcdsaSk => key_bits.iter()
+            .filter_map(|bits| ecdsa_curve(*bits))
+            .map(|curve| curve.as_str().to_string())
+            .collect(),
+        KeyType::Ed25519 | KeyType::Ed25519Sk => vec!["ed25519".to_string()],
+        _ => vec!(),
+    }
+}
+
+// Check each key spec against a list of supported specs.
+fn check_key_specs(specs: &[String], supported: Vec<String>) -> Result<()> {
+    for spec in specs {
+        if !supported.contains(spec) {
+            return Err(anyhow!("Unsupported key type: {}. The supported key types are {:?}.", spec, supported));
+        }
+    }
+    Ok(())
+}
+
+// Get the lengths of a generated key type.
+fn get_key_lengths(key_type: KeyType) -> Result<&'static Vec<i32>> {
+    match KEY_LEN_MAP.get(&key_type) {
+        Some(lengths) => Ok(lengths),
+        None => Err(anyhow!("Algorithm not supported: {}", key_type)),
+    }
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_generated_keys() {
+        for (key_type, key_bits) in [(KeyType::Ed25519, 256), (KeyType::Ecdsa, 256), (KeyType::Ecdsa, 384)] {
+            let key = generate_key(key_type, key_bits).unwrap();
+            let parsed = parse_public_key(&format!("{} user@example.com\n", key.public_key)).unwrap();
+            assert_eq!(parsed.public_key, key.public_key);
+            assert_eq!(parsed.public_key_fingerprint, key.public_key_fingerprint);
+            assert_eq!(parsed.key_type, key.key_type);
+            assert_eq!(parsed.key_bits, key.key_bits);
+        }
+    }
+
+    #[test]
+    fn reject_unsupported_keys() {
+        assert!(parse_public_key("ssh-ed25519 not-base64").is_err());
+        let rsa_1024 = concat!("ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAAAgQC//iQsxvJ+1spaj6H7hXqjOquFTBF7QWlcrPkeh6EZD",
+            "uAdKkExxI70mpdD6Q5OytIdBo9seqUmgaL0/2MdlGljh/JbOj8doxt9xkA4O//dmt3NuwiTD/HHVaNsd5X4CCv73jqmTL",
+            "HUz6AbdRt0Bum9ofzz13ZG5VxStrmR0hbJmw== weak");
+        let err = parse_public_key(rsa_1024).unwrap_err();
+        assert!(err.to_string().contains("1024 bit"));
+    }
+
+    #[test]
+    fn parse_security_keys() {
+        let key = generate_key(KeyType::Ed25519, 256).unwrap();
+        let ed25519 = PublicKey::from_openssh(&key.public_key).unwrap();
+        let sk = ssh_key::public::SkEd25519::new(*ed25519.key_data().ed25519().unwrap(), "ssh:tms");
+        let sk_openssh = PublicKey::new(KeyData::SkEd25519(sk), "").to_openssh().unwrap();
+        let parsed = parse_public_key(&sk_openssh).unwrap();
+        assert!(parsed.public_key.starts_with("sk-ssh-ed25519@openssh.com "));
+        assert_eq!(parsed.key_type, "ed25519-sk");
+        assert_eq!(parsed.sk_application, Some("ssh:tms".to_string()));
+        assert_eq!(parsed.key_type.parse::<KeyType>().unwrap(), KeyType::Ed25519Sk);
+        assert!(parse_key_type(&Some(parsed.key_type)).is_err());
+
+        let flags = Some(vec!["verify-required".to_string(), "no-touch-required".to_string()]);
+        assert_eq!(check_sk_flags(KeyType::Ed25519Sk, &flags).unwrap(), ["no-touch-required", "verify-required"]);
+        assert!(check_sk_flags(KeyType::Ed25519, &flags).is_err());
+        assert!(check_sk_flags(KeyType::EcdsaSk, &Some(vec!["no-pty".to_string()])).is_err());
+        assert!(validate_policy_key_specs(&["ecdsa-sk".to_string()]).is_ok());
+        assert!(validate_key_specs(&["ecdsa-sk".to_string()]).is_err());
+    }
+
+    #[test]
+    fn key_type_info() {
+        let info = get_key_type_info();
+        let specs: Vec<String> = info.iter().flat_map(|i| i.key_specs.clone()).collect();
+        assert_eq!(specs, get_policy_key_specs());
+        let ecdsa = info.iter().find(|i| i.key_type == KeyType::Ecdsa).unwrap();
+        assert_eq!(ecdsa.curves, ["nistp256", "nistp384", "nistp521"]);
+        assert_eq!(ecdsa.default_key_bits, Some(521));
+        let ecdsa_sk = info.iter().find(|i| i.key_type == KeyType::EcdsaSk).unwrap();
+        assert!(!ecdsa_sk.generated && ecdsa_sk.registered);
+        assert_eq!(ecdsa_sk.curves, ["nistp256"]);
+        assert!(!info.iter().any(|i| i.key_type == KeyType::Dsa));
+    }
+
+    #[test]
+    fn key_type_policy() {
+        assert_eq!(parse_key_type(&None).unwrap(), KeyType::Ed25519);
+        assert_eq!(parse_key_type(&Some("rsa".to_string())).unwrap(), KeyType::Rsa);
+        assert!(parse_key_type(&Some("dsa".to_string())).is_err());
+        assert_eq!(get_key_bits(KeyType::Ecdsa, None).unwrap(), 521);
+        assert!(get_key_bits(KeyType::Rsa, Some(1024)).is_err());
+        assert_eq!(get_key_specs(), ["ed25519", "ecdsa-256", "ecdsa-384", "ecdsa-521",
+                                     "rsa-2048", "rsa-3072", "rsa-4096"]);
+        assert_eq!(parse_key_spec("rsa-3072").unwrap(), (KeyType::Rsa, 3072));
+        assert_eq!(parse_key_spec("ed25519").unwrap(), (KeyType::Ed25519, 256));
+        assert!(parse_key_spec("ecdsa").is_err());
+
+        let allowed = Some(vec!["ed25519".to_string(), "rsa-3072".to_string()]);
+        assert!(check_key_allowed(&None, KeyType::Rsa, 2048).is_ok());
+        assert!(check_key_allowed(&allowed, KeyType::Ed25519, 256).is_ok());
+        assert!(check_key_allowed(&allowed, KeyType::Rsa, 3500).is_ok());
+        assert!(check_key_allowed(&allowed, KeyType::Rsa, 4096).is_err());
+        assert!(check_key_allowed(&allowed, KeyType::Ecdsa, 256).is_err());
+    }
+}
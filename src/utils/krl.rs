@@ -0,0 +1,172 @@
+#![forbid(unsafe_code)]
+
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use ssh_key::{Fingerprint, HashAlg, LineEnding, PrivateKey, PublicKey};
+
+use crate::utils::ssh_ca::TenantCa;
+
+// ***************************************************************************
+//                          Key Revocation Lists
+// ***************************************************************************
+// TMS publishes the public keys that were deleted or expired in a tenant as an
+// OpenSSH key revocation list (KRL), which sshd reads from the file named by
+// its RevokedKeys option.  The KRL format is described in OpenSSH's
+// PROTOCOL.krl file.  Keys are revoked by their SHA256 fingerprints, which
+// OpenSSH 7.9 and later support, and the user certificates signed for them are
+// also revoked by the serial numbers assigned by the tenant's CA.
+//
+// OpenSSH no longer verifies the signatures that can be embedded in a KRL, so
+// TMS signs the whole KRL with the tenant's CA key instead.  The signature is
+// in the format created by "ssh-keygen -Y sign" using the KRL_NAMESPACE
+// namespace, so hosts can check it with "ssh-keygen -Y verify" before
+// installing the KRL.
+pub const KRL_NAMESPACE: &str = "tms-krl";
+
+// KRL header and section constants.
+const KRL_MAGIC: &[u8; 8] = b"SSHKRL\n\0";
+const KRL_FORMAT_VERSION: u32 = 1;
+const KRL_SECTION_CERTIFICATES: u8 = 1;
+const KRL_SECTION_FINGERPRINT_SHA256: u8 = 5;
+const KRL_SECTION_CERT_SERIAL_LIST: u8 = 0x20;
+
+// ***************************************************************************
+//                             Public Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// build_krl:
+// ---------------------------------------------------------------------------
+/** Build a binary KRL that revokes the keys with the given SHA256 fingerprints,
+ * such as SHA256:jKcQ0SXmuAT0Y5bu3TqI4KBUEfHZS9XkNZxkNvBtEOE, and the
+ * certificates with the given serial numbers signed by the CA public key.  The
+ * generation time is used as the KRL version so that later KRLs have larger 
+ * versions.
+ */
+pub fn build_krl(fingerprints: &[String], cert_serials: &[i64], ca_public_key: &str,
+                 generated: DateTime<Utc>, comment: &str) -> Result<Vec<u8>> {
+    // Fingerprint hashes must be sorted and unique.
+    let mut hashes: Vec<Vec<u8>> = vec!();
+    for fp in fingerprints {
+        let hash = match Fingerprint::from_str(fp) {
+            Ok(f) if f.algorithm() == HashAlg::Sha256 => f.as_bytes().to_vec(),
+            _ => return Err(anyhow!("Invalid SHA256 public key fingerprint: {}", fp)),
+        };
+        hashes.push(hash);
+    }
+    hashes.sort();
+    hashes.dedup();
+
+    // Serial numbers must be sorted, unique and non-zero.
+    let mut serials: Vec<u64> = vec!();
+    for serial in cert_serials {
+        if *serial < 1 {
+            return Err(anyhow!("Invalid certificate serial number: {}", serial));
+        }
+        serials.push(*serial as u64);
+    }
+    serials.sort();
+    serials.dedup();
+
+    // Write the header.
+    let timestamp = generated.timestamp().max(0) as u64;
+    let mut krl: Vec<u8> = Vec::with_capacity(64 + hashes.len() * 36);
+    krl.extend_from_slice(KRL_MAGIC);
+    krl.extend_from_slice(&KRL_FORMAT_VERSION.to_be_bytes());
+    krl.extend_from_slice(&timestamp.to_be_bytes());  // krl_version
+    krl.extend_from_slice(&timestamp.to_be_bytes());  // generated_date
+    krl.extend_from_slice(&0u64.to_be_bytes());       // flags
+    put_string(&mut krl, b"");                         // reserved
+    put_string(&mut krl, comment.as_bytes());
+
+    // Write the certificates section for the CA, which is omitted when there's
+    // nothing to revoke.
+    if !serials.is_empty() {
+        let ca_key = PublicKey::from_openssh(ca_public_key)
+            .map_err(|e| anyhow!("INTERNAL ERROR: Unable to parse certificate authority public key: {}", e))?;
+        let mut serial_list: Vec<u8> = Vec::with_capacity(serials.len() * 8);
+        for serial in serials {
+            serial_list.extend_from_slice(&serial.to_be_bytes());
+        }
+        let mut section: Vec<u8> = vec!();
+        put_string(&mut section, &ca_key.to_bytes()?);
+        put_string(&mut section, b"");                 // reserved
+        section.push(KRL_SECTION_CERT_SERIAL_LIST);
+        put_string(&mut section, &serial_list);
+        krl.push(KRL_SECTION_CERTIFICATES);
+        put_string(&mut krl, &section);
+    }
+
+    // Write the fingerprint section, which is omitted when there's nothing to revoke.
+    if !hashes.is_empty() {
+        let mut section: Vec<u8> = Vec::with_capacity(hashes.len() * 36);
+        for hash in hashes {
+            put_string(&mut section, &hash);
+        }
+        krl.push(KRL_SECTION_FINGERPRINT_SHA256);
+        put_string(&mut krl, &section);
+    }
+
+    Ok(krl)
+}
+
+// ---------------------------------------------------------------------------
+// sign_krl:
+// ---------------------------------------------------------------------------
+/** Sign a KRL with the tenant's CA key and return the armored signature. */
+pub fn sign_krl(ca: &TenantCa, krl: &[u8]) -> Result<String> {
+    let ca_key = PrivateKey::from_openssh(&ca.private_key)
+        .map_err(|e| anyhow!("INTERNAL ERROR: Unable to parse certificate authority key: {}", e))?;
+    let sig = ca_key.sign(KRL_NAMESPACE, HashAlg::Sha512, krl)?;
+    Ok(sig.to_pem(LineEnding::LF)?)
+}
+
+// ***************************************************************************
+//                             Private Functions
+// ***************************************************************************
+// Append an SSH wire format string, which is a 32 bit length followed by the data.
+fn put_string(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssh_key::SshSig;
+    use crate::utils::keygen::{self, KeyType};
+
+    #[test]
+    fn build_and_sign_krl() {
+        let ca_key = keygen::generate_key(KeyType::Ed25519, 256).unwrap();
+        let ca = TenantCa {private_key: ca_key.private_key, public_key: ca_key.public_key,
+                           public_key_fingerprint: ca_key.public_key_fingerprint, key_type: ca_key.key_type};
+
+        let key1 = keygen::generate_key(KeyType::Ed25519, 256).unwrap();
+        let key2 = keygen::generate_key(KeyType::Ecdsa, 256).unwrap();
+        let fps = vec![key1.public_key_fingerprint.clone(), key2.public_key_fingerprint, key1.public_key_fingerprint];
+        let krl = build_krl(&fps, &[], &ca.public_key, Utc::now(), "test").unwrap();
+        assert_eq!(&krl[..8], KRL_MAGIC);
+        assert_eq!(krl[44 + 4], KRL_SECTION_FINGERPRINT_SHA256);
+        assert_eq!(krl.len(), 44 + 4 + 1 + 4 + 2 * 36);
+
+        // An empty KRL has no sections.
+        assert_eq!(build_krl(&[], &[], &ca.public_key, Utc::now(), "").unwrap().len(), 44);
+        assert!(build_krl(&["MD5:00".to_string()], &[], &ca.public_key, Utc::now(), "").is_err());
+
+        // Certificate serials are revoked in a certificates section for the CA key,
+        // which contains the 51 byte ed25519 key blob and two unique serials.
+        let krl = build_krl(&[], &[7, 3, 7], &ca.public_key, Utc::now(), "").unwrap();
+        assert_eq!(krl[44], KRL_SECTION_CERTIFICATES);
+        assert_eq!(krl.len(), 44 + 1 + 4 + (4 + 51) + 4 + 1 + 4 + 2 * 8);
+        assert!(build_krl(&[], &[0], &ca.public_key, Utc::now(), "").is_err());
+
+        let sig = SshSig::from_pem(sign_krl(&ca, &krl).unwrap()).unwrap();
+        let ca_pubkey = PublicKey::from_openssh(&ca.public_key).unwrap();
+        assert!(ca_pubkey.verify(KRL_NAMESPACE, &krl, &sig).is_ok());
+    }
+}
@@ -0,0 +1,312 @@
+#![forbid(unsafe_code)]
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use lazy_static::lazy_static;
+use log::{debug, error};
+use openssl::nid::Nid;
+use openssl::ssl::{select_next_proto, AlpnError, Ssl, SslAcceptor, SslFiletype, SslMethod, SslRef, SslVerifyMode};
+use openssl::x509::{X509Name, X509NameRef, X509Ref, X509VerifyResult};
+use poem::http::uri::Scheme;
+use poem::listener::Acceptor;
+use poem::web::{LocalAddr, RemoteAddr};
+use poem::Request;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf, Error as IoError, ErrorKind, Result as IoResult};
+use tokio_openssl::SslStream;
+
+use crate::utils::tms_utils::get_absolute_path;
+
+// ***************************************************************************
+//                          Mutual TLS
+// ***************************************************************************
+// When mutual TLS is enabled, the HTTPS listener asks callers for a client
+// certificate and verifies any certificate presented against the configured CA
+// bundle.  The names in a verified certificate, its subject's common name and
+// its DNS, URI and email subject alternative names, are recorded for the
+// lifetime of the connection so that request authorization can map them to a
+// client or host using the cert_mappings table.
+//
+// Poem does not pass TLS connection information to requests, so the names are
+// kept in a registry keyed by the connection's remote address, which is unique
+// among open connections.
+pub const CERT_SUBJECT_CLIENT: &str = "client";
+pub const CERT_SUBJECT_HOST: &str = "host";
+pub const CERT_SUBJECT_TYPES: [&str; 2] = [CERT_SUBJECT_CLIENT, CERT_SUBJECT_HOST];
+
+// ALPN protocols offered by the server, the same as poem's TLS listeners.
+static ALPN_PROTOS: &[u8] = b"\x02h2\x08http/1.1";
+
+lazy_static! {
+    static ref PEER_CERT_NAMES: Mutex<HashMap<SocketAddr, Vec<String>>> = Mutex::new(HashMap::new());
+}
+
+// ***************************************************************************
+//                             Public Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// create_ssl_acceptor:
+// ---------------------------------------------------------------------------
+/** Create the server's TLS configuration with client certificate verification.
+ * When required is true, connections without a client certificate are refused.
+ */
+pub fn create_ssl_acceptor(cert_file: &str, key_file: &str, ca_file: &str, required: bool) -> Result<SslAcceptor> {
+    let ca_file = get_absolute_path(ca_file);
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_certificate_chain_file(cert_file)?;
+    builder.set_private_key_file(key_file, SslFiletype::PEM)?;
+
+    // Verify client certificates against the CA bundle and advertise its CAs.
+    builder.set_ca_file(&ca_file)?;
+    builder.set_client_ca_list(X509Name::load_client_ca_file(&ca_file)?);
+    let mode = if required {SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT}
+               else {SslVerifyMode::PEER};
+    builder.set_verify(mode);
+
+    // Negotiate http/2 when the client supports it.
+    builder.set_alpn_protos(ALPN_PROTOS)?;
+    builder.set_alpn_select_callback(|_: &mut SslRef, list: &[u8]| {
+        select_next_proto(ALPN_PROTOS, list).ok_or(AlpnError::NOACK)
+    });
+    Ok(builder.build())
+}
+
+// ---------------------------------------------------------------------------
+// get_peer_cert_names:
+// ---------------------------------------------------------------------------
+/** Return the names in the verified client certificate of the connection the
+ * request arrived on, or None if no certificate was presented.
+ */
+pub fn get_peer_cert_names(http_req: &Request) -> Option<Vec<String>> {
+    let addr = http_req.remote_addr().as_socket_addr()?;
+    match PEER_CERT_NAMES.lock() {
+        Ok(names) => names.get(addr).cloned(),
+        Err(e) => {
+            error!("Unable to access client certificate names: {}", e);
+            None
+        },
+    }
+}
+
+// ***************************************************************************
+//                              MtlsAcceptor
+// ***************************************************************************
+/** An acceptor that performs the TLS handshake with client certificate
+ * verification on connections accepted by the inner acceptor.  As with poem's
+ * TLS acceptors, the handshake runs on the connection's own task.
+ */
+pub struct MtlsAcceptor<T> {
+    inner: T,
+    tls_acceptor: Arc<SslAcceptor>,
+}
+
+impl<T: Acceptor> MtlsAcceptor<T> {
+    pub fn new(inner: T, tls_acceptor: SslAcceptor) -> Self {
+        Self {inner, tls_acceptor: Arc::new(tls_acceptor)}
+    }
+}
+
+impl<T: Acceptor> Acceptor for MtlsAcceptor<T> {
+    type Io = MtlsStream<T::Io>;
+
+    fn local_addr(&self) -> Vec<LocalAddr> {
+        self.inner.local_addr()
+    }
+
+    async fn accept(&mut self) -> IoResult<(Self::Io, LocalAddr, RemoteAddr, Scheme)> {
+        let (stream, local_addr, remote_addr, _) = self.inner.accept().await?;
+        let tls_acceptor = self.tls_acceptor.clone();
+        let peer_addr = remote_addr.as_socket_addr().cloned();
+        let handshake = async move {
+            let ssl = Ssl::new(tls_acceptor.context()).map_err(to_io_error)?;
+            let mut tls_stream = SslStream::new(ssl, stream).map_err(to_io_error)?;
+            Pin::new(&mut tls_stream).accept().await.map_err(to_io_error)?;
+            if let Some(addr) = peer_addr {
+                register_peer_cert(addr, tls_stream.ssl());
+            }
+            Ok(tls_stream)
+        };
+        let stream = MtlsStream {state: MtlsState::Handshaking(handshake.boxed()), peer_addr};
+        Ok((stream, local_addr, remote_addr, Scheme::HTTPS))
+    }
+}
+
+// ***************************************************************************
+//                               MtlsStream
+// ***************************************************************************
+enum MtlsState<S> {
+    Handshaking(BoxFuture<'static, IoResult<SslStream<S>>>),
+    Ready(SslStream<S>),
+    Error,
+}
+
+/** A TLS stream that completes its handshake on first use and forgets the
+ * connection's certificate names when dropped.
+ */
+pub struct MtlsStream<S> {
+    state: MtlsState<S>,
+    peer_addr: Option<SocketAddr>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> MtlsStream<S> {
+    /** Drive the handshake and return the established stream. */
+    fn poll_stream(&mut self, cx: &mut Context<'_>) -> Poll<IoResult<&mut SslStream<S>>> {
+        if let MtlsState::Handshaking(fut) = &mut self.state {
+            match fut.poll_unpin(cx) {
+                Poll::Ready(Ok(s)) => self.state = MtlsState::Ready(s),
+                Poll::Ready(Err(e)) => {
+                    self.state = MtlsState::Error;
+                    return Poll::Ready(Err(e));
+                },
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        match &mut self.state {
+            MtlsState::Ready(s) => Poll::Ready(Ok(s)),
+            _ => Poll::Ready(Err(IoError::new(ErrorKind::InvalidData, "TLS handshake failed"))),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for MtlsStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        match self.get_mut().poll_stream(cx) {
+            Poll::Ready(Ok(s)) => Pin::new(s).poll_read(cx, buf),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for MtlsStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        match self.get_mut().poll_stream(cx) {
+            Poll::Ready(Ok(s)) => Pin::new(s).poll_write(cx, buf),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        match self.get_mut().poll_stream(cx) {
+            Poll::Ready(Ok(s)) => Pin::new(s).poll_flush(cx),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        match self.get_mut().poll_stream(cx) {
+            Poll::Ready(Ok(s)) => Pin::new(s).poll_shutdown(cx),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S> Drop for MtlsStream<S> {
+    fn drop(&mut self) {
+        if let Some(addr) = &self.peer_addr {
+            if let Ok(mut names) = PEER_CERT_NAMES.lock() {
+                names.remove(addr);
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                             Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// register_peer_cert:
+// ---------------------------------------------------------------------------
+/** Record the names of the connection's client certificate if it was verified. */
+fn register_peer_cert(addr: SocketAddr, ssl: &SslRef) {
+    let cert = match ssl.peer_certificate() {
+        Some(c) if ssl.verify_result() == X509VerifyResult::OK => c,
+        _ => return,
+    };
+    let names = get_cert_names(&cert);
+    debug!("Client certificate from {} has names {:?}", addr, names);
+    match PEER_CERT_NAMES.lock() {
+        Ok(mut map) => {map.insert(addr, names);},
+        Err(e) => error!("Unable to record client certificate names: {}", e),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// get_cert_names:
+// ---------------------------------------------------------------------------
+/** Collect the subject's common names and the DNS, URI and email subject
+ * alternative names of a certificate.
+ */
+fn get_cert_names(cert: &X509Ref) -> Vec<String> {
+    let mut names = get_common_names(cert.subject_name());
+    if let Some(sans) = cert.subject_alt_names() {
+        for san in sans.iter() {
+            if let Some(name) = san.dnsname().or_else(|| san.uri()).or_else(|| san.email()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.dedup();
+    names
+}
+
+fn get_common_names(subject: &X509NameRef) -> Vec<String> {
+    subject.entries_by_nid(Nid::COMMONNAME)
+        .filter_map(|e| e.data().as_utf8().ok().map(|s| s.to_string()))
+        .collect()
+}
+
+fn to_io_error(e: impl std::fmt::Display) -> IoError {
+    IoError::other(e.to_string())
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::X509;
+    use openssl::x509::extension::SubjectAlternativeName;
+
+    #[test]
+    fn collect_cert_names() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509Name::builder().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "app1.example.com").unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns("host1.example.com")
+            .uri("spiffe://example.com/app1")
+            .ip("10.0.0.1")
+            .build(&builder.x509v3_context(None, None)).unwrap();
+        builder.append_extension(san).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = builder.build();
+
+        // IP address SANs are not names.
+        assert_eq!(get_cert_names(&cert),
+                   vec!["app1.example.com", "host1.example.com", "spiffe://example.com/app1"]);
+    }
+}
@@ -0,0 +1,195 @@
+#![forbid(unsafe_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::jwk::{AlgorithmParameters, Jwk, JwkSet};
+use log::info;
+use serde_json::Value;
+
+use crate::utils::authz::AuthzTypes;
+use crate::utils::config::{OidcConfig, OidcTenantConfig};
+use crate::utils::tms_utils::get_absolute_path;
+
+// ***************************************************************************
+//                          OIDC Bearer Tokens
+// ***************************************************************************
+// Clients that already hold a JWT issued by their identity provider (IdP) can
+// pass it in an "Authorization: Bearer" header instead of the X-TMS id/secret
+// headers.  Each tenant that accepts bearer tokens is configured with its IdP's
+// issuer, the audience TMS expects, the IdP's signing keys (JWKS) and the claims
+// that hold client and admin ids.  Tokens must be signed with an asymmetric key
+// from the JWKS and carry valid exp, aud and iss claims.
+//
+// A token authorizes its caller as the client or admin named in the configured
+// claim, so all the checks that apply to secret-based authorization apply to
+// token-based authorization too.
+pub const BEARER_PREFIX: &str = "Bearer ";
+
+// Timeout for JWKS downloads at startup.
+const JWKS_URL_TIMEOUT_SECS: u64 = 30;
+
+// ***************************************************************************
+//                             Public Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// load_jwks:
+// ---------------------------------------------------------------------------
+/** Read the JWKS of each configured tenant from its local file or URL.  Keys
+ * are only loaded at startup, so a restart is required when an IdP rotates
+ * its signing keys.
+ */
+pub fn load_jwks(config: &OidcConfig) -> Result<HashMap<String, JwkSet>> {
+    let mut jwks = HashMap::new();
+    for (tenant, cfg) in &config.tenants {
+        let contents = if let Some(file) = &cfg.jwks_file {
+            let path = get_absolute_path(file);
+            fs::read_to_string(&path)
+                .map_err(|e| anyhow!("Unable to read JWKS file {} for tenant {}: {}", path, tenant, e))?
+        } else if let Some(url) = &cfg.jwks_url {
+            let agent = ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(JWKS_URL_TIMEOUT_SECS))
+                .build();
+            agent.get(url).call()
+                .map_err(|e| anyhow!("Unable to download JWKS from {} for tenant {}: {}", url, tenant, e))?
+                .into_string()?
+        } else {
+            return Err(anyhow!("No JWKS source configured for tenant {}.", tenant));
+        };
+
+        let set: JwkSet = serde_json::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid JWKS for tenant {}: {}", tenant, e))?;
+        info!("Loaded {} OIDC signing key(s) for tenant {}.", set.keys.len(), tenant);
+        jwks.insert(tenant.clone(), set);
+    }
+    Ok(jwks)
+}
+
+// ---------------------------------------------------------------------------
+// get_claim_name:
+// ---------------------------------------------------------------------------
+/** Return the configured claim that holds the id for the authz type.  Only
+ * client and tenant admin ids can be taken from bearer tokens.  The site admin
+ * has rights in every tenant, so it never authenticates with a token issued by
+ * a tenant's identity provider.
+ */
+pub fn get_claim_name<'a>(cfg: &'a OidcTenantConfig, authz_type: &AuthzTypes) -> Option<&'a str> {
+    match authz_type {
+        AuthzTypes::ClientOwn => cfg.client_claim.as_deref(),
+        AuthzTypes::TenantAdmin => cfg.admin_claim.as_deref(),
+        _ => None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// validate_bearer_token:
+// ---------------------------------------------------------------------------
+/** Verify the token's signature using the tenant's JWKS, check its exp, aud
+ * and iss claims and return the string value of the named claim.
+ */
+pub fn validate_bearer_token(token: &str, cfg: &OidcTenantConfig, jwks: &JwkSet, claim: &str) -> Result<String> {
+    // Find the signing key.  The key id can be omitted when the JWKS has only one key.
+    let header = decode_header(token)?;
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid).ok_or_else(|| anyhow!("Unknown signing key id '{}'.", kid))?,
+        None if jwks.keys.len() == 1 => &jwks.keys[0],
+        None => return Err(anyhow!("The token header has no key id.")),
+    };
+    let key = get_decoding_key(jwk, header.alg)?;
+
+    // Check the signature and standard claims.
+    let mut validation = Validation::new(header.alg);
+    validation.set_required_spec_claims(&["exp", "aud", "iss"]);
+    validation.set_audience(&[&cfg.audience]);
+    validation.set_issuer(&[&cfg.issuer]);
+    let data = decode::<HashMap<String, Value>>(token, &key, &validation)?;
+
+    // Map the claim to an id.
+    match data.claims.get(claim) {
+        Some(Value::String(id)) if !id.is_empty() => Ok(id.clone()),
+        _ => Err(anyhow!("The token has no '{}' string claim.", claim)),
+    }
+}
+
+// ***************************************************************************
+//                             Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// get_decoding_key:
+// ---------------------------------------------------------------------------
+/** Only asymmetric keys are accepted, and a key that declares its algorithm
+ * can only verify tokens signed with that algorithm.
+ */
+fn get_decoding_key(jwk: &Jwk, alg: Algorithm) -> Result<DecodingKey> {
+    if let AlgorithmParameters::OctetKey(_) = jwk.algorithm {
+        return Err(anyhow!("Symmetric signing keys are not supported."));
+    }
+    if let Some(key_alg) = &jwk.common.key_algorithm {
+        if key_alg.to_string() != format!("{:?}", alg) {
+            return Err(anyhow!("The token algorithm {:?} does not match the signing key algorithm {}.", alg, key_alg));
+        }
+    }
+    Ok(DecodingKey::from_jwk(jwk)?)
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use ssh_key::private::Ed25519Keypair;
+
+    // PKCS#8 prefix of a DER encoded ed25519 private key.
+    const ED25519_PKCS8_PREFIX: [u8; 16] = [0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06,
+                                            0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20];
+
+    fn tenant_config() -> OidcTenantConfig {
+        OidcTenantConfig {issuer: "https://idp.example.com".to_string(), audience: "tms".to_string(),
+                          jwks_file: None, jwks_url: None, client_claim: Some("client_id".to_string()),
+                          admin_claim: None}
+    }
+
+    #[test]
+    fn validate_ed25519_tokens() {
+        let keypair = Ed25519Keypair::random(&mut rand_core::OsRng);
+        let mut der = ED25519_PKCS8_PREFIX.to_vec();
+        der.extend_from_slice(keypair.private.as_ref());
+        let encoding_key = EncodingKey::from_ed_der(&der);
+        let jwks: JwkSet = serde_json::from_value(json!({"keys": [{
+            "kty": "OKP", "crv": "Ed25519", "kid": "k1", "alg": "EdDSA",
+            "x": URL_SAFE_NO_PAD.encode(keypair.public.as_ref())}]})).unwrap();
+
+        let cfg = tenant_config();
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some("k1".to_string());
+        let exp = chrono::Utc::now().timestamp() + 300;
+        let make_token = |claims: Value| encode(&header, &claims, &encoding_key).unwrap();
+
+        let token = make_token(json!({"iss": cfg.issuer, "aud": "tms", "exp": exp, "client_id": "app1"}));
+        assert_eq!(validate_bearer_token(&token, &cfg, &jwks, "client_id").unwrap(), "app1");
+        assert!(validate_bearer_token(&token, &cfg, &jwks, "admin_id").is_err());
+
+        // Wrong audience, wrong issuer and expired tokens are rejected.
+        let token = make_token(json!({"iss": cfg.issuer, "aud": "other", "exp": exp, "client_id": "app1"}));
+        assert!(validate_bearer_token(&token, &cfg, &jwks, "client_id").is_err());
+        let token = make_token(json!({"iss": "https://evil.example.com", "aud": "tms", "exp": exp, "client_id": "app1"}));
+        assert!(validate_bearer_token(&token, &cfg, &jwks, "client_id").is_err());
+        let token = make_token(json!({"iss": cfg.issuer, "aud": "tms", "exp": exp - 3600, "client_id": "app1"}));
+        assert!(validate_bearer_token(&token, &cfg, &jwks, "client_id").is_err());
+    }
+
+    #[test]
+    fn site_admin_has_no_claim() {
+        let mut cfg = tenant_config();
+        cfg.admin_claim = Some("tms_admin_id".to_string());
+        assert_eq!(get_claim_name(&cfg, &AuthzTypes::TenantAdmin), Some("tms_admin_id"));
+        assert_eq!(get_claim_name(&cfg, &AuthzTypes::SiteAdmin), None);
+    }
+}
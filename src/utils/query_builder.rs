@@ -0,0 +1,164 @@
+#![forbid(unsafe_code)]
+
+use sqlx::{Encode, Postgres, QueryBuilder, Type};
+use sqlx::postgres::PgArguments;
+use sqlx::query::Query;
+
+use crate::utils::tms_utils::ListParms;
+
+// ***************************************************************************
+//                               Query Builder
+// ***************************************************************************
+// Queries whose constraints depend on the request are composed with TmsQuery
+// rather than by splicing text into SQL templates.  All SQL text passed to the
+// builder, including column and table names, must be 'static so that only
+// text written into TMS can become part of a statement.  Every value supplied
+// by a caller is sent to the database as a bound parameter.
+//
+// Constraints are appended in the order they are added, the first one
+// introducing the WHERE clause and the rest joined with AND.  Ordering and
+// limit clauses are added last.
+// ---------------------------------------------------------------------------
+// Cmp:
+// ---------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Cmp {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Cmp::Eq => " = ",
+            Cmp::Gt => " > ",
+            Cmp::Ge => " >= ",
+            Cmp::Lt => " < ",
+            Cmp::Le => " <= ",
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TmsQuery:
+// ---------------------------------------------------------------------------
+pub struct TmsQuery<'a> {
+    builder: QueryBuilder<'a, Postgres>,
+    has_where: bool,
+}
+
+impl<'a> TmsQuery<'a> {
+    /** Start a query from a SELECT statement that has no WHERE clause. */
+    pub fn new(select: &'static str) -> Self {
+        Self {builder: QueryBuilder::new(select), has_where: false}
+    }
+
+    /** Start a query whose table is chosen at run time.  The table name must
+     * come from a fixed list of names, never from request input.
+     */
+    pub fn select_from(columns: &'static str, table: &'static str) -> Self {
+        let mut builder = QueryBuilder::new(columns);
+        builder.push(" FROM ").push(table);
+        Self {builder, has_where: false}
+    }
+
+    /** Add the constraint "column cmp value". */
+    pub fn and<T>(mut self, column: &'static str, cmp: Cmp, value: T) -> Self
+    where T: 'a + Encode<'a, Postgres> + Type<Postgres>
+    {
+        self.push_conjunction();
+        self.builder.push(column).push(cmp.as_sql()).push_bind(value);
+        self
+    }
+
+    /** Add the constraint "column cmp value" only when a value is given. */
+    pub fn and_opt<T>(self, column: &'static str, cmp: Cmp, value: Option<T>) -> Self
+    where T: 'a + Encode<'a, Postgres> + Type<Postgres>
+    {
+        match value {
+            Some(v) => self.and(column, cmp, v),
+            None => self,
+        }
+    }
+
+    /** Select unexpired records when active is true, expired records when
+     * active is false and all records when no value is given.
+     */
+    pub fn and_active(mut self, active: Option<bool>) -> Self {
+        if let Some(active) = active {
+            self.push_conjunction();
+            self.builder.push(if active {"expires_at > NOW()"} else {"expires_at <= NOW()"});
+        }
+        self
+    }
+
+    /** Add the keyset pagination constraint, the sort order and the limit
+     * of a list request.  Records are paged by id.
+     */
+    pub fn paginate(self, parms: &ListParms) -> Self {
+        let cmp = if parms.ascending {Cmp::Gt} else {Cmp::Lt};
+        let order = if parms.ascending {"id ASC"} else {"id DESC"};
+        self.and_opt("id", cmp, parms.cursor)
+            .order_by(order)
+            .limit(parms.query_limit())
+    }
+
+    /** Add an ORDER BY clause. */
+    pub fn order_by(mut self, order: &'static str) -> Self {
+        self.builder.push(" ORDER BY ").push(order);
+        self
+    }
+
+    /** Add a LIMIT clause. */
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.builder.push(" LIMIT ").push_bind(limit);
+        self
+    }
+
+    /** The SQL text composed so far. */
+    #[cfg(test)]
+    pub fn sql(&self) -> &str {
+        self.builder.sql()
+    }
+
+    /** Create the executable query with all parameters bound. */
+    pub fn build(&mut self) -> Query<'_, Postgres, PgArguments> {
+        self.builder.build()
+    }
+
+    // ----- Clause helpers.
+    fn push_conjunction(&mut self) {
+        self.builder.push(if self.has_where {" AND "} else {" WHERE "});
+        self.has_where = true;
+    }
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose_constraints() {
+        let client_id: Option<String> = None;
+        let query = TmsQuery::new("SELECT id FROM pubkeys")
+            .and("tenant", Cmp::Eq, "test")
+            .and_opt("client_id", Cmp::Eq, client_id)
+            .and_opt("host", Cmp::Eq, Some("o'host"))
+            .and_active(Some(false));
+        assert_eq!(query.sql(),
+                   "SELECT id FROM pubkeys WHERE tenant = $1 AND host = $2 AND expires_at <= NOW()");
+    }
+
+    #[test]
+    fn compose_pagination() {
+        let parms = ListParms::new(Some(10), &Some("42".to_string()), &Some("desc".to_string())).unwrap();
+        let query = TmsQuery::select_from("SELECT id", "hosts_audit").paginate(&parms);
+        assert_eq!(query.sql(), "SELECT id FROM hosts_audit WHERE id < $1 ORDER BY id DESC LIMIT $2");
+    }
+}
@@ -0,0 +1,187 @@
+#![forbid(unsafe_code)]
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use lazy_static::lazy_static;
+use log::{error, info};
+use sqlx::Row;
+
+use crate::utils::config::ReaperConfig;
+use crate::utils::db_statements::{REAP_RESERVATIONS, REAP_PUBKEYS, REAP_DELEGATIONS, REAP_USER_HOSTS, REAP_USER_MFA,
+                                  ARCHIVE_RESERVATIONS, ARCHIVE_PUBKEYS, ARCHIVE_DELEGATIONS, ARCHIVE_USER_HOSTS,
+                                  ARCHIVE_USER_MFA};
+use crate::utils::tms_utils::timestamp_utc;
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                                 Reaper
+// ***************************************************************************
+// The reaper is a background task that removes records that have been expired
+// for longer than the configured grace period.  A record is only removed once no
+// other reaped record references it, so that removing a record never cascades to
+// records that have not expired or that would not be archived.  The tables are
+// reaped in an order that removes dependent records, such as reservations, before
+// the records they depend on, so records whose dependents expired in the same run
+// are removed without waiting for the next run.
+struct ReapedTable {
+    name: &'static str,
+    reap: &'static str,
+    archive: &'static str,
+}
+
+const REAPED_TABLES: [ReapedTable; 5] = [
+    ReapedTable {name: "reservations", reap: REAP_RESERVATIONS, archive: ARCHIVE_RESERVATIONS},
+    ReapedTable {name: "pubkeys", reap: REAP_PUBKEYS, archive: ARCHIVE_PUBKEYS},
+    ReapedTable {name: "delegations", reap: REAP_DELEGATIONS, archive: ARCHIVE_DELEGATIONS},
+    ReapedTable {name: "user_hosts", reap: REAP_USER_HOSTS, archive: ARCHIVE_USER_HOSTS},
+    ReapedTable {name: "user_mfa", reap: REAP_USER_MFA, archive: ARCHIVE_USER_MFA},
+];
+
+// ---------------------------------------------------------------------------
+// ReaperStats:
+// ---------------------------------------------------------------------------
+// Counts of removed records accumulated since the server started.
+#[derive(Debug, Default)]
+struct ReaperStats {
+    runs: u64,
+    last_run: Option<DateTime<Utc>>,
+    reaped: HashMap<String, HashMap<&'static str, u64>>,  // tenant -> table -> count
+}
+
+// The reaper statistics of a single tenant.
+#[derive(Debug)]
+pub struct TenantReaperStats {
+    pub runs: u64,
+    pub last_run: Option<DateTime<Utc>>,
+    pub reaped: Vec<(&'static str, u64)>,  // (table, count) for each reaped table
+}
+
+lazy_static! {
+    static ref REAPER_STATS: Mutex<ReaperStats> = Mutex::new(ReaperStats::default());
+}
+
+// ***************************************************************************
+//                             Public Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// start_reaper:
+// ---------------------------------------------------------------------------
+/** Start the reaper task if it's enabled in the configuration.  The first run
+ * begins immediately and subsequent runs start every interval_minutes.
+ */
+pub fn start_reaper() {
+    let config = &RUNTIME_CTX.parms.config.reaper;
+    if !config.enabled {
+        info!("The expired record reaper is disabled.");
+        return;
+    }
+    info!("Starting the expired record reaper: interval {} minutes, grace period {} minutes, batch size {}, archive {}.",
+          config.interval_minutes, config.grace_minutes, config.batch_size, config.archive);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.interval_minutes as u64 * 60));
+        loop {
+            interval.tick().await;
+            reap_expired(config).await;
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// get_reaper_stats:
+// ---------------------------------------------------------------------------
+/** Return the number of records removed from each reaped table in the tenant. */
+pub fn get_reaper_stats(tenant: &str) -> TenantReaperStats {
+    let stats = REAPER_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let tenant_counts = stats.reaped.get(tenant);
+    let reaped = REAPED_TABLES.iter()
+        .map(|t| (t.name, tenant_counts.and_then(|c| c.get(t.name)).copied().unwrap_or(0)))
+        .collect();
+    TenantReaperStats {runs: stats.runs, last_run: stats.last_run, reaped}
+}
+
+// ***************************************************************************
+//                             Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// reap_expired:
+// ---------------------------------------------------------------------------
+/** Remove the expired records from each reaped table.  Errors are logged and
+ * the next table is processed; records missed are removed on a later run.
+ */
+async fn reap_expired(config: &ReaperConfig) {
+    // Records that expired before the cutoff are removed.
+    let now = timestamp_utc();
+    let cutoff = now - TimeDelta::minutes(config.grace_minutes as i64);
+
+    let mut run_counts: Vec<(&'static str, HashMap<String, u64>)> = vec!();
+    for table in REAPED_TABLES.iter() {
+        match reap_table(table, cutoff, config).await {
+            Ok(counts) => {
+                let total: u64 = counts.values().sum();
+                if total > 0 {
+                    info!("Reaper removed {} expired record(s) from the {} table.", total, table.name);
+                }
+                run_counts.push((table.name, counts));
+            },
+            Err(e) => error!("Reaper unable to remove expired records from the {} table: {}", table.name, e),
+        }
+    }
+
+    // Accumulate the counts.
+    let mut stats = REAPER_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    stats.runs += 1;
+    stats.last_run = Some(now);
+    for (table, counts) in run_counts {
+        for (tenant, count) in counts {
+            *stats.reaped.entry(tenant).or_default().entry(table).or_insert(0) += count;
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// reap_table:
+// ---------------------------------------------------------------------------
+/** Remove the expired records from one table in batches, each in its own
+ * transaction, until a partial batch indicates that no more remain.  The
+ * number of records removed in each tenant is returned.
+ */
+async fn reap_table(table: &ReapedTable, cutoff: DateTime<Utc>, config: &ReaperConfig) -> Result<HashMap<String, u64>> {
+    let sql_query = if config.archive {table.archive} else {table.reap};
+    let batch_size = config.batch_size as i64;
+
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    loop {
+        // Get a connection to the db and start a transaction.  Uncommited transactions
+        // are automatically rolled back when they go out of scope.
+        // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+        let mut tx = RUNTIME_CTX.db.begin().await?;
+
+        // Remove the next batch.
+        let rows = sqlx::query(sql_query)
+            .bind(cutoff)
+            .bind(batch_size)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        // Commit the transaction.
+        tx.commit().await?;
+
+        // Collect the per tenant counts.
+        let mut removed: i64 = 0;
+        for row in rows {
+            let tenant: Option<String> = row.get(0);
+            let count: i64 = row.get(1);
+            removed += count;
+            *counts.entry(tenant.unwrap_or_default()).or_insert(0) += count as u64;
+        }
+        if removed < batch_size {break;}
+    }
+
+    Ok(counts)
+}
@@ -0,0 +1,266 @@
+#![forbid(unsafe_code)]
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use log::info;
+use rand::RngCore;
+use sqlx::{Postgres, Row, Transaction};
+use ssh_key::{PrivateKey, PublicKey};
+use ssh_key::certificate::{Builder, CertType};
+
+use crate::utils::db_statements::{INSERT_TENANT_CA, GET_TENANT_CA, LIST_UNENCRYPTED_TENANT_CAS,
+                                  UPDATE_TENANT_CA_PRIVATE_KEY};
+use crate::utils::key_encryption::{encrypt_at_rest, decrypt_at_rest};
+use crate::utils::keygen::{self, KeyType};
+use crate::utils::tms_utils::timestamp_utc;
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                           Tenant Certificate Authority
+// ***************************************************************************
+// Each tenant has its own SSH certificate authority (CA) key pair.  The CA
+// private key signs the user certificates that TMS issues in the tenant and
+// the CA public key is installed on hosts using sshd's TrustedUserCAKeys
+// option.  CA keys are created with their tenant and never leave TMS.  The
+// private keys are encrypted in the database with the server's database
+// encryption key (see key_encryption.rs).
+//
+// A certificate's principal is the host account, which every host that
+// trusts the tenant's CA accepts with a stock TrustedUserCAKeys setup.
+// Certificates can instead be bound to the hosts on which the key can be used
+// by naming the account on each of those hosts, such as bud@host1.  Hosts that
+// accept host-bound certificates map their accounts to these principals with
+// sshd's AuthorizedPrincipalsFile or AuthorizedPrincipalsCommand options.
+// Certificate serial numbers are saved with their keys so that the
+// certificates of revoked keys can be listed in key revocation lists.
+//
+// The purpose of encrypted CA private keys.
+const CA_KEY_PURPOSE: &str = "tenant CA private key";
+
+// The extensions granted to every user certificate, which match the defaults
+// that ssh-keygen grants.
+const USER_CERT_EXTENSIONS: [&str; 5] = [
+    "permit-X11-forwarding",
+    "permit-agent-forwarding",
+    "permit-port-forwarding",
+    "permit-pty",
+    "permit-user-rc",
+];
+
+// ---------------------------------------------------------------------------
+// TenantCa:
+// ---------------------------------------------------------------------------
+#[derive(Debug)]
+pub struct TenantCa {
+    pub private_key: String,
+    pub public_key: String,
+    pub public_key_fingerprint: String,
+    pub key_type: String,
+}
+
+// ---------------------------------------------------------------------------
+// UserCert:
+// ---------------------------------------------------------------------------
+#[derive(Debug)]
+pub struct UserCert {
+    pub certificate: String,
+    pub serial: i64,
+}
+
+// ***************************************************************************
+//                             Public Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// insert_tenant_ca:
+// ---------------------------------------------------------------------------
+/** Generate a CA key pair for the tenant and insert it using the caller's
+ * transaction.  Nothing is inserted if the tenant already has a CA.
+ */
+pub async fn insert_tenant_ca(tx: &mut Transaction<'_, Postgres>, tenant: &str, now: DateTime<Utc>) -> Result<u64> {
+    // Certificate authorities always use ed25519 keys.
+    let key = keygen::generate_key(KeyType::Ed25519, 256)?;
+    let private_key = encrypt_at_rest(&RUNTIME_CTX.db_encryption_key.0, &key.private_key, CA_KEY_PURPOSE)?;
+    let result = sqlx::query(INSERT_TENANT_CA)
+        .bind(tenant)
+        .bind(private_key)
+        .bind(key.public_key)
+        .bind(key.public_key_fingerprint)
+        .bind(key.key_type)
+        .bind(now)
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+// ---------------------------------------------------------------------------
+// get_tenant_ca:
+// ---------------------------------------------------------------------------
+/** Get the tenant's CA, creating it if the tenant existed before certificate
+ * authorities were introduced.
+ */
+pub async fn get_tenant_ca(tenant: &str) -> Result<TenantCa> {
+    // Return the existing CA.
+    if let Some(ca) = select_tenant_ca(tenant).await? {
+        return Ok(ca);
+    }
+
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+    if insert_tenant_ca(&mut tx, tenant, timestamp_utc()).await? > 0 {
+        info!("Created certificate authority for tenant {}.", tenant);
+    }
+    tx.commit().await?;
+
+    // A concurrent request may have won the race, so always reread the CA.
+    match select_tenant_ca(tenant).await? {
+        Some(ca) => Ok(ca),
+        None => Err(anyhow!("INTERNAL ERROR: Unable to create the certificate authority for tenant {}.", tenant)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// encrypt_tenant_cas:
+// ---------------------------------------------------------------------------
+/** Encrypt the CA private keys that were stored before CA keys were encrypted.
+ * Return the number of keys encrypted.
+ */
+pub async fn encrypt_tenant_cas() -> Result<u64> {
+    let rows = sqlx::query(LIST_UNENCRYPTED_TENANT_CAS)
+        .fetch_all(&RUNTIME_CTX.db)
+        .await?;
+
+    // The update is skipped if the key changed in the meantime.
+    let mut count = 0;
+    for row in rows {
+        let tenant: String = row.get(0);
+        let private_key: String = row.get(1);
+        let encrypted = encrypt_at_rest(&RUNTIME_CTX.db_encryption_key.0, &private_key, CA_KEY_PURPOSE)?;
+        let result = sqlx::query(UPDATE_TENANT_CA_PRIVATE_KEY)
+            .bind(encrypted)
+            .bind(timestamp_utc())
+            .bind(&tenant)
+            .bind(&private_key)
+            .execute(&RUNTIME_CTX.db)
+            .await?;
+        count += result.rows_affected();
+    }
+    Ok(count)
+}
+
+// ---------------------------------------------------------------------------
+// get_host_cert_principal:
+// ---------------------------------------------------------------------------
+/** The host-bound principal that authorizes login to the account on the host. */
+pub fn get_host_cert_principal(host_account: &str, host: &str) -> String {
+    format!("{}@{}", host_account, host)
+}
+
+// ---------------------------------------------------------------------------
+// sign_user_cert:
+// ---------------------------------------------------------------------------
+/** Sign a user certificate for the OpenSSH public key that authorizes login
+ * as the principals during the validity period.  The certificate is returned
+ * in OpenSSH format with its randomly assigned serial number, which is always
+ * positive so that it can be saved in a BIGINT column.
+ */
+pub fn sign_user_cert(ca: &TenantCa, public_key: &str, key_id: &str, principals: &[String],
+                      valid_after: DateTime<Utc>, valid_before: DateTime<Utc>) -> Result<UserCert> {
+    // Validate the period and principals.
+    if valid_before <= valid_after {
+        return Err(anyhow!("EXPIRED: The certificate validity period ends before it begins."));
+    }
+    if principals.is_empty() {
+        return Err(anyhow!("INTERNAL ERROR: A certificate requires at least one principal."));
+    }
+
+    // Parse the keys.
+    let ca_key = PrivateKey::from_openssh(&ca.private_key)
+        .map_err(|e| anyhow!("INTERNAL ERROR: Unable to parse certificate authority key: {}", e))?;
+    let user_key = PublicKey::from_openssh(public_key)
+        .map_err(|e| anyhow!("INTERNAL ERROR: Unable to parse public key: {}", e))?;
+
+    // Build and sign the certificate.
+    let mut rng = rand::rngs::OsRng;
+    let serial = ((rng.next_u64() >> 1) as i64).max(1);
+    let mut builder = Builder::new_with_random_nonce(&mut rng, user_key,
+                                                     valid_after.timestamp().max(0) as u64,
+                                                     valid_before.timestamp().max(0) as u64)?;
+    builder.serial(serial as u64)?
+        .key_id(key_id)?
+        .cert_type(CertType::User)?;
+    for principal in principals {
+        builder.valid_principal(principal)?;
+    }
+    for ext in USER_CERT_EXTENSIONS {
+        builder.extension(ext, "")?;
+    }
+    let cert = builder.sign(&ca_key)?;
+
+    Ok(UserCert {certificate: cert.to_openssh()?, serial})
+}
+
+// ***************************************************************************
+//                             Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// select_tenant_ca:
+// ---------------------------------------------------------------------------
+async fn select_tenant_ca(tenant: &str) -> Result<Option<TenantCa>> {
+    let row = sqlx::query(GET_TENANT_CA)
+        .bind(tenant)
+        .fetch_optional(&RUNTIME_CTX.db)
+        .await?;
+    let row = match row {
+        Some(r) => r,
+        None => return Ok(None),
+    };
+
+    // The private key is decrypted for signing.
+    let encrypted: String = row.get(0);
+    let private_key = decrypt_at_rest(&RUNTIME_CTX.db_encryption_key.0, &encrypted, CA_KEY_PURPOSE)
+        .map_err(|e| anyhow!("INTERNAL ERROR: Unable to read the certificate authority of tenant {}: {}", tenant, e))?;
+    Ok(Some(TenantCa {
+        private_key,
+        public_key: row.get(1),
+        public_key_fingerprint: row.get(2),
+        key_type: row.get(3),
+    }))
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use ssh_key::Certificate;
+
+    #[test]
+    fn sign_and_parse_user_cert() {
+        let ca_key = keygen::generate_key(KeyType::Ed25519, 256).unwrap();
+        let ca = TenantCa {private_key: ca_key.private_key, public_key: ca_key.public_key,
+                           public_key_fingerprint: ca_key.public_key_fingerprint, key_type: ca_key.key_type};
+        let user_key = keygen::generate_key(KeyType::Ed25519, 256).unwrap();
+
+        let now = Utc::now();
+        let principals = [get_host_cert_principal("bud", "host1"), get_host_cert_principal("bud", "host2")];
+        let user_cert = sign_user_cert(&ca, &user_key.public_key, "bud@test", &principals,
+                                       now, now + TimeDelta::hours(1)).unwrap();
+        let cert = Certificate::from_openssh(&user_cert.certificate).unwrap();
+        assert_eq!(cert.valid_principals(), ["bud@host1".to_string(), "bud@host2".to_string()]);
+        assert_eq!(cert.serial(), user_cert.serial as u64);
+        assert!(user_cert.serial > 0);
+        assert_eq!(cert.cert_type(), CertType::User);
+        assert_eq!(cert.signature_key(), PublicKey::from_openssh(&ca.public_key).unwrap().key_data());
+
+        // An empty period and missing principals are rejected.
+        assert!(sign_user_cert(&ca, &user_key.public_key, "bud@test", &principals, now, now).is_err());
+        assert!(sign_user_cert(&ca, &user_key.public_key, "bud@test", &[], 
+                               now, now + TimeDelta::hours(1)).is_err());
+    }
+}
@@ -15,12 +15,13 @@ use poem::Request;
 use rand_core::{RngCore, OsRng};
 use hex;
 use sha2::{Sha512, Digest};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::SaltString;
 
 use anyhow::{Result, anyhow};
 use lazy_static::lazy_static;
 use log::{error, debug, LevelFilter};
 
-use crate::utils::db_statements::PLACEHOLDER;
 use crate::utils::authz::{AuthzResult, AuthzTypes};
 use crate::utils::db::is_tenant_enabled;
 
@@ -293,8 +294,10 @@ pub fn create_hex_secret() -> String {
 // ---------------------------------------------------------------------------
 // hash_hex_secret:
 // ---------------------------------------------------------------------------
-/** Take a hex secret as provided to the user and hash it for storage in the
- * database.
+/** Take a hex secret as provided to the user and hash it using unsalted SHA-512.
+ * The result is deterministic, so it's used for values that are looked up by
+ * their hash, such as approval codes, and to verify legacy secret hashes.
+ * Secrets used for authentication are stored using hash_secret().
  */
 pub fn hash_hex_secret(hex_str: &String) -> String {
     let mut hasher = Sha512::new();
@@ -303,6 +306,43 @@ pub fn hash_hex_secret(hex_str: &String) -> String {
     hex::encode(raw)
 }
 
+// ---------------------------------------------------------------------------
+// hash_secret:
+// ---------------------------------------------------------------------------
+/** Hash a secret for storage in the database using Argon2id with a random salt.
+ * The result is a PHC string, such as $argon2id$v=19$m=19456,t=2,p=1$..., that
+ * records the algorithm, its parameters and the salt.  Hashing is deliberately
+ * slow, so it runs on the blocking thread pool.
+ */
+pub async fn hash_secret(secret: &str) -> Result<String> {
+    let secret = secret.to_string();
+    tokio::task::spawn_blocking(move || hash_secret_argon2(&secret)).await?
+}
+
+// ---------------------------------------------------------------------------
+// verify_secret:
+// ---------------------------------------------------------------------------
+/** Check a secret against its stored hash, which is either an Argon2id PHC
+ * string or a legacy hex encoded SHA-512 hash.  Use is_legacy_hash() to
+ * determine whether the stored hash should be replaced.
+ */
+pub async fn verify_secret(secret: &str, stored_hash: &str) -> Result<bool> {
+    if is_legacy_hash(stored_hash) {
+        return Ok(hash_hex_secret(&secret.to_string()) == stored_hash);
+    }
+    let secret = secret.to_string();
+    let stored_hash = stored_hash.to_string();
+    tokio::task::spawn_blocking(move || verify_secret_argon2(&secret, &stored_hash)).await?
+}
+
+// ---------------------------------------------------------------------------
+// is_legacy_hash:
+// ---------------------------------------------------------------------------
+/** PHC strings start with $; legacy SHA-512 hashes are hex encoded. */
+pub fn is_legacy_hash(stored_hash: &str) -> bool {
+    !stored_hash.starts_with('$')
+}
+
 // ---------------------------------------------------------------------------
 // validate_semver:
 // ---------------------------------------------------------------------------
@@ -321,41 +361,58 @@ pub fn validate_semver(semver: &str) -> Result<bool> {
 }
 
 // ---------------------------------------------------------------------------
-// sql_substitute_client_constraint:
+// get_client_constraint:
 // ---------------------------------------------------------------------------
-/** Complete the sql select statement by substituting an appropriate value
- * for the placeholder text in the query template.  The substitution 
- * restricts the query to a specific client when the request was authorized 
- * using X_TMS_CLIENT_ID.  When authorized using a tenant admin, the record
- * created by any client in the tenant can be returned.
+/** Determine the client to which a query must be restricted.  When the request
+ * was authorized using X_TMS_CLIENT_ID, the client ID used for authorization is
+ * returned and only that client's records can be accessed.  When authorized 
+ * using a tenant admin, None is returned and records created by any client in
+ * the tenant can be accessed.
  * 
- * The sql_query is the template text into which a value is substituted 
- * for the placeholder.  The authz_result is the result of a prior authorize()
- * call.
+ * The authz_result is the result of a prior authorize() call.  We can only get
+ * here if authorization succeeded, so missing authz result data indicates an 
+ * internal error.
  */
-pub fn sql_substitute_client_constraint(sql_query: &str, authz_result: &AuthzResult) -> String {
-    // We can only get here if authorization succeeded, so we don't
-    // really have to worry about missing authz result data.  To be
-    // doubly safe, we return the unresolved template query which 
-    // will be rejected by the database.
+pub fn get_client_constraint(authz_result: &AuthzResult) -> Result<Option<String>> {
     let authz_type = match &authz_result.authz_type {
         Some(a) => a,
-        None => return sql_query.to_string(), // poison pill
+        None => return Err(anyhow!("INTERNAL ERROR: Missing authorization type.")),
     };
 
-    // Construct the replacement text.
-    let replacement = if *authz_type == AuthzTypes::ClientOwn {
-        // Get the client ID that was used for authorization.
-        let client_id = match &authz_result.hdr_id {
-            Some(id) => id,
-            None => return sql_query.to_string(), // another poison pill 
-        };
-        " AND client_id = '".to_string() + client_id + "'"
+    // Restrict the query to the client used for authorization.
+    if *authz_type == AuthzTypes::ClientOwn {
+        match &authz_result.hdr_id {
+            Some(id) => Ok(Some(id.clone())),
+            None => Err(anyhow!("INTERNAL ERROR: Missing authorized client ID.")),
+        }
     }
-    else {"".to_string()};
+    else {Ok(None)}
+}
+
+// ---------------------------------------------------------------------------
+// get_user_constraint:
+// ---------------------------------------------------------------------------
+/** Determine the end user to which a query must be restricted.  When the request
+ * was authorized using X_TMS_USER_ID, the tms user ID used for authorization is
+ * returned and only that user's records can be accessed.  Otherwise, None is
+ * returned and no user restriction applies.
+ * 
+ * The authz_result is the result of a prior authorize() call.
+ */
+pub fn get_user_constraint(authz_result: &AuthzResult) -> Result<Option<String>> {
+    let authz_type = match &authz_result.authz_type {
+        Some(a) => a,
+        None => return Err(anyhow!("INTERNAL ERROR: Missing authorization type.")),
+    };
 
-    // Return the template after substitution.
-    sql_query.replace(PLACEHOLDER, replacement.as_str())    
+    // Restrict the query to the user used for authorization.
+    if *authz_type == AuthzTypes::UserOwn {
+        match &authz_result.hdr_id {
+            Some(id) => Ok(Some(id.clone())),
+            None => Err(anyhow!("INTERNAL ERROR: Missing authorized user ID.")),
+        }
+    }
+    else {Ok(None)}
 }
 
 // ---------------------------------------------------------------------------
@@ -372,6 +429,92 @@ pub async fn check_tenant_enabled(tenant: &String) -> bool {
     }
 }
 
+// ***************************************************************************
+// LIST PAGINATION
+// ***************************************************************************
+// List endpoints use keyset pagination on record ids.  Each page is requested
+// with a limit and the cursor returned with the previous page, and records are
+// sorted by id in ascending (creation) order unless descending order is requested.
+pub const DEFAULT_LIST_LIMIT: u32 = 100;
+pub const MAX_LIST_LIMIT    : u32 = 1000;
+pub const SORT_ASC          : &str = "asc";
+pub const SORT_DESC         : &str = "desc";
+pub const STATUS_ACTIVE     : &str = "active";
+pub const STATUS_EXPIRED    : &str = "expired";
+
+// ---------------------------------------------------------------------------
+// ListParms:
+// ---------------------------------------------------------------------------
+#[derive(Debug)]
+pub struct ListParms {
+    pub limit: u32,
+    pub cursor: Option<i32>,
+    pub ascending: bool,
+}
+
+impl ListParms {
+    /** Validate and normalize the paging parameters of a list request.  The 
+     * limit defaults to DEFAULT_LIST_LIMIT and cannot exceed MAX_LIST_LIMIT, 
+     * the cursor must be a value previously returned as a next_cursor and the
+     * sort direction must be asc or desc.  
+     */
+    pub fn new(limit: Option<u32>, cursor: &Option<String>, sort: &Option<String>) -> Result<Self> {
+        let limit = match limit {
+            Some(0) => return Err(anyhow!("The limit must be greater than zero.")),
+            Some(l) => l.min(MAX_LIST_LIMIT),
+            None => DEFAULT_LIST_LIMIT,
+        };
+        let cursor = match cursor {
+            Some(c) => match c.parse::<i32>() {
+                Ok(id) => Some(id),
+                Err(_) => return Err(anyhow!("Invalid cursor value '{}'.", c)),
+            },
+            None => None,
+        };
+        let ascending = match sort.as_deref() {
+            None | Some(SORT_ASC) => true,
+            Some(SORT_DESC) => false,
+            Some(other) => return Err(anyhow!("Invalid sort value '{}', expected {} or {}.", 
+                                              other, SORT_ASC, SORT_DESC)),
+        };
+        Ok(Self {limit, cursor, ascending})
+    }
+
+    /** The query limit is one more than the page size so that we can tell 
+     * whether another page follows.
+     */
+    pub fn query_limit(&self) -> i64 {
+        self.limit as i64 + 1
+    }
+
+    /** Trim the extra record retrieved using query_limit() and return the
+     * cursor of the next page, if there is one.
+     */
+    pub fn paginate<T>(&self, elements: &mut Vec<T>, get_id: impl Fn(&T) -> i32) -> Option<String> {
+        if elements.len() <= self.limit as usize {
+            return None;
+        }
+        elements.truncate(self.limit as usize);
+        elements.last().map(|e| get_id(e).to_string())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// parse_status_filter:
+// ---------------------------------------------------------------------------
+/** Convert the optional active/expired status filter of list requests into the 
+ * optional boolean bound to list queries, where true selects unexpired records.  
+ */
+pub fn parse_status_filter(status: &Option<String>) -> Result<Option<bool>> {
+    match status.as_deref() {
+        None => Ok(None),
+        Some(STATUS_ACTIVE) => Ok(Some(true)),
+        Some(STATUS_EXPIRED) => Ok(Some(false)),
+        Some(other) => Err(anyhow!("Invalid status value '{}', expected {} or {}.", 
+                                   other, STATUS_ACTIVE, STATUS_EXPIRED)),
+    }
+}
+
 // ***************************************************************************
 // PRIVATE FUNCTIONS
 // ***************************************************************************
@@ -386,4 +529,51 @@ fn run_command_emsg(command: Command, status: ExitStatus) -> String {
     " with exit status: " + &status.to_string()
 }
 
+// ---------------------------------------------------------------------------
+// hash_secret_argon2:
+// ---------------------------------------------------------------------------
+fn hash_secret_argon2(secret: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    match Argon2::default().hash_password(secret.as_bytes(), &salt) {
+        Ok(h) => Ok(h.to_string()),
+        Err(e) => Err(anyhow!("Unable to hash secret: {}", e)),
+    }
+}
 
+// ---------------------------------------------------------------------------
+// verify_secret_argon2:
+// ---------------------------------------------------------------------------
+fn verify_secret_argon2(secret: &str, stored_hash: &str) -> Result<bool> {
+    let parsed = match PasswordHash::new(stored_hash) {
+        Ok(h) => h,
+        Err(e) => return Err(anyhow!("Invalid stored secret hash: {}", e)),
+    };
+    Ok(Argon2::default().verify_password(secret.as_bytes(), &parsed).is_ok())
+}
+
+// ***************************************************************************
+//                                  Tests
+// ***************************************************************************
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn verify_argon2_and_legacy_secrets() {
+        let secret = create_hex_secret();
+        let hash = hash_secret(&secret).await.unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(!is_legacy_hash(&hash));
+        assert!(verify_secret(&secret, &hash).await.unwrap());
+        assert!(!verify_secret("wrong", &hash).await.unwrap());
+
+        // Salts make each hash different.
+        assert_ne!(hash, hash_secret(&secret).await.unwrap());
+
+        // Legacy SHA-512 hashes still verify.
+        let legacy = hash_hex_secret(&secret);
+        assert!(is_legacy_hash(&legacy));
+        assert!(verify_secret(&secret, &legacy).await.unwrap());
+        assert!(!verify_secret("wrong", &legacy).await.unwrap());
+    }
+}
@@ -40,4 +40,34 @@ pub mod reservations_get;
 pub mod reservations_delete;
 pub mod reservations_create;
 pub mod reservations_extend;
-pub mod reservations_delete_related;
\ No newline at end of file
+pub mod reservations_delete_related;
+pub mod host_creds_create;
+pub mod host_creds_list;
+pub mod host_creds_delete;
+pub mod host_creds_update_secret;
+pub mod client_approval_create;
+pub mod audit_list;
+pub mod reaper_stats;
+pub mod tenant_ca_get;
+pub mod pubkeys_register;
+pub mod key_pool_stats;
+pub mod pubkeys_rotate;
+pub mod host_groups_create;
+pub mod host_groups_get;
+pub mod host_groups_delete;
+pub mod pubkeys_krl;
+pub mod keytypes_get;
+pub mod admin_create;
+pub mod admin_get;
+pub mod admin_list;
+pub mod admin_update;
+pub mod admin_delete;
+pub mod admin_update_secret;
+pub mod admin_reset_secret;
+pub mod user_creds_create;
+pub mod user_creds_list;
+pub mod user_creds_delete;
+pub mod user_creds_update_secret;
+pub mod cert_mappings_create;
+pub mod cert_mappings_list;
+pub mod cert_mappings_delete;
\ No newline at end of file
@@ -0,0 +1,249 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::{Result, anyhow};
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::{GET_ADMIN, INSERT_ADMIN};
+use crate::utils::config::{ADMIN_ID_PREFIX, ADMIN_PRIVILEGES, PERM_ADMIN, PERM_SITE_ADMIN};
+use crate::utils::tms_utils::{self, RequestDebug, create_hex_secret, hash_secret, timestamp_utc,
+                              check_tenant_enabled};
+use crate::utils::authz::{authorize, get_tenant_header, AuthzTypes, X_TMS_TENANT};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Create an administrator in the caller's tenant.  Administrator IDs always
+ * start with the ~~ prefix.  The generated secret is only returned in this
+ * response; TMS saves its hash.
+ */
+pub struct CreateAdminApi;
+
+#[derive(Object)]
+pub struct ReqCreateAdmin
+{
+    tenant: String,
+    admin_user: String,
+    privilege: Option<String>,  // PERM_ADMIN (default) or PERM_SITE_ADMIN
+}
+
+#[derive(Object, Debug)]
+pub struct RespCreateAdmin
+{
+    result_code: String,
+    result_msg: String,
+    tenant: String,
+    admin_user: String,
+    admin_secret: String,
+    privilege: String,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqCreateAdmin {
+    type Req = ReqCreateAdmin;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    admin_user: ");
+        s.push_str(&self.admin_user);
+        s.push_str("\n    privilege: ");
+        s.push_str(&format!("{:?}", self.privilege));
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 201)]
+    Http201(Json<RespCreateAdmin>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 409)]
+    Http409(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_201(resp: RespCreateAdmin) -> TmsResponse {
+    TmsResponse::Http201(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_409(msg: String) -> TmsResponse {
+    TmsResponse::Http409(Json(HttpResult::new(409.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl CreateAdminApi {
+    #[oai(path = "/tms/admin", method = "post")]
+    async fn create_admin_api(&self, http_req: &Request, req: Json<ReqCreateAdmin>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check that the tenant specified in the header is the same as the one in the request body.
+        if hdr_tenant != req.tenant {
+            let msg = format!("ERROR: FORBIDDEN - The tenant in the {} header ({}) does not match the tenant in the request body ({})",
+                                      X_TMS_TENANT, hdr_tenant, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only a tenant admin can create other admins.  Only the site admin can
+        // create other site admins.
+        let allowed = [AuthzTypes::SiteAdmin, AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to create admin {} in tenant {}.", req.admin_user, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        let site_admin = authz_result.authz_type == Some(AuthzTypes::SiteAdmin);
+        match RespCreateAdmin::process(http_req, &req, site_admin).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespCreateAdmin {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, tenant: String, admin_user: String,
+           admin_secret: String, privilege: String) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, tenant, admin_user, admin_secret, privilege}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqCreateAdmin, site_admin: bool) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Validate the admin id and privilege.
+        if !req.admin_user.starts_with(ADMIN_ID_PREFIX) || req.admin_user.len() <= ADMIN_ID_PREFIX.len() {
+            let msg = format!("ERROR: Invalid admin_user ({}), admin IDs must start with '{}'.",
+                                      req.admin_user, ADMIN_ID_PREFIX);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+        let privilege = req.privilege.clone().unwrap_or(PERM_ADMIN.to_string());
+        if !ADMIN_PRIVILEGES.contains(&privilege.as_str()) {
+            let msg = format!("ERROR: Invalid privilege ({}), valid privileges are {:?}.", privilege, ADMIN_PRIVILEGES);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+        if privilege == PERM_SITE_ADMIN && !site_admin {
+            let msg = format!("ERROR: FORBIDDEN - Only the site admin can create admins with the {} privilege.", PERM_SITE_ADMIN);
+            error!("{}", msg);
+            return Ok(make_http_403(msg));
+        }
+
+        // ------------------------ Generate Secret --------------------
+        let admin_secret_str  = create_hex_secret();
+        let admin_secret_hash = hash_secret(&admin_secret_str).await?;
+
+        // Insert the admin unless it already exists.
+        match insert_admin(req, &admin_secret_hash, &privilege).await {
+            Ok(_) => (),
+            Err(e) => {
+                let msg = e.to_string();
+                if msg.contains("DUPLICATE:") {
+                    error!("{}", msg);
+                    return Ok(make_http_409(msg));
+                } else {return Err(e);}
+            }
+        };
+
+        // Log result and return response.
+        let msg = format!("Admin {} created in tenant {}", req.admin_user, req.tenant);
+        info!("{}", msg);
+        Ok(make_http_201(Self::new("0", msg, req.tenant.clone(), req.admin_user.clone(),
+                                   admin_secret_str, privilege)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// insert_admin:
+// ---------------------------------------------------------------------------
+/** Insert the admin record.  An admin that already exists in the tenant is
+ * rejected with a "DUPLICATE:" error.
+ */
+async fn insert_admin(req: &ReqCreateAdmin, admin_secret_hash: &String, privilege: &String) -> Result<u64> {
+    // Get timestamp.
+    let now = timestamp_utc();
+
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Reject admins that already exist.
+    let existing = sqlx::query(GET_ADMIN)
+        .bind(&req.admin_user)
+        .bind(&req.tenant)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if existing.is_some() {
+        return Err(anyhow!("DUPLICATE: Admin {} already exists in tenant {}.", req.admin_user, req.tenant));
+    }
+
+    // Create the insert statement.
+    let result = sqlx::query(INSERT_ADMIN)
+        .bind(&req.tenant)
+        .bind(&req.admin_user)
+        .bind(admin_secret_hash)
+        .bind(privilege)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,191 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db::check_not_last_admin_in_tx;
+use crate::utils::db_statements::DELETE_ADMIN;
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Delete an administrator from the caller's tenant.  The tenant's last
+ * enabled admin cannot be deleted.
+ */
+pub struct DeleteAdminApi;
+
+#[derive(Object)]
+pub struct ReqDeleteAdmin
+{
+    admin_user: String,
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespDeleteAdmin
+{
+    result_code: String,
+    result_msg: String,
+    num_deleted: u32,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqDeleteAdmin {
+    type Req = ReqDeleteAdmin;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    admin_user: ");
+        s.push_str(&self.admin_user);
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespDeleteAdmin>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 409)]
+    Http409(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespDeleteAdmin) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_409(msg: String) -> TmsResponse {
+    TmsResponse::Http409(Json(HttpResult::new(409.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl DeleteAdminApi {
+    #[oai(path = "/tms/admin/del/:admin_user", method = "delete")]
+    async fn delete_admin_api(&self, http_req: &Request, admin_user: Path<String>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqDeleteAdmin {admin_user: admin_user.to_string(), tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Only a tenant admin can delete admin records.  Only the site admin can
+        // delete site admin records.
+        let allowed = [AuthzTypes::SiteAdmin, AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to delete admin {} in tenant {}.", req.admin_user, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        let site_admin = authz_result.authz_type == Some(AuthzTypes::SiteAdmin);
+        match RespDeleteAdmin::process(http_req, &req, site_admin).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespDeleteAdmin {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, num_deleted: u32) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, num_deleted}}
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqDeleteAdmin, site_admin: bool) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Delete the admin record.
+        let deletes = match delete_admin(req, site_admin).await {
+            Ok(d) => d,
+            Err(e) => {
+                let msg = e.to_string();
+                if msg.contains("LAST_ADMIN:") {
+                    error!("{}", msg);
+                    return Ok(make_http_409(msg));
+                } else {return Err(e);}
+            }
+        };
+
+        // Log result and return response.
+        let msg =
+            if deletes < 1 {format!("Admin {} NOT FOUND - Nothing deleted", req.admin_user)}
+            else {format!("Admin {} deleted", req.admin_user)};
+        info!("{}", msg);
+        Ok(make_http_200(Self::new("0", msg, deletes as u32)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// delete_admin:
+// ---------------------------------------------------------------------------
+async fn delete_admin(req: &ReqDeleteAdmin, site_admin: bool) -> Result<u64> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Never delete the last enabled admin.
+    check_not_last_admin_in_tx(&mut tx, &req.tenant, &req.admin_user).await?;
+
+    // Issue the db delete call.
+    let result = sqlx::query(DELETE_ADMIN)
+        .bind(&req.admin_user)
+        .bind(&req.tenant)
+        .bind(site_admin)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
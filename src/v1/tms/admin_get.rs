@@ -0,0 +1,198 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::GET_ADMIN;
+use crate::utils::db_types::Admin;
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use log::error;
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Get an administrator in the caller's tenant.  The admin secret is never
+ * returned.
+ */
+pub struct GetAdminApi;
+
+#[derive(Object)]
+pub struct ReqGetAdmin
+{
+    admin_user: String,
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespGetAdmin
+{
+    result_code: String,
+    result_msg: String,
+    id: i32,
+    tenant: String,
+    admin_user: String,
+    privilege: String,
+    enabled: bool,
+    created: DateTime<Utc>,
+    updated: DateTime<Utc>,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqGetAdmin {
+    type Req = ReqGetAdmin;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    admin_user: ");
+        s.push_str(&self.admin_user);
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespGetAdmin>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 404)]
+    Http404(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespGetAdmin) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_404(msg: String) -> TmsResponse {
+    TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl GetAdminApi {
+    #[oai(path = "/tms/admin/:admin_user", method = "get")]
+    async fn get_admin_api(&self, http_req: &Request, admin_user: Path<String>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqGetAdmin {admin_user: admin_user.to_string(), tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Only a tenant admin can query admin records.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to view admin {} in tenant {}.", req.admin_user, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespGetAdmin::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespGetAdmin {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, admin: Admin) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, id: admin.id, tenant: admin.tenant,
+              admin_user: admin.admin_user, privilege: admin.privilege, enabled: admin.enabled,
+              created: admin.created, updated: admin.updated}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqGetAdmin) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Search for the tenant/admin id in the database.
+        match get_admin(req).await {
+            Ok(admin) => Ok(make_http_200(Self::new("0", "success".to_string(), admin))),
+            Err(e) => {
+                // Determine if this is a real db error or just record not found.
+                let msg = e.to_string();
+                if msg.contains("NOT_FOUND") {Ok(make_http_404(msg))}
+                  else {Err(e)}
+            },
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// get_admin:
+// ---------------------------------------------------------------------------
+async fn get_admin(req: &ReqGetAdmin) -> Result<Admin> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Create the select statement.
+    let result = sqlx::query(GET_ADMIN)
+        .bind(&req.admin_user)
+        .bind(&req.tenant)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    // We may have found the admin.
+    match result {
+        Some(row) => {
+            Ok(Admin::new(row.get(0), row.get(1), row.get(2), row.get(3),
+                          row.get(4), row.get(5), row.get(6)))
+        },
+        None => {
+            Err(anyhow!("NOT_FOUND: Admin {} not found in tenant {}.", req.admin_user, req.tenant))
+        },
+    }
+}
@@ -0,0 +1,198 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::UPDATE_ADMIN_SECRET;
+use crate::utils::tms_utils::{self, RequestDebug, create_hex_secret, hash_secret,
+                              timestamp_utc, check_tenant_enabled};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Reset another administrator's secret to a newly generated one, such as when
+ * the ~~admin secret is lost.  Any admin in a tenant can reset the secrets of the
+ * other admins in the tenant, but only site admins can reset site admin secrets.
+ * Admins rotate their own secrets with the update API in admin_update_secret.rs.
+ */
+pub struct ResetAdminSecretApi;
+
+#[derive(Object)]
+pub struct ReqResetAdminSecret
+{
+    admin_user: String,
+    tenant: String,
+    reset_by: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespResetAdminSecret
+{
+    result_code: String,
+    result_msg: String,
+    admin_user: String,
+    tenant: String,
+    admin_secret: String,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqResetAdminSecret {
+    type Req = ReqResetAdminSecret;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    admin_user: ");
+        s.push_str(&self.admin_user);
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    reset_by: ");
+        s.push_str(&self.reset_by);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespResetAdminSecret>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 404)]
+    Http404(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespResetAdminSecret) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_404(msg: String) -> TmsResponse {
+    TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl ResetAdminSecretApi {
+    #[oai(path = "/tms/admin/secret/reset/:admin_user", method = "patch")]
+    async fn reset_admin_secret_api(&self, http_req: &Request, admin_user: Path<String>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only a tenant admin can reset admin secrets.  Only the site admin can
+        // reset site admin secrets.
+        let allowed = [AuthzTypes::SiteAdmin, AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to reset the secret of admin {} in tenant {}.",
+                                      admin_user.as_str(), hdr_tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // Package the request parameters.  The resetting admin is recorded in the log.
+        let req = ReqResetAdminSecret {admin_user: admin_user.to_string(), tenant: hdr_tenant,
+                                       reset_by: authz_result.hdr_id.clone().unwrap_or_default()};
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        let site_admin = authz_result.authz_type == Some(AuthzTypes::SiteAdmin);
+        match RespResetAdminSecret::process(http_req, &req, site_admin).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespResetAdminSecret {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, admin_user: String, tenant: String, admin_secret: String) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, admin_user, tenant, admin_secret}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqResetAdminSecret, site_admin: bool) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // ------------------------ Generate Secret --------------------
+        let admin_secret_str  = create_hex_secret();
+        let admin_secret_hash = hash_secret(&admin_secret_str).await?;
+
+        // Replace the secret hash.
+        let updates = reset_admin_secret(req, admin_secret_hash, site_admin).await?;
+        if updates < 1 {
+            let msg = format!("NOT_FOUND: Admin {} not found in tenant {}.", req.admin_user, req.tenant);
+            error!("{}", msg);
+            return Ok(make_http_404(msg));
+        }
+
+        // Log result and return response.
+        let msg = format!("Secret reset for admin {} by admin {}", req.admin_user, req.reset_by);
+        info!("{}", msg);
+        Ok(make_http_200(Self::new("0", msg, req.admin_user.clone(), req.tenant.clone(), admin_secret_str)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// reset_admin_secret:
+// ---------------------------------------------------------------------------
+async fn reset_admin_secret(req: &ReqResetAdminSecret, admin_secret_hash: String, site_admin: bool) -> Result<u64> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Issue the db update call.
+    let result = sqlx::query(UPDATE_ADMIN_SECRET)
+        .bind(admin_secret_hash)
+        .bind(timestamp_utc())
+        .bind(&req.admin_user)
+        .bind(&req.tenant)
+        .bind(site_admin)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,198 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::UPDATE_ADMIN_SECRET;
+use crate::utils::tms_utils::{self, RequestDebug, create_hex_secret, hash_secret,
+                              timestamp_utc, check_tenant_enabled};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Replace the calling administrator's secret with a newly generated one.  Admins
+ * can only rotate their own secrets; the secrets of other admins are reset with
+ * the reset API in admin_reset_secret.rs.
+ */
+pub struct UpdateAdminSecretApi;
+
+#[derive(Object)]
+pub struct ReqUpdateAdminSecret
+{
+    admin_user: String,
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespUpdateAdminSecret
+{
+    result_code: String,
+    result_msg: String,
+    admin_user: String,
+    tenant: String,
+    admin_secret: String,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqUpdateAdminSecret {
+    type Req = ReqUpdateAdminSecret;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    admin_user: ");
+        s.push_str(&self.admin_user);
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespUpdateAdminSecret>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 404)]
+    Http404(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespUpdateAdminSecret) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_404(msg: String) -> TmsResponse {
+    TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl UpdateAdminSecretApi {
+    #[oai(path = "/tms/admin/secret/:admin_user", method = "patch")]
+    async fn update_admin_secret_api(&self, http_req: &Request, admin_user: Path<String>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqUpdateAdminSecret {admin_user: admin_user.to_string(), tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Admins can only rotate their own secrets.
+        let allowed = [AuthzTypes::SiteAdmin, AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to update the secret of admin {} in tenant {}.",
+                                      req.admin_user, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+        if !authz_result.check_admin_own(&req.admin_user) {
+            let msg = format!("ERROR: NOT AUTHORIZED Admins can only rotate their own secrets, not the secret of admin {} in tenant {}.",
+                                      req.admin_user, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        let site_admin = authz_result.authz_type == Some(AuthzTypes::SiteAdmin);
+        match RespUpdateAdminSecret::process(http_req, &req, site_admin).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespUpdateAdminSecret {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, admin_user: String, tenant: String, admin_secret: String) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, admin_user, tenant, admin_secret}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqUpdateAdminSecret, site_admin: bool) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // ------------------------ Generate Secret --------------------
+        let admin_secret_str  = create_hex_secret();
+        let admin_secret_hash = hash_secret(&admin_secret_str).await?;
+
+        // Replace the secret hash.
+        let updates = update_admin_secret(req, admin_secret_hash, site_admin).await?;
+        if updates < 1 {
+            let msg = format!("NOT_FOUND: Admin {} not found in tenant {}.", req.admin_user, req.tenant);
+            error!("{}", msg);
+            return Ok(make_http_404(msg));
+        }
+
+        // Log result and return response.
+        let msg = format!("Secret updated for admin {}", req.admin_user);
+        info!("{}", msg);
+        Ok(make_http_200(Self::new("0", msg, req.admin_user.clone(), req.tenant.clone(), admin_secret_str)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// update_admin_secret:
+// ---------------------------------------------------------------------------
+async fn update_admin_secret(req: &ReqUpdateAdminSecret, admin_secret_hash: String, site_admin: bool) -> Result<u64> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Issue the db update call.
+    let result = sqlx::query(UPDATE_ADMIN_SECRET)
+        .bind(admin_secret_hash)
+        .bind(timestamp_utc())
+        .bind(&req.admin_user)
+        .bind(&req.tenant)
+        .bind(site_admin)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
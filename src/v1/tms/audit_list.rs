@@ -0,0 +1,294 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::{Path, Query}, ApiResponse };
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::db_statements::LIST_AUDIT_COLUMNS;
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms, check_tenant_enabled, timestamp_utc};
+use log::error;
+
+use crate::RUNTIME_CTX;
+
+// The TMS tables that have audit tables and the names of their audit tables.
+const AUDITED_TABLES: [(&str, &str); 16] = [
+    ("tenants", "tenants_audit"),
+    ("clients", "clients_audit"),
+    ("user_mfa", "user_mfa_audit"),
+    ("user_hosts", "user_hosts_audit"),
+    ("delegations", "delegations_audit"),
+    ("pubkeys", "pubkeys_audit"),
+    ("reservations", "reservations_audit"),
+    ("admin", "admin_audit"),
+    ("hosts", "hosts_audit"),
+    ("host_creds", "host_creds_audit"),
+    ("client_approvals", "client_approvals_audit"),
+    ("host_groups", "host_groups_audit"),
+    ("pubkey_hosts", "pubkey_hosts_audit"),
+    ("user_creds", "user_creds_audit"),
+    ("cert_mappings", "cert_mappings_audit"),
+    ("tenant_ca", "tenant_ca_audit"),
+];
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** List the audit records for a TMS table in the caller's tenant.  Records can
+ * be filtered by time range, the id of the audited record and change type 
+ * (I = insert, U = update, D = delete).  Records are returned in pages in the
+ * order in which they were written, or in reverse order when sort is desc.
+ */
+pub struct ListAuditApi;
+
+#[derive(Object)]
+struct ReqListAudit
+{
+    tenant: String,
+    table: String,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    refid: Option<i32>,
+    change: Option<String>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
+}
+
+#[derive(Object, Debug)]
+pub struct RespListAudit
+{
+    result_code: String,
+    result_msg: String,
+    num_records: i32,
+    records: Vec<AuditListElement>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Object, Debug)]
+pub struct AuditListElement
+{
+    id: i32,
+    refid: i32,
+    tenant: Option<String>,
+    refcol: String,
+    change: Option<String>,
+    oldvalue: Option<String>,
+    newvalue: Option<String>,
+    changed: DateTime<Utc>,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqListAudit {   
+    type Req = ReqListAudit;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    table: ");
+        s.push_str(&self.table);
+        s.push_str("\n    start: ");
+        s.push_str(&format!("{:?}", self.start));
+        s.push_str("\n    end: ");
+        s.push_str(&format!("{:?}", self.end));
+        s.push_str("\n    refid: ");
+        s.push_str(&format!("{:?}", self.refid));
+        s.push_str("\n    change: ");
+        s.push_str(&format!("{:?}", self.change));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespListAudit>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespListAudit) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl ListAuditApi {
+    #[allow(clippy::too_many_arguments)]
+    #[oai(path = "/tms/audit/:table", method = "get")]
+    async fn get_list_audit_api(&self, http_req: &Request, table: Path<String>, 
+                                start: Query<Option<DateTime<Utc>>>, end: Query<Option<DateTime<Utc>>>,
+                                refid: Query<Option<i32>>, change: Query<Option<String>>, 
+                                limit: Query<Option<u32>>, cursor: Query<Option<String>>,
+                                sort: Query<Option<String>>) 
+        -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+        
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.        
+        let req = ReqListAudit {tenant: hdr_tenant, table: table.to_string(), start: *start, end: *end,
+                                          refid: *refid, change: change.clone(), limit: *limit,
+                                          cursor: cursor.clone(), sort: sort.clone()};
+        
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can read the audit trail.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to list audit records in tenant {}.", req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespListAudit::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl AuditListElement {
+    /// Create response elements.
+    #[allow(clippy::too_many_arguments)]
+    fn new(id: i32, refid: i32, tenant: Option<String>, refcol: String, change: Option<String>, 
+           oldvalue: Option<String>, newvalue: Option<String>, changed: DateTime<Utc>) -> Self {
+        Self {id, refid, tenant, refcol, change, oldvalue, newvalue, changed}
+    }
+}
+
+impl RespListAudit {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, num_records: i32, records: Vec<AuditListElement>,
+           next_cursor: Option<String>) 
+    -> Self {
+        Self {result_code: result_code.to_string(), result_msg, num_records, records, next_cursor}
+        }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqListAudit) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Only audited tables can be queried.  The audit table name used in the
+        // query always comes from our list, never from the request.
+        let audit_table = match AUDITED_TABLES.iter().find(|(t, _)| *t == req.table) {
+            Some((_, audit_table)) => *audit_table,
+            None => {
+                let tables: Vec<&str> = AUDITED_TABLES.iter().map(|(t, _)| *t).collect();
+                let msg = format!("ERROR: Unknown audit table '{}'.  Valid tables are: {}.", 
+                                          req.table, tables.join(", "));
+                error!("{}", msg);
+                return Ok(make_http_400(msg));
+            }
+        };
+
+        // Validate the change type.
+        if let Some(change) = &req.change {
+            if !["I", "U", "D"].contains(&change.as_str()) {
+                let msg = format!("ERROR: Invalid change type '{}', expected I, U or D.", change);
+                error!("{}", msg);
+                return Ok(make_http_400(msg));
+            }
+        }
+
+        // Validate the paging parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
+        // Query the audit table.
+        let mut records = list_audit(req, audit_table, &parms).await?;
+        let next_cursor = parms.paginate(&mut records, |e| e.id);
+        Ok(make_http_200(Self::new("0", "success".to_string(), 
+                                        records.len() as i32, records, next_cursor)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// list_audit:
+// ---------------------------------------------------------------------------
+async fn list_audit(req: &ReqListAudit, audit_table: &'static str, parms: &ListParms) -> Result<Vec<AuditListElement>> {
+    // Fill in the time range defaults.
+    let start = req.start.unwrap_or(DateTime::<Utc>::UNIX_EPOCH);
+    let end = req.end.unwrap_or_else(timestamp_utc);
+
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
+    // are automatically rolled back when they go out of scope. 
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+    
+    // Create the select statement.
+    let mut query = TmsQuery::select_from(LIST_AUDIT_COLUMNS, audit_table)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and("changed", Cmp::Ge, start)
+        .and("changed", Cmp::Le, end)
+        .and_opt("refid", Cmp::Eq, req.refid)
+        .and_opt("change", Cmp::Eq, req.change.as_ref())
+        .paginate(parms);
+    let rows = query.build()
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    // Collect the row data into element objects.
+    let mut element_list: Vec<AuditListElement> = vec!();
+    for row in rows {
+        let elem = AuditListElement::new(
+                 row.get(0), row.get(1), row.get(2), row.get(3), 
+        row.get(4), row.get(5), row.get(6), row.get(7),);
+        element_list.push(elem);
+    }
+
+    Ok(element_list)
+}
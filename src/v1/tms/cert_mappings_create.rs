@@ -0,0 +1,219 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::INSERT_CERT_MAPPING;
+use crate::utils::db_types::CertMappingInput;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, X_TMS_TENANT};
+use crate::utils::mtls::CERT_SUBJECT_TYPES;
+use crate::utils::tms_utils::{self, timestamp_utc, RequestDebug, check_tenant_enabled};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Map a client certificate name to a client or host.  When mutual TLS is
+ * enabled, a caller that presents a verified certificate containing the
+ * cert_subject, either the subject's common name or a subject alternative name,
+ * is authenticated as the client or host without sending a secret.  The
+ * subject_type is "client" or "host".
+ */
+pub struct CreateCertMappingsApi;
+
+#[derive(Object)]
+pub struct ReqCreateCertMapping
+{
+    tenant: String,
+    cert_subject: String,
+    subject_type: String,
+    subject_id: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespCreateCertMapping
+{
+    result_code: String,
+    result_msg: String,
+    id: i32,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqCreateCertMapping {
+    type Req = ReqCreateCertMapping;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    cert_subject: ");
+        s.push_str(&self.cert_subject);
+        s.push_str("\n    subject_type: ");
+        s.push_str(&self.subject_type);
+        s.push_str("\n    subject_id: ");
+        s.push_str(&self.subject_id);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 201)]
+    Http201(Json<RespCreateCertMapping>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_201(resp: RespCreateCertMapping) -> TmsResponse {
+    TmsResponse::Http201(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl CreateCertMappingsApi {
+    #[oai(path = "/tms/certmappings", method = "post")]
+    async fn create_cert_mapping(&self, http_req: &Request, req: Json<ReqCreateCertMapping>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check that the tenant specified in the header is the same as the one in the request body.
+        if hdr_tenant != req.tenant {
+            let msg = format!("ERROR: FORBIDDEN - The tenant in the {} header ({}) does not match the tenant in the request body ({})",
+                                      X_TMS_TENANT, hdr_tenant, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can map certificates.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to map certificate subject {} in tenant {}.",
+                                      req.cert_subject, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        match RespCreateCertMapping::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespCreateCertMapping {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, id: i32) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, id}}
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqCreateCertMapping) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Validate the request.
+        if !CERT_SUBJECT_TYPES.contains(&req.subject_type.as_str()) {
+            let msg = format!("ERROR: Invalid subject_type '{}'.  Valid values are: {}.",
+                              req.subject_type, CERT_SUBJECT_TYPES.join(", "));
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+        if req.cert_subject.is_empty() || req.subject_id.is_empty() {
+            let msg = "ERROR: The cert_subject and subject_id must not be empty.".to_string();
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
+        // Use the same current UTC timestamp in all related time caculations..
+        let now = timestamp_utc();
+
+        // Create the input record.
+        let input_record = CertMappingInput::new(
+            req.tenant.clone(),
+            req.cert_subject.clone(),
+            req.subject_type.clone(),
+            req.subject_id.clone(),
+            now,
+            now,
+        );
+
+        // Insert the new record.
+        let id = insert_cert_mapping(input_record).await?;
+        let msg = format!("Certificate subject '{}' mapped to {} '{}' in tenant '{}'.",
+                          req.cert_subject, req.subject_type, req.subject_id, req.tenant);
+        info!("{}", msg);
+        Ok(make_http_201(Self::new("0", msg, id)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// insert_cert_mapping:
+// ---------------------------------------------------------------------------
+async fn insert_cert_mapping(rec: CertMappingInput) -> Result<i32> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Create the insert statement.
+    let id: i32 = sqlx::query_scalar(INSERT_CERT_MAPPING)
+        .bind(&rec.tenant)
+        .bind(&rec.cert_subject)
+        .bind(&rec.subject_type)
+        .bind(&rec.subject_id)
+        .bind(rec.created)
+        .bind(rec.updated)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok(id)
+}
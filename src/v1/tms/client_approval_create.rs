@@ -0,0 +1,215 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::cmp::min;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::INSERT_CLIENT_APPROVAL;
+use crate::utils::db_types::ClientApprovalInput;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, X_TMS_TENANT}; 
+use crate::utils::tms_utils::{self, timestamp_utc, calc_expires_at, create_hex_secret, hash_hex_secret, 
+                              RequestDebug, check_tenant_enabled};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// Approval codes are short-lived, 24 hours maximum (1440 minutes).
+const MAX_APPROVAL_MINUTES: i32 = 24 * 60;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Issue a one-time code that allows a single new client to be created in the
+ * tenant when TMS is configured with new_clients = "on_approval".  The code is
+ * only returned in this response; only its hash is saved.  The code is consumed
+ * when a client is created with it or becomes unusable when it expires.
+ */
+pub struct CreateClientApprovalApi;
+
+#[derive(Object)]
+pub struct ReqCreateClientApproval
+{
+    tenant: String,
+    ttl_minutes: i32,  // negative means MAX_APPROVAL_MINUTES
+}
+
+#[derive(Object, Debug)]
+pub struct RespCreateClientApproval
+{
+    result_code: String,
+    result_msg: String,
+    tenant: String,
+    approval_code: String,
+    expires_at: DateTime<Utc>,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqCreateClientApproval {   
+    type Req = ReqCreateClientApproval;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    ttl_minutes: ");
+        s.push_str(&self.ttl_minutes.to_string());
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 201)]
+    Http201(Json<RespCreateClientApproval>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_201(resp: RespCreateClientApproval) -> TmsResponse {
+    TmsResponse::Http201(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl CreateClientApprovalApi {
+    #[oai(path = "/tms/client/approval", method = "post")]
+    async fn create_client_approval(&self, http_req: &Request, req: Json<ReqCreateClientApproval>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check that the tenant specified in the header is the same as the one in the request body.
+        if hdr_tenant != req.tenant {
+            let msg = format!("ERROR: FORBIDDEN - The tenant in the {} header ({}) does not match the tenant in the request body ({})", 
+                                      X_TMS_TENANT, hdr_tenant, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);  
+        }
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can approve new clients.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to approve new clients in tenant {}.", req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        match RespCreateClientApproval::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespCreateClientApproval {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, tenant: String, approval_code: String, 
+           expires_at: DateTime<Utc>) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, tenant, approval_code, expires_at}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqCreateClientApproval) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // ------------------------ Time Values ------------------------ 
+        // The ttl can be negative, which means maximum ttl.
+        let ttl_minutes = if req.ttl_minutes < 0 {MAX_APPROVAL_MINUTES} 
+                                else {min(req.ttl_minutes, MAX_APPROVAL_MINUTES)};
+        let now = timestamp_utc();
+        let expires_at = calc_expires_at(now, ttl_minutes);
+
+        // ------------------------ Generate Code ----------------------  
+        let approval_code_str  = create_hex_secret();
+        let approval_code_hash = hash_hex_secret(&approval_code_str);
+
+        // Create the input record.  Note that we save the hash of
+        // the hex code, but never the code itself.  
+        let input_record = ClientApprovalInput::new(
+            req.tenant.clone(),
+            approval_code_hash,
+            expires_at,
+            now, 
+            now,
+        );
+
+        // Insert the new record.
+        insert_client_approval(input_record).await?;
+        info!("Client approval code created in tenant '{}' with expiration at {}.", req.tenant, expires_at);
+        
+        // Return the code represented in hex.
+        Ok(make_http_201(Self::new("0", "success".to_string(), req.tenant.clone(), 
+                         approval_code_str, expires_at)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// insert_client_approval:
+// ---------------------------------------------------------------------------
+async fn insert_client_approval(rec: ClientApprovalInput) -> Result<u64> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
+    // are automatically rolled back when they go out of scope. 
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+    
+    // Create the insert statement.
+    let result = sqlx::query(INSERT_CLIENT_APPROVAL)
+        .bind(&rec.tenant)
+        .bind(&rec.approval_hash)
+        .bind(rec.expires_at)
+        .bind(rec.created)
+        .bind(rec.updated)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
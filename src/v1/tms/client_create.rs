@@ -2,13 +2,14 @@
 
 use poem::Request;
 use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use sqlx::{Postgres, Transaction};
 
 use crate::utils::errors::HttpResult;
-use crate::utils::db_statements::INSERT_CLIENTS;
+use crate::utils::db_statements::{INSERT_CLIENTS, CONSUME_CLIENT_APPROVAL};
 use crate::utils::db_types::ClientInput; 
-use crate::utils::config::{DB_TRUE, NEW_CLIENTS_DISALLOW};
-use crate::utils::tms_utils::{self, create_hex_secret, hash_hex_secret, timestamp_utc, timestamp_utc_to_str, 
+use crate::utils::config::{DB_TRUE, NEW_CLIENTS_DISALLOW, NEW_CLIENTS_ON_APPROVAL};
+use crate::utils::tms_utils::{self, create_hex_secret, hash_hex_secret, hash_secret, timestamp_utc, timestamp_utc_to_str, 
                               RequestDebug, validate_semver, check_tenant_enabled};
 use log::{error, info};
 
@@ -29,6 +30,7 @@ pub struct ReqCreateClient
     tenant: String,
     app_name: String,
     app_version: String,
+    approval_code: Option<String>,  // required when new_clients = "on_approval"
 }
 
 #[derive(Object, Debug)]
@@ -54,6 +56,8 @@ impl RequestDebug for ReqCreateClient {
         s.push_str(&self.app_name);
         s.push_str("\n    app_version: ");
         s.push_str(&self.app_version);
+        s.push_str("\n    approval_code: ");
+        s.push_str(if self.approval_code.is_some() {"***"} else {"None"});
         s
     }
 }
@@ -65,6 +69,8 @@ enum TmsResponse {
     Http201(Json<RespCreateClient>),
     #[oai(status = 400)]
     Http400(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
     #[oai(status = 500)]
     Http500(Json<HttpResult>),
 }
@@ -75,6 +81,9 @@ fn make_http_201(resp: RespCreateClient) -> TmsResponse {
 fn make_http_400(msg: String) -> TmsResponse {
     TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
 }
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
 fn make_http_500(msg: String) -> TmsResponse {
     TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
 }
@@ -133,6 +142,21 @@ impl RespCreateClient {
             return Ok(make_http_400(msg.to_string()));
         }
 
+        // When new clients require approval, the caller must present a one-time
+        // code issued by the tenant admin.  The code is consumed when the client
+        // is inserted.
+        let approval_hash = if RUNTIME_CTX.parms.config.new_clients == NEW_CLIENTS_ON_APPROVAL {
+            match &req.approval_code {
+                Some(code) => Some(hash_hex_secret(code)),
+                None => {
+                    let msg = format!("ERROR: FORBIDDEN - An approval code issued by the administrator of tenant {} \
+                                      is required to create a client.", req.tenant);
+                    error!("{}", msg);
+                    return Ok(make_http_403(msg));
+                }
+            }
+        } else {None};
+
         // ------------------------ Validate Version -------------------
         // Only valid semantic versions are accepted.
         match validate_semver(req.app_version.as_str()) {
@@ -146,7 +170,7 @@ impl RespCreateClient {
 
         // ------------------------ Generate Secret --------------------  
         let client_secret_str  = create_hex_secret();
-        let client_secret_hash = hash_hex_secret(&client_secret_str);
+        let client_secret_hash = hash_secret(&client_secret_str).await?;
 
         // ------------------------ Update Database --------------------
         let now = timestamp_utc();
@@ -163,8 +187,15 @@ impl RespCreateClient {
             now.clone(),
         );
 
-        // Insert the new key record.
-        insert_new_client(input_record).await?;
+        // Insert the new client record.
+        if let Err(e) = insert_new_client(input_record, approval_hash).await {
+            let msg = e.to_string();
+            if msg.contains("APPROVAL_REJECTED:") {
+                error!("{}", msg);
+                return Ok(make_http_403(msg));
+            }
+            return Err(e);
+        }
         info!("Client '{}' created for application '{}:{}' in tenant '{}'.", 
               req.client_id, req.app_name, req.app_version, req.tenant);
         
@@ -179,11 +210,20 @@ impl RespCreateClient {
 // ---------------------------------------------------------------------------
 // insert_new_client:
 // ---------------------------------------------------------------------------
-async fn insert_new_client(rec: ClientInput) -> Result<u64> {
+/** Insert the client record.  When an approval code hash is provided, the 
+ * matching unexpired approval is deleted in the same transaction so that a
+ * code can only ever be used to create one client.
+ */
+async fn insert_new_client(rec: ClientInput, approval_hash: Option<String>) -> Result<u64> {
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
     let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Consume the approval code if one is required.
+    if let Some(hash) = approval_hash {
+        consume_approval(&mut tx, &rec.tenant, &hash).await?;
+    }
     
     // Create the insert statement.
     let result = sqlx::query(INSERT_CLIENTS)
@@ -203,3 +243,21 @@ async fn insert_new_client(rec: ClientInput) -> Result<u64> {
 
     Ok(result.rows_affected())
 }
+
+// ---------------------------------------------------------------------------
+// consume_approval:
+// ---------------------------------------------------------------------------
+async fn consume_approval(tx: &mut Transaction<'_, Postgres>, tenant: &String, approval_hash: &String) 
+    -> Result<()> {
+    let result = sqlx::query(CONSUME_CLIENT_APPROVAL)
+        .bind(tenant)
+        .bind(approval_hash)
+        .bind(timestamp_utc())
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    match result {
+        Some(_) => Ok(()),
+        None => Err(anyhow!("APPROVAL_REJECTED: The approval code is invalid, expired or already used in tenant {}.", tenant)),
+    }
+}
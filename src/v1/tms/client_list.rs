@@ -1,15 +1,16 @@
 #![forbid(unsafe_code)]
 
 use poem::Request;
-use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use poem_openapi::{ OpenApi, payload::Json, param::Query, Object, ApiResponse };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use sqlx::Row;
 
 use crate::utils::errors::HttpResult;
 use crate::utils::authz::{authorize, get_tenant_header, AuthzResult, AuthzTypes};
-use crate::utils::db_statements::LIST_CLIENTS_TEMPLATE;
-use crate::utils::tms_utils::{self, RequestDebug, sql_substitute_client_constraint, check_tenant_enabled};
+use crate::utils::db_statements::LIST_CLIENTS;
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms, get_client_constraint, check_tenant_enabled};
 use log::error;
 
 use crate::RUNTIME_CTX;
@@ -26,6 +27,10 @@ pub struct ListClientApi;
 struct ReqListClient
 {
     tenant: String,
+    created_after: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -35,6 +40,7 @@ pub struct RespListClient
     result_msg: String,
     num_clients: i32,
     clients: Vec<ClientListElement>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -58,6 +64,14 @@ impl RequestDebug for ReqListClient {
         s.push_str("  Request body:");
         s.push_str("\n    tenant: ");
         s.push_str(&self.tenant);
+        s.push_str("\n    created_after: ");
+        s.push_str(&format!("{:?}", self.created_after));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
         s
     }
 }
@@ -94,7 +108,10 @@ fn make_http_500(msg: String) -> TmsResponse {
 #[OpenApi]
 impl ListClientApi {
     #[oai(path = "/tms/client/list", method = "get")]
-    async fn get_clients(&self, http_req: &Request) -> TmsResponse {
+    async fn get_clients(&self, http_req: &Request, 
+                                created_after: Query<Option<DateTime<Utc>>>, limit: Query<Option<u32>>,
+                                cursor: Query<Option<String>>, sort: Query<Option<String>>) 
+        -> TmsResponse {
         // -------------------- Get Tenant Header --------------------
         // Get the required tenant header value.
         let hdr_tenant = match get_tenant_header(http_req) {
@@ -108,7 +125,8 @@ impl ListClientApi {
         }
 
         // Package the request parameters.        
-        let req = ReqListClient {tenant: hdr_tenant};
+        let req = ReqListClient {tenant: hdr_tenant, created_after: *created_after, limit: *limit,
+                                 cursor: cursor.clone(), sort: sort.clone()};
         
         // -------------------- Authorize ----------------------------
         // Only the tenant admin can query all client records; 
@@ -149,9 +167,9 @@ impl ClientListElement {
 impl RespListClient {
     /// Create a new response.
     #[allow(clippy::too_many_arguments)]
-    fn new(result_code: &str, result_msg: String, num_clients: i32, clients: Vec<ClientListElement>) 
+    fn new(result_code: &str, result_msg: String, num_clients: i32, clients: Vec<ClientListElement>, next_cursor: Option<String>) 
     -> Self {
-        Self {result_code: result_code.to_string(), result_msg, num_clients, clients}
+        Self {result_code: result_code.to_string(), result_msg, num_clients, clients, next_cursor}
         }
 
     /// Process the request.
@@ -159,13 +177,17 @@ impl RespListClient {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Validate the paging and filter parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
         // Search for the tenant/client ids in the database.  
         // The client_secret is never part of the response.
-        match list_clients(authz_result, req).await {
-            Ok(clients) =>
-                Ok(make_http_200(Self::new("0", "success".to_string(), clients.len() as i32, clients))),
-            Err(e) => Err(e),
-        }
+        let mut clients = list_clients(authz_result, req, &parms).await?;
+        let next_cursor = parms.paginate(&mut clients, |c| c.id);
+        Ok(make_http_200(Self::new("0", "success".to_string(), clients.len() as i32, clients, next_cursor)))
     }
 }
 
@@ -175,18 +197,22 @@ impl RespListClient {
 // ---------------------------------------------------------------------------
 // list_clients:
 // ---------------------------------------------------------------------------
-async fn list_clients(authz_result: &AuthzResult, req: &ReqListClient) -> Result<Vec<ClientListElement>> {
-    // Substitute the placeholder in the query template.  Uncommited transactions 
+async fn list_clients(authz_result: &AuthzResult, req: &ReqListClient, parms: &ListParms) -> Result<Vec<ClientListElement>> {
+    // Restrict the query to the authorized client when necessary.
+    let client_id = get_client_constraint(authz_result)?;
+
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
-    let sql_query = sql_substitute_client_constraint(LIST_CLIENTS_TEMPLATE, authz_result); 
-
-    // Get a connection to the db and start a transaction.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
     // Create the select statement.
-    let rows = sqlx::query(&sql_query)
-        .bind(req.tenant.clone())
+    let mut query = TmsQuery::new(LIST_CLIENTS)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and_opt("client_id", Cmp::Eq, client_id)
+        .and_opt("created", Cmp::Gt, req.created_after)
+        .paginate(parms);
+    let rows = query.build()
         .fetch_all(&mut *tx)
         .await?;
 
@@ -6,7 +6,7 @@ use anyhow::Result;
 
 use crate::utils::errors::HttpResult;
 use crate::utils::db_statements::UPDATE_CLIENT_SECRET;
-use crate::utils::tms_utils::{self, RequestDebug, create_hex_secret, hash_hex_secret, 
+use crate::utils::tms_utils::{self, RequestDebug, create_hex_secret, hash_secret, 
                               timestamp_utc, timestamp_utc_to_str, check_tenant_enabled};
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
 use log::{error, info};
@@ -152,7 +152,7 @@ impl RespUpdateClientSecret {
 
         // ------------------------ Generate Secret --------------------  
         let client_secret_str  = create_hex_secret();
-        let client_secret_hash = hash_hex_secret(&client_secret_str);
+        let client_secret_hash = hash_secret(&client_secret_str).await?;
 
         // Insert the new key record.
         update_client_secret(req, client_secret_hash).await?;
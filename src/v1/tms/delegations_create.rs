@@ -120,10 +120,8 @@ impl CreateDelegationsApi {
         }
 
         // -------------------- Authorize ----------------------------
-        // Currently, only the tenant admin can create a delegation record.
-        // When user authentication is implemented, we'll add user-own 
-        // authorization and any additional validation.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the tenant admin and the delegating user can create a delegation record.
+        let allowed = [AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to add a client delegation record in tenant {}.", req.tenant);
@@ -131,6 +129,14 @@ impl CreateDelegationsApi {
             return make_http_401(msg);
         }
 
+        // Make sure the request parms conform to the header values used for authorization.
+        if !authz_result.check_hdr_id(&req.client_user_id) {
+            let msg = format!("ERROR: FORBIDDEN - Payload parameters ({}@{}) differ from those in the request header.", 
+                                      req.client_user_id, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
         // -------------------- Process Request ----------------------
         match RespCreateDelegations::process(http_req, &req).await {
             Ok(r) => r,
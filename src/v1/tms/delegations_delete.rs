@@ -111,10 +111,8 @@ impl DeleteDelegationsApi {
         }
 
         // -------------------- Authorize ----------------------------
-        // Currently, only the tenant admin can delete a delegation record.
-        // When user authentication is implemented, we'll add user-own 
-        // authorization and any additional validation.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the tenant admin and the delegating user can delete a delegation record.
+        let allowed = [AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to delete delegation for user {} to client {} in tenant {}.", 
@@ -123,6 +121,14 @@ impl DeleteDelegationsApi {
             return make_http_401(msg);
         }
 
+        // Make sure the request parms conform to the header values used for authorization.
+        if !authz_result.check_hdr_id(&req.client_user_id) {
+            let msg = format!("ERROR: FORBIDDEN - Payload parameters ({}@{}) differ from those in the request header.", 
+                                      req.client_user_id, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
         // -------------------- Process Request ----------------------
         // Process the request.
         match RespDeleteDelegations::process(http_req, &req).await {
@@ -1,16 +1,17 @@
 #![forbid(unsafe_code)]
 
 use poem::Request;
-use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use poem_openapi::{ OpenApi, payload::Json, param::Query, Object, ApiResponse };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use sqlx::Row;
 
 use crate::utils::errors::HttpResult;
 
-use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, AuthzResult};
 use crate::utils::db_statements::LIST_DELEGATIONS;
-use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms, parse_status_filter, get_user_constraint, check_tenant_enabled};
 use log::error;
 
 use crate::RUNTIME_CTX;
@@ -27,6 +28,12 @@ pub struct ListDelegationsApi;
 struct ReqListDelegations
 {
     tenant: String,
+    client_user_id: Option<String>,
+    status: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -36,6 +43,7 @@ pub struct RespListDelegations
     result_msg: String,
     num_users: i32,
     users: Vec<DelegationsListElement>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -58,6 +66,18 @@ impl RequestDebug for ReqListDelegations {
         s.push_str("  Request body:");
         s.push_str("\n    tenant: ");
         s.push_str(&self.tenant);
+        s.push_str("\n    client_user_id: ");
+        s.push_str(&format!("{:?}", self.client_user_id));
+        s.push_str("\n    status: ");
+        s.push_str(&format!("{:?}", self.status));
+        s.push_str("\n    created_after: ");
+        s.push_str(&format!("{:?}", self.created_after));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
         s
     }
 }
@@ -94,7 +114,12 @@ fn make_http_500(msg: String) -> TmsResponse {
 #[OpenApi]
 impl ListDelegationsApi {
     #[oai(path = "/tms/delegations/list", method = "get")]
-    async fn get_delegations(&self, http_req: &Request) -> TmsResponse {
+    #[allow(clippy::too_many_arguments)]
+    async fn get_delegations(&self, http_req: &Request, 
+                                    client_user_id: Query<Option<String>>, status: Query<Option<String>>,
+                                    created_after: Query<Option<DateTime<Utc>>>, limit: Query<Option<u32>>,
+                                    cursor: Query<Option<String>>, sort: Query<Option<String>>) 
+        -> TmsResponse {
         // -------------------- Get Tenant Header --------------------
         // Get the required tenant header value.
         let hdr_tenant = match get_tenant_header(http_req) {
@@ -108,11 +133,14 @@ impl ListDelegationsApi {
         }
 
         // Package the request parameters.        
-        let req = ReqListDelegations {tenant: hdr_tenant};
+        let req = ReqListDelegations {tenant: hdr_tenant, client_user_id: client_user_id.clone(),
+                                      status: status.clone(), created_after: *created_after,
+                                      limit: *limit, cursor: cursor.clone(), sort: sort.clone()};
         
         // -------------------- Authorize ----------------------------
-        // Only the tenant admin can query a user delegation record.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the tenant admin can query all user delegation records; 
+        // a user can query their own records.
+        let allowed = [AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to list delegations in tenant {}.", req.tenant);
@@ -122,7 +150,7 @@ impl ListDelegationsApi {
 
         // -------------------- Process Request ----------------------
         // Process the request.
-        match RespListDelegations::process(http_req, &req).await {
+        match RespListDelegations::process(http_req, &req, &authz_result).await {
             Ok(r) => r,
             Err(e) => {
                 let msg = "ERROR: ".to_owned() + e.to_string().as_str();
@@ -148,21 +176,32 @@ impl DelegationsListElement {
 impl RespListDelegations {
     /// Create a new response.
     #[allow(clippy::too_many_arguments)]
-    fn new(result_code: &str, result_msg: String, num_users: i32, users: Vec<DelegationsListElement>) 
+    fn new(result_code: &str, result_msg: String, num_users: i32, users: Vec<DelegationsListElement>, next_cursor: Option<String>) 
     -> Self {
-        Self {result_code: result_code.to_string(), result_msg, num_users, users}
+        Self {result_code: result_code.to_string(), result_msg, num_users, users, next_cursor}
         }
 
     /// Process the request.
-    async fn process(http_req: &Request, req: &ReqListDelegations) -> Result<TmsResponse, anyhow::Error> {
+    async fn process(http_req: &Request, req: &ReqListDelegations, authz_result: &AuthzResult) -> Result<TmsResponse, anyhow::Error> {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Validate the paging and filter parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+        let active = match parse_status_filter(&req.status) {
+            Ok(a) => a,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
         // Search for the tenant/client id in the database.  Not found was already 
         // The client_secret is never part of the response.
-        let clients = list_delegations(req).await?;
+        let mut clients = list_delegations(authz_result, req, &parms, active).await?;
+        let next_cursor = parms.paginate(&mut clients, |e| e.id);
         Ok(make_http_200(Self::new("0", "success".to_string(), 
-                                        clients.len() as i32, clients)))
+                                        clients.len() as i32, clients, next_cursor)))
     }
 }
 
@@ -172,15 +211,24 @@ impl RespListDelegations {
 // ---------------------------------------------------------------------------
 // list_delegations:
 // ---------------------------------------------------------------------------
-async fn list_delegations(req: &ReqListDelegations) -> Result<Vec<DelegationsListElement>> {
+async fn list_delegations(authz_result: &AuthzResult, req: &ReqListDelegations, parms: &ListParms, active: Option<bool>) -> Result<Vec<DelegationsListElement>> {
+    // Restrict the query to the authorized user when necessary.
+    let user_id = get_user_constraint(authz_result)?;
+
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
     // Create the select statement.
-    let rows = sqlx::query(LIST_DELEGATIONS)
-        .bind(req.tenant.clone())
+    let mut query = TmsQuery::new(LIST_DELEGATIONS)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and_opt("client_user_id", Cmp::Eq, user_id)
+        .and_opt("client_user_id", Cmp::Eq, req.client_user_id.as_ref())
+        .and_active(active)
+        .and_opt("created", Cmp::Gt, req.created_after)
+        .paginate(parms);
+    let rows = query.build()
         .fetch_all(&mut *tx)
         .await?;
 
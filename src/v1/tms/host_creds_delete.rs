@@ -0,0 +1,175 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::DELETE_HOST_CREDS;
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+pub struct DeleteHostCredsApi;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+#[derive(Object)]
+pub struct ReqDeleteHostCreds
+{
+    host_id: String,
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespDeleteHostCreds
+{
+    result_code: String,
+    result_msg: String,
+    num_deleted: u32,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqDeleteHostCreds {   
+    type Req = ReqDeleteHostCreds;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    host_id: ");
+        s.push_str(&self.host_id);
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespDeleteHostCreds>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespDeleteHostCreds) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl DeleteHostCredsApi {
+    #[oai(path = "/tms/hostcreds/del/:host_id", method = "delete")]
+    async fn delete_host_creds(&self, http_req: &Request, host_id: Path<String>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+        
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqDeleteHostCreds {host_id: host_id.to_string(), tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can delete host credentials.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to delete credentials for host {} in tenant {}.", 
+                                      req.host_id, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespDeleteHostCreds::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespDeleteHostCreds {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, num_deleted: u32) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, num_deleted,}}
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqDeleteHostCreds) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Delete the record.
+        let deletes = delete_host_creds(req).await?;
+        
+        // Log result and return response.
+        let msg = 
+            if deletes < 1 {format!("Credentials for host {} NOT FOUND in tenant {} - Nothing deleted", req.host_id, req.tenant)}
+            else {format!("Credentials for host {} deleted in tenant {}", req.host_id, req.tenant)};
+        info!("{}", msg);
+        Ok(make_http_200(RespDeleteHostCreds::new("0", msg, deletes as u32)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// delete_host_creds:
+// ---------------------------------------------------------------------------
+async fn delete_host_creds(req: &ReqDeleteHostCreds) -> Result<u64> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
+    // are automatically rolled back when they go out of scope. 
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Deletion count.
+    let mut deletes: u64 = 0;
+
+    // Issue the db delete call.
+    let result = sqlx::query(DELETE_HOST_CREDS)
+        .bind(&req.host_id)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+    deletes += result.rows_affected();
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(deletes)
+}
@@ -0,0 +1,193 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::UPDATE_HOST_SECRET;
+use crate::utils::tms_utils::{self, RequestDebug, create_hex_secret, hash_secret, 
+                              timestamp_utc, check_tenant_enabled};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+pub struct UpdateHostCredsSecretApi;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+#[derive(Object)]
+pub struct ReqUpdateHostCredsSecret
+{
+    host_id: String,
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespUpdateHostCredsSecret
+{
+    result_code: String,
+    result_msg: String,
+    host_id: String,
+    tenant: String,
+    host_secret: String,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqUpdateHostCredsSecret {   
+    type Req = ReqUpdateHostCredsSecret;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    host_id: ");
+        s.push_str(&self.host_id);
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespUpdateHostCredsSecret>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 404)]
+    Http404(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespUpdateHostCredsSecret) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_404(msg: String) -> TmsResponse {
+    TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl UpdateHostCredsSecretApi {
+    #[oai(path = "/tms/hostcreds/secret/:host_id", method = "patch")]
+    async fn update_host_creds_secret(&self, http_req: &Request, host_id: Path<String>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqUpdateHostCredsSecret {host_id: host_id.to_string(), tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can reset a host's secret.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to update the secret of host {} in tenant {}.", 
+                                      req.host_id, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespUpdateHostCredsSecret::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespUpdateHostCredsSecret {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, host_id: String, tenant: String, host_secret: String,) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, host_id, tenant, host_secret,}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqUpdateHostCredsSecret) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // ------------------------ Generate Secret --------------------  
+        let host_secret_str  = create_hex_secret();
+        let host_secret_hash = hash_secret(&host_secret_str).await?;
+
+        // Replace the secret hash.
+        let updates = update_host_secret(req, host_secret_hash).await?;
+        if updates < 1 {
+            let msg = format!("Credentials for host {} NOT FOUND in tenant {}", req.host_id, req.tenant);
+            error!("{}", msg);
+            return Ok(make_http_404(msg));
+        }
+        
+        // Log result and return response.
+        let msg = format!("Secret updated for host {}", req.host_id);
+        info!("{}", msg);
+        Ok(make_http_200(RespUpdateHostCredsSecret::new("0", msg, req.host_id.clone(), 
+                                       req.tenant.clone(), host_secret_str)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// update_host_secret:
+// ---------------------------------------------------------------------------
+async fn update_host_secret(req: &ReqUpdateHostCredsSecret, host_secret_hash: String) -> Result<u64> {
+    // Get timestamp.
+    let now = timestamp_utc();
+
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
+    // are automatically rolled back when they go out of scope. 
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Issue the db update call.
+    let result = sqlx::query(UPDATE_HOST_SECRET)
+        .bind(host_secret_hash)
+        .bind(now)
+        .bind(&req.host_id)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
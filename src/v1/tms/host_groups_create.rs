@@ -0,0 +1,200 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::INSERT_HOST_GROUP_MEMBERS;
+use crate::utils::db::MAX_KEY_SCOPE_HOSTS;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, X_TMS_TENANT};
+use crate::utils::tms_utils::{self, timestamp_utc, RequestDebug, check_tenant_enabled};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Add hosts to a host group, creating the group if it doesn't exist.  Keys
+ * scoped to the group can be used on all of the group's members.  Hosts that
+ * are already members are ignored.
+ */
+pub struct CreateHostGroupsApi;
+
+#[derive(Object)]
+pub struct ReqCreateHostGroup
+{
+    tenant: String,
+    host_group: String,
+    hosts: Vec<String>,
+}
+
+#[derive(Object, Debug)]
+pub struct RespCreateHostGroup
+{
+    result_code: String,
+    result_msg: String,
+    tenant: String,
+    host_group: String,
+    num_added: u32,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqCreateHostGroup {
+    type Req = ReqCreateHostGroup;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    host_group: ");
+        s.push_str(&self.host_group);
+        s.push_str("\n    hosts: ");
+        s.push_str(&format!("{:?}", self.hosts));
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 201)]
+    Http201(Json<RespCreateHostGroup>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_201(resp: RespCreateHostGroup) -> TmsResponse {
+    TmsResponse::Http201(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl CreateHostGroupsApi {
+    #[oai(path = "/tms/hosts/groups", method = "post")]
+    async fn create_host_group_api(&self, http_req: &Request, req: Json<ReqCreateHostGroup>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check that the tenant specified in the header is the same as the one in the request body.
+        if hdr_tenant != req.tenant {
+            let msg = format!("ERROR: FORBIDDEN - The tenant in the {} header ({}) does not match the tenant in the request body ({})",
+                                      X_TMS_TENANT, hdr_tenant, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can change host groups.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to add hosts to host group {} in tenant {}.",
+                                      req.host_group, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        match RespCreateHostGroup::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespCreateHostGroup {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, tenant: String, host_group: String, num_added: u32)
+        -> Self {Self {result_code: result_code.to_string(), result_msg, tenant, host_group, num_added}}
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqCreateHostGroup) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Validate the group name and members.
+        let hosts: Vec<String> = req.hosts.iter().map(|h| h.trim().to_string()).collect();
+        if req.host_group.trim().is_empty() || hosts.is_empty() || hosts.iter().any(|h| h.is_empty()) {
+            let msg = "ERROR: A host group name and at least one non-empty host name are required.".to_string();
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+        if hosts.len() > MAX_KEY_SCOPE_HOSTS {
+            let msg = format!("ERROR: No more than {} hosts can be added to a host group at once.", MAX_KEY_SCOPE_HOSTS);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
+        // Insert the new members.
+        let added = insert_host_group_members(&req.tenant, req.host_group.trim(), &hosts).await?;
+        info!("{} hosts added to host group '{}' in tenant '{}'.", added, req.host_group.trim(), req.tenant);
+
+        Ok(make_http_201(Self::new("0", "success".to_string(), req.tenant.clone(),
+                         req.host_group.trim().to_string(), added as u32)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// insert_host_group_members:
+// ---------------------------------------------------------------------------
+async fn insert_host_group_members(tenant: &String, host_group: &str, hosts: &Vec<String>) -> Result<u64> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Existing members are skipped.
+    let result = sqlx::query(INSERT_HOST_GROUP_MEMBERS)
+        .bind(tenant)
+        .bind(host_group)
+        .bind(hosts)
+        .bind(timestamp_utc())
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
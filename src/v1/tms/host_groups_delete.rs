@@ -0,0 +1,186 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::DELETE_HOST_GROUP_MEMBERS;
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use crate::utils::authz::{authorize, get_tenant_header, AuthzTypes, X_TMS_TENANT};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Remove hosts from a host group.  Keys scoped to the group can no longer be
+ * used on the removed hosts.  The group no longer exists once all its members
+ * are removed.
+ */
+pub struct DeleteHostGroupsApi;
+
+#[derive(Object)]
+pub struct ReqDeleteHostGroup
+{
+    tenant: String,
+    host_group: String,
+    hosts: Vec<String>,
+}
+
+#[derive(Object, Debug)]
+pub struct RespDeleteHostGroup
+{
+    result_code: String,
+    result_msg: String,
+    num_deleted: u32,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqDeleteHostGroup {
+    type Req = ReqDeleteHostGroup;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    host_group: ");
+        s.push_str(&self.host_group);
+        s.push_str("\n    hosts: ");
+        s.push_str(&format!("{:?}", self.hosts));
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespDeleteHostGroup>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespDeleteHostGroup) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl DeleteHostGroupsApi {
+    #[oai(path = "/tms/hosts/groups/del", method = "delete")]
+    async fn delete_host_group_api(&self, http_req: &Request, req: Json<ReqDeleteHostGroup>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check that the tenant specified in the header is the same as the one in the request body.
+        if hdr_tenant != req.tenant {
+            let msg = format!("ERROR: FORBIDDEN - The tenant in the {} header ({}) does not match the tenant in the request body ({})",
+                                      X_TMS_TENANT, hdr_tenant, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can change host groups.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to remove hosts from host group {} in tenant {}.",
+                                      req.host_group, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespDeleteHostGroup::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespDeleteHostGroup {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, num_deleted: u32) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, num_deleted}}
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqDeleteHostGroup) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Remove the members.
+        let deletes = delete_host_group_members(req).await?;
+
+        // Log result and return response.
+        let msg =
+            if deletes < 1 {format!("No members of host group {} removed in tenant {}", req.host_group, req.tenant)}
+            else {format!("{} members of host group {} removed in tenant {}", deletes, req.host_group, req.tenant)};
+        info!("{}", msg);
+        Ok(make_http_200(RespDeleteHostGroup::new("0", msg, deletes as u32)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// delete_host_group_members:
+// ---------------------------------------------------------------------------
+async fn delete_host_group_members(req: &ReqDeleteHostGroup) -> Result<u64> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Issue the db delete call.
+    let result = sqlx::query(DELETE_HOST_GROUP_MEMBERS)
+        .bind(&req.tenant)
+        .bind(&req.host_group)
+        .bind(&req.hosts)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
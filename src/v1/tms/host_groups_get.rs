@@ -0,0 +1,177 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::Result;
+use sqlx::Row;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::GET_HOST_GROUP_MEMBERS;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use log::error;
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Get the members of a host group. */
+pub struct GetHostGroupsApi;
+
+#[derive(Object)]
+struct ReqGetHostGroup
+{
+    host_group: String,
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespGetHostGroup
+{
+    result_code: String,
+    result_msg: String,
+    tenant: String,
+    host_group: String,
+    hosts: Vec<String>,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqGetHostGroup {
+    type Req = ReqGetHostGroup;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    host_group: ");
+        s.push_str(&self.host_group);
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespGetHostGroup>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 404)]
+    Http404(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespGetHostGroup) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_404(msg: String) -> TmsResponse {
+    TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl GetHostGroupsApi {
+    #[oai(path = "/tms/hosts/groups/:host_group", method = "get")]
+    async fn get_host_group_api(&self, http_req: &Request, host_group: Path<String>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqGetHostGroup {host_group: host_group.to_string(), tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can view host groups.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to view host group {} in tenant {}",
+                                      req.host_group, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespGetHostGroup::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespGetHostGroup {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, tenant: String, host_group: String, hosts: Vec<String>)
+        -> Self {Self {result_code: result_code.to_string(), result_msg, tenant, host_group, hosts}}
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqGetHostGroup) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // A group exists as long as it has members.
+        let hosts = get_host_group_members(req).await?;
+        if hosts.is_empty() {
+            let msg = format!("NOT_FOUND: Host group {} not found in tenant {}.", req.host_group, req.tenant);
+            return Ok(make_http_404(msg));
+        }
+        Ok(make_http_200(Self::new("0", "success".to_string(), req.tenant.clone(),
+                                   req.host_group.clone(), hosts)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// get_host_group_members:
+// ---------------------------------------------------------------------------
+async fn get_host_group_members(req: &ReqGetHostGroup) -> Result<Vec<String>> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Get the group's members in host order.
+    let rows = sqlx::query(GET_HOST_GROUP_MEMBERS)
+        .bind(&req.tenant)
+        .bind(&req.host_group)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
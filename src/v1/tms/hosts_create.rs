@@ -7,6 +7,7 @@ use anyhow::Result;
 use crate::utils::errors::HttpResult;
 use crate::utils::db_statements::INSERT_HOSTS;
 use crate::utils::db_types::HostInput;
+use crate::utils::addr_match::AddrPattern;
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, X_TMS_TENANT}; 
 use crate::utils::tms_utils::{self, timestamp_utc, timestamp_utc_to_str, RequestDebug, check_tenant_enabled};
 use log::{error, info};
@@ -150,6 +151,13 @@ impl RespCreateHost {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Reject addresses that can never be matched.
+        if let Err(e) = AddrPattern::parse(&req.addr) {
+            let msg = format!("ERROR: Invalid address for host {} in tenant {}: {}", req.host, req.tenant, e);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
         // Use the same current UTC timestamp in all related time caculations..
         let now = timestamp_utc();
 
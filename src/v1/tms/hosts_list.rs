@@ -1,7 +1,7 @@
 #![forbid(unsafe_code)]
 
 use poem::Request;
-use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use poem_openapi::{ OpenApi, payload::Json, param::Query, Object, ApiResponse };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use sqlx::Row;
@@ -10,7 +10,8 @@ use crate::utils::errors::HttpResult;
 
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
 use crate::utils::db_statements::LIST_HOSTS;
-use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms, check_tenant_enabled};
 use log::error;
 
 use crate::RUNTIME_CTX;
@@ -27,6 +28,11 @@ pub struct ListHostsApi;
 struct ReqListHosts
 {
     tenant: String,
+    host: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -36,6 +42,7 @@ pub struct RespListHosts
     result_msg: String,
     num_mappings: i32,
     users: Vec<HostsListElement>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -57,6 +64,16 @@ impl RequestDebug for ReqListHosts {
         s.push_str("  Request body:");
         s.push_str("\n    tenant: ");
         s.push_str(&self.tenant);
+        s.push_str("\n    host: ");
+        s.push_str(&format!("{:?}", self.host));
+        s.push_str("\n    created_after: ");
+        s.push_str(&format!("{:?}", self.created_after));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
         s
     }
 }
@@ -93,7 +110,11 @@ fn make_http_500(msg: String) -> TmsResponse {
 #[OpenApi]
 impl ListHostsApi {
     #[oai(path = "/tms/hosts/list", method = "get")]
-    async fn get_list_hosts_api(&self, http_req: &Request) -> TmsResponse {
+    async fn get_list_hosts_api(&self, http_req: &Request, 
+                                       host: Query<Option<String>>, created_after: Query<Option<DateTime<Utc>>>,
+                                       limit: Query<Option<u32>>, cursor: Query<Option<String>>,
+                                       sort: Query<Option<String>>) 
+        -> TmsResponse {
         // -------------------- Get Tenant Header --------------------
         // Get the required tenant header value.
         let hdr_tenant = match get_tenant_header(http_req) {
@@ -107,7 +128,9 @@ impl ListHostsApi {
         }
 
         // Package the request parameters.        
-        let req = ReqListHosts {tenant: hdr_tenant};
+        let req = ReqListHosts {tenant: hdr_tenant, host: host.clone(),
+                                created_after: *created_after, limit: *limit,
+                                cursor: cursor.clone(), sort: sort.clone()};
         
         // -------------------- Authorize ----------------------------
         // Only the tenant admin can query a user host record.
@@ -147,9 +170,9 @@ impl HostsListElement {
 impl RespListHosts {
     /// Create a new response.
     #[allow(clippy::too_many_arguments)]
-    fn new(result_code: &str, result_msg: String, num_mappings: i32, users: Vec<HostsListElement>) 
+    fn new(result_code: &str, result_msg: String, num_mappings: i32, users: Vec<HostsListElement>, next_cursor: Option<String>) 
     -> Self {
-        Self {result_code: result_code.to_string(), result_msg, num_mappings, users}
+        Self {result_code: result_code.to_string(), result_msg, num_mappings, users, next_cursor}
         }
 
     /// Process the request.
@@ -157,11 +180,18 @@ impl RespListHosts {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Validate the paging and filter parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
         // Search for the tenant/client id in the database.  Not found was already 
         // The client_secret is never part of the response.
-        let clients = list_hosts_users(req).await?;
+        let mut clients = list_hosts_users(req, &parms).await?;
+        let next_cursor = parms.paginate(&mut clients, |e| e.id);
         Ok(make_http_200(Self::new("0", "success".to_string(), 
-                                        clients.len() as i32, clients)))
+                                        clients.len() as i32, clients, next_cursor)))
     }
 }
 
@@ -171,15 +201,19 @@ impl RespListHosts {
 // ---------------------------------------------------------------------------
 // list_hosts_users:
 // ---------------------------------------------------------------------------
-async fn list_hosts_users(req: &ReqListHosts) -> Result<Vec<HostsListElement>> {
+async fn list_hosts_users(req: &ReqListHosts, parms: &ListParms) -> Result<Vec<HostsListElement>> {
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
     // Create the select statement.
-    let rows = sqlx::query(LIST_HOSTS)
-        .bind(req.tenant.clone())
+    let mut query = TmsQuery::new(LIST_HOSTS)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and_opt("host", Cmp::Eq, req.host.as_ref())
+        .and_opt("created", Cmp::Gt, req.created_after)
+        .paginate(parms);
+    let rows = query.build()
         .fetch_all(&mut *tx)
         .await?;
 
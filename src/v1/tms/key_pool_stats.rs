@@ -0,0 +1,154 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::key_pool::get_key_pool_stats;
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use log::error;
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Report the depth and usage of the pre-generated key pool of each key type.
+ * The key pool is shared by all tenants.
+ */
+pub struct GetKeyPoolStatsApi;
+
+#[derive(Object)]
+struct ReqGetKeyPoolStats
+{
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespGetKeyPoolStats
+{
+    result_code: String,
+    result_msg: String,
+    enabled: bool,
+    pools: Vec<KeyPoolStatsElement>,
+}
+
+#[derive(Object, Debug)]
+pub struct KeyPoolStatsElement
+{
+    key_type: String,
+    target: u32,
+    depth: u32,
+    hits: u64,
+    misses: u64,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqGetKeyPoolStats {
+    type Req = ReqGetKeyPoolStats;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespGetKeyPoolStats>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespGetKeyPoolStats) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl GetKeyPoolStatsApi {
+    #[oai(path = "/tms/keypool/stats", method = "get")]
+    async fn get_key_pool_stats_api(&self, http_req: &Request) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqGetKeyPoolStats {tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Only tenant admins can view the key pool statistics.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to view key pool statistics in tenant {}.", req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespGetKeyPoolStats::process(http_req, &req) {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespGetKeyPoolStats {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, enabled: bool, pools: Vec<KeyPoolStatsElement>) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, enabled, pools}
+    }
+
+    /// Process the request.
+    fn process(http_req: &Request, req: &ReqGetKeyPoolStats) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // The statistics are kept in memory.
+        let enabled = RUNTIME_CTX.parms.config.key_pool.enabled;
+        let pools = get_key_pool_stats().into_iter()
+            .map(|p| KeyPoolStatsElement {key_type: p.key_type, target: p.target, depth: p.depth,
+                                          hits: p.hits, misses: p.misses})
+            .collect();
+        Ok(make_http_200(Self::new("0", "success".to_string(), enabled, pools)))
+    }
+}
@@ -0,0 +1,181 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::db::get_tenant_allowed_key_types;
+use crate::utils::keygen::{get_key_type_info, parse_key_type};
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use log::error;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** List the key types that TMS supports in the caller's tenant.  Each key type
+ * has its supported lengths and curves, whether TMS generates keys of the type
+ * or only registers them, and the key specs (see the tenant allowed_key_types
+ * policy) that the tenant allows.  The key type used when a request doesn't
+ * specify one is also returned.
+ */
+pub struct GetKeyTypesApi;
+
+#[derive(Object)]
+struct ReqGetKeyTypes
+{
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespGetKeyTypes
+{
+    result_code: String,
+    result_msg: String,
+    tenant: String,
+    default_key_type: String,
+    key_types: Vec<KeyTypeElement>,
+}
+
+#[derive(Object, Debug)]
+pub struct KeyTypeElement
+{
+    key_type: String,
+    key_bits: Vec<i32>,
+    default_key_bits: Option<i32>,  // generated key types only
+    min_key_bits: Option<i32>,      // registered keys whose length is not fixed
+    curves: Vec<String>,
+    generated: bool,
+    registered: bool,
+    key_specs: Vec<String>,
+    allowed_key_specs: Vec<String>,
+    allowed: bool,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqGetKeyTypes {
+    type Req = ReqGetKeyTypes;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespGetKeyTypes>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespGetKeyTypes) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl GetKeyTypesApi {
+    #[oai(path = "/tms/keytypes", method = "get")]
+    async fn get_key_types_api(&self, http_req: &Request) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqGetKeyTypes {tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Clients and tenant admins can view the supported key types.
+        let allowed = [AuthzTypes::ClientOwn, AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to view the key types in tenant {}.", req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespGetKeyTypes::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespGetKeyTypes {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, tenant: String, default_key_type: String,
+           key_types: Vec<KeyTypeElement>) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, tenant, default_key_type, key_types}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqGetKeyTypes) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // No policy allows all supported key specs.
+        let policy = get_tenant_allowed_key_types(&req.tenant).await?;
+        let key_types = get_key_type_info().into_iter()
+            .map(|info| {
+                let allowed_key_specs: Vec<String> = info.key_specs.iter()
+                    .filter(|spec| policy.as_ref().is_none_or(|p| p.contains(spec)))
+                    .cloned()
+                    .collect();
+                KeyTypeElement {
+                    key_type: info.key_type.to_string(),
+                    key_bits: info.key_bits,
+                    default_key_bits: info.default_key_bits,
+                    min_key_bits: info.min_key_bits,
+                    curves: info.curves,
+                    generated: info.generated,
+                    registered: info.registered,
+                    key_specs: info.key_specs,
+                    allowed: !allowed_key_specs.is_empty(),
+                    allowed_key_specs,
+                }
+            })
+            .collect();
+
+        // The default key type is the one chosen when none is requested.
+        let default_key_type = parse_key_type(&None)?.to_string();
+        Ok(make_http_200(Self::new("0", "success".to_string(), req.tenant.clone(), default_key_type, key_types)))
+    }
+}
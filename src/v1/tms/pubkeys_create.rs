@@ -3,16 +3,20 @@
 use poem::Request;
 use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
 use anyhow::{Result, anyhow};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::Row;
 
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, get_client_id_header};
 use crate::utils::errors::HttpResult;
-use crate::utils::keygen::{self, KeyType};
-use crate::utils::db_types::PubkeyInput;
-use crate::utils::db_statements::INSERT_PUBKEYS;
-use crate::utils::db::check_pubkey_dependencies;
+use crate::utils::keygen::{parse_key_type, get_key_bits, check_key_allowed};
+use crate::utils::key_pool;
+use crate::utils::db_types::{KeyScope, PubkeyInput};
+use crate::utils::db_statements::GET_HOST_GROUP_MEMBERS;
+use crate::utils::db::{check_pubkey_dependencies, check_key_scope, get_tenant_allowed_key_types, insert_pubkey_in_tx};
 use crate::utils::tms_utils::{self, timestamp_utc, timestamp_utc_to_str, calc_expires_at, RequestDebug, check_tenant_enabled};
 use crate::utils::mvp::{MVPDependencyParms, create_pubkey_dependencies};
+use crate::utils::ssh_ca::{get_host_cert_principal, get_tenant_ca, sign_user_cert};
+use crate::utils::key_encryption::{encrypt_with_passphrase, encrypt_to_recipient, KeyEnvelope};
 use log::{error, info};
 
 use crate::RUNTIME_CTX;
@@ -20,6 +24,9 @@ use crate::RUNTIME_CTX;
 // ***************************************************************************
 //                          Request/Response Definitions
 // ***************************************************************************
+// Certificates are backdated to tolerate clock skew between TMS and the hosts.
+const CERT_BACKDATE_SECS: i64 = 300;
+
 pub struct NewSshKeysApi;
 
 #[derive(Object)]
@@ -31,6 +38,20 @@ pub struct ReqNewSshKeys
     num_uses: i32,     // negative means i32::MAX
     ttl_minutes: i32,  // negative means i32::MAX
     key_type: Option<String>,  // RSA, ECDSA, ED25519, DEFAULT (=ED25519)   
+    key_bits: Option<i32>,     // RSA 2048, 3072, 4096 (default); ECDSA 256, 384, 521 (default)
+    private_key_encryption: Option<ReqPrivateKeyEncryption>,
+    hosts: Option<Vec<String>>,   // additional hosts on which the key can be used
+    host_group: Option<String>,   // host group on whose members the key can be used
+    host_bound_principals: Option<bool>,  // certificates only, default false names host_account
+}
+
+// Exactly one of the fields must be set to return the private key encrypted.
+// Key rotation accepts the same encryption options.
+#[derive(Object)]
+pub struct ReqPrivateKeyEncryption
+{
+    passphrase: Option<String>,            // passphrase protected OpenSSH private key
+    recipient_public_key: Option<String>,  // envelope encrypted to an ed25519 or rsa OpenSSH public key
 }
 
 #[derive(Object, Debug)]
@@ -46,6 +67,27 @@ struct RespNewSshKeys
     max_uses: String,
     remaining_uses: String,
     expires_at: DateTime<Utc>,
+    certificate: Option<String>,
+    certificate_expires_at: Option<DateTime<Utc>>,
+    private_key_envelope: Option<RespPrivateKeyEnvelope>,
+}
+
+// The private_key field is empty when the private key is returned in an envelope.
+#[derive(Object, Debug)]
+pub struct RespPrivateKeyEnvelope
+{
+    scheme: String,
+    recipient_fingerprint: String,
+    encapsulated_key: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl From<KeyEnvelope> for RespPrivateKeyEnvelope {
+    fn from(env: KeyEnvelope) -> Self {
+        Self {scheme: env.scheme, recipient_fingerprint: env.recipient_fingerprint, 
+              encapsulated_key: env.encapsulated_key, nonce: env.nonce, ciphertext: env.ciphertext}
+    }
 }
 
 // Implement the debug record trait for logging.
@@ -70,6 +112,16 @@ impl RequestDebug for ReqNewSshKeys {
             None => "None",
         };
         s.push_str(kt);
+        s.push_str("\n    key_bits: ");
+        s.push_str(&format!("{:?}", self.key_bits));
+        s.push_str("\n    private_key_encryption: ");
+        s.push_str(private_key_encryption_mode(&self.private_key_encryption));
+        s.push_str("\n    hosts: ");
+        s.push_str(&format!("{:?}", self.hosts));
+        s.push_str("\n    host_group: ");
+        s.push_str(&format!("{:?}", self.host_group));
+        s.push_str("\n    host_bound_principals: ");
+        s.push_str(&format!("{:?}", self.host_bound_principals));
         s.push('\n');
         s
     }
@@ -126,7 +178,26 @@ fn make_http_500(msg: String) -> TmsResponse {
 impl NewSshKeysApi {
     #[oai(path = "/tms/pubkeys/creds", method = "post")]
     async fn get_new_ssh_keys(&self, http_req: &Request, req: Json<ReqNewSshKeys>) -> TmsResponse {
-        match RespNewSshKeys::process(http_req, &req).await {
+        match RespNewSshKeys::process(http_req, &req, false).await {
+            Ok(r) => r,
+            Err(e) => {
+                // Assume a server fault if a raw error came through.
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+
+    /** Create a key pair and a user certificate for its public key signed by
+     * the tenant's certificate authority.  The certificate's principal is the
+     * host account, or, when host_bound_principals is set, the host account on
+     * each host in the key's scope, such as bud@host1.  The certificate expires
+     * when the key or any of its dependencies expire.
+     */
+    #[oai(path = "/tms/pubkeys/creds/cert", method = "post")]
+    async fn get_new_ssh_keys_cert(&self, http_req: &Request, req: Json<ReqNewSshKeys>) -> TmsResponse {
+        match RespNewSshKeys::process(http_req, &req, true).await {
             Ok(r) => r,
             Err(e) => {
                 // Assume a server fault if a raw error came through.
@@ -145,15 +216,18 @@ impl RespNewSshKeys {
     #[allow(clippy::too_many_arguments)]
     fn new(result_code: &str, result_msg: &str, private_key: String, public_key: String, 
            public_key_fingerprint: String, key_type: String, key_bits: String,
-           max_uses: String, remaining_uses: String, expires_at: DateTime<Utc>) -> Self {
+           max_uses: String, remaining_uses: String, expires_at: DateTime<Utc>,
+           certificate: Option<String>, certificate_expires_at: Option<DateTime<Utc>>,
+           private_key_envelope: Option<RespPrivateKeyEnvelope>) -> Self {
         Self {result_code: result_code.to_string(), 
               result_msg: result_msg.to_string(), 
               private_key, public_key, public_key_fingerprint,
               key_type, key_bits, max_uses, remaining_uses, expires_at,
+              certificate, certificate_expires_at, private_key_envelope,
             }
     }
 
-    async fn process(http_req: &Request, req: &ReqNewSshKeys) -> Result<TmsResponse, anyhow::Error> {
+    async fn process(http_req: &Request, req: &ReqNewSshKeys, with_cert: bool) -> Result<TmsResponse, anyhow::Error> {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
@@ -182,6 +256,43 @@ impl RespNewSshKeys {
             return Ok(make_http_401(msg));
         }
 
+        // -------------------- Validate Key Type ---------------------
+        // Unknown key types and lengths, and those not allowed in the tenant, are rejected.
+        let allowed_key_types = get_tenant_allowed_key_types(&req_ext.tenant).await?;
+        let key_spec = parse_key_type(&req.key_type)
+            .and_then(|key_type| Ok((key_type, get_key_bits(key_type, req.key_bits)?)))
+            .and_then(|(key_type, key_bits)| {
+                check_key_allowed(&allowed_key_types, key_type, key_bits)?;
+                Ok((key_type, key_bits))
+            });
+        let (key_type, key_bits) = match key_spec {
+            Ok(spec) => spec,
+            Err(e) => {
+                let msg = format!("ERROR: {}", e);
+                error!("{}", msg);
+                return Ok(make_http_400(msg));
+            }
+        };
+
+        // Exactly one encryption mode must be specified when encryption is requested.
+        if let Err(e) = validate_private_key_encryption(&req.private_key_encryption) {
+            let msg = format!("ERROR: {}", e);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
+        // The key can also be used on the hosts in its scope.  The dependencies 
+        // of those hosts are checked when the key is retrieved.
+        let scope = match check_key_scope(&req_ext.tenant, &req.host, &req.hosts, &req.host_group).await {
+            Ok(s) => s,
+            Err(e) => {
+                let msg = format!("ERROR: {}", e);
+                error!("{}", msg);
+                if msg.contains("BAD_REQUEST:") {return Ok(make_http_400(msg));}
+                else {return Ok(make_http_500(msg));}
+            }
+        };
+
         // -------------------- MVP Execution ------------------------
         // Determine if we are running in minimal viable product mode.
         if RUNTIME_CTX.parms.config.enable_mvp {
@@ -218,10 +329,11 @@ impl RespNewSshKeys {
         //
         // This method returns a detailed error message that indicates which table did not contain
         // the required values and whether the error resulted from a missing or expired record.
-        match check_pubkey_dependencies(&req_ext.tenant, &req_ext.client_id,
+        // On success, the earliest expiration time of the dependencies is returned.
+        let deps_expires_at = match check_pubkey_dependencies(&req_ext.tenant, &req_ext.client_id,
                                         &req.client_user_id, &req.host, &req.host_account).await
         {
-            Ok(_) => (),
+            Ok(t) => t,
             Err(e) => {
                 let msg = format!("Missing or expired dependency: {}", e);
                 error!("{}", msg);
@@ -229,32 +341,31 @@ impl RespNewSshKeys {
                 else {return Ok(make_http_403(msg));}
 
             } 
-        }
-
-        // ------------------------ Generate Keys ------------------------
-        // Get the caller's key type or use default.
-        let key_type_str = match &req.key_type {
-            Some(k) => k.as_str(),
-            None => "ED25519",
-        };
-        let key_type_upper = key_type_str.to_uppercase();
-
-        // Get the enumerated key type.
-        let key_type = match key_type_upper.as_str() {
-            "RSA" => KeyType::Rsa,
-            "ECDSA" => KeyType::Ecdsa,
-            "ED25519" => KeyType::Ed25519,
-            _ => KeyType::Ed25519,
         };
 
+        // ------------------------ Generate Keys ------------------------
         // Generate the new key pair.
-        let keyinfo = match keygen::generate_key(key_type) {
+        let keyinfo = match key_pool::get_key(key_type, key_bits).await {
             Ok(k) => k,
             Err(e) => {
                 return Result::Err(anyhow!(e));
             }
         };
         
+        // ------------------------ Encrypt Private Key ------------------
+        // The private key is encrypted before the key is saved so that no key is
+        // created when encryption fails.  The plaintext private key is discarded.
+        let (private_key, private_key_envelope) = match encrypt_private_key(&keyinfo.private_key, 
+                                                                            &req.private_key_encryption).await {
+            Ok(k) => k,
+            Err(e) => {
+                let msg = format!("ERROR: Unable to encrypt private key: {}", e);
+                error!("{}", msg);
+                if msg.contains("BAD_REQUEST:") {return Ok(make_http_400(msg));}
+                else {return Ok(make_http_500(msg));}
+            }
+        };
+
         // ------------------------ Update Database --------------------
         // Interpret numeric input.
         let max_uses = if req.num_uses < 0 {i32::MAX} else {req.num_uses};
@@ -269,6 +380,35 @@ impl RespNewSshKeys {
         let expires_at  = calc_expires_at(now, req.ttl_minutes); 
         let remaining_uses = max_uses;
 
+        // ------------------------ Sign Certificate ---------------------
+        // The certificate is signed before the key is saved so that no key is
+        // created when signing fails.  The certificate cannot outlive the key
+        // or the records that authorize the key's use.  Host-bound principals
+        // only authorize login to the host account on the hosts in the key's scope.
+        let (certificate, certificate_serial, certificate_expires_at) = if with_cert {
+            let valid_before = expires_at.min(deps_expires_at);
+            let valid_after = now - TimeDelta::seconds(CERT_BACKDATE_SECS);
+            let key_id = format!("{}@{}:{}", req.client_user_id, req_ext.tenant, keyinfo.public_key_fingerprint);
+            let principals: Vec<String> = if req.host_bound_principals.unwrap_or(false) {
+                get_cert_hosts(&req_ext.tenant, &req.host, &scope).await?
+                    .iter()
+                    .map(|h| get_host_cert_principal(&req.host_account, h))
+                    .collect()
+            } else {vec!(req.host_account.clone())};
+            let ca = get_tenant_ca(&req_ext.tenant).await?;
+            let cert = match sign_user_cert(&ca, &keyinfo.public_key, &key_id, &principals,
+                                            valid_after, valid_before) {
+                Ok(c) => c,
+                Err(e) => {
+                    let msg = format!("Unable to sign certificate: {}", e);
+                    error!("{}", msg);
+                    if msg.contains("EXPIRED:") {return Ok(make_http_403(msg));}
+                    else {return Ok(make_http_500(msg));}
+                }
+            };
+            (Some(cert.certificate), Some(cert.serial), Some(valid_before))
+        } else {(None, None, None)};
+
         // Create the input record.
         let input_record = PubkeyInput::new(
             req_ext.tenant.clone(),
@@ -286,6 +426,9 @@ impl RespNewSshKeys {
             expires_at.clone(), 
             now.clone(), 
             now.clone(),
+            scope,
+            None,
+            certificate_serial,
         );
 
         // Insert the new key record.
@@ -293,16 +436,24 @@ impl RespNewSshKeys {
         info!("A key of type '{}' created for '{}@{}' for host '{}' expires at {} and has {} remaining uses.", 
             keyinfo.key_type.clone(), req.client_user_id, req_ext.tenant, req.host, expires_at, remaining_uses);
 
+        if let Some(cert_expires_at) = certificate_expires_at {
+            info!("A certificate for account '{}' on host '{}' signed by the '{}' tenant CA expires at {}.",
+                req.host_account, req.host, req_ext.tenant, cert_expires_at);
+        }
+
         // Success! Zero key bits means a fixed key length.
         Ok(make_http_201(Self::new("0", "success", 
-                    keyinfo.private_key, 
+                    private_key, 
                     keyinfo.public_key, 
                     keyinfo.public_key_fingerprint,
                     keyinfo.key_type,
                     keyinfo.key_bits.to_string(),
                     max_uses.to_string(),
     remaining_uses.to_string(),
-                    expires_at,)))
+                    expires_at,
+                    certificate,
+                    certificate_expires_at,
+                    private_key_envelope,)))
     }
 }
 
@@ -312,36 +463,109 @@ impl RespNewSshKeys {
 // ---------------------------------------------------------------------------
 // insert_new_key:
 // ---------------------------------------------------------------------------
-async fn insert_new_key(rec: PubkeyInput) -> Result<u64> {
+async fn insert_new_key(rec: PubkeyInput) -> Result<i32> {
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
-    // Create the insert statement.
-    let result = sqlx::query(INSERT_PUBKEYS)
-        .bind(rec.tenant)
-        .bind(rec.client_id)
-        .bind(rec.client_user_id)
-        .bind(rec.host)
-        .bind(rec.host_account)
-        .bind(rec.public_key_fingerprint)
-        .bind(rec.public_key)
-        .bind(rec.key_type)
-        .bind(rec.key_bits)
-        .bind(rec.max_uses)
-        .bind(rec.remaining_uses)
-        .bind(rec.initial_ttl_minutes)
-        .bind(rec.expires_at)
-        .bind(rec.created)
-        .bind(rec.updated)
-        .execute(&mut *tx)
-        .await?;
+    // Insert the key and the hosts in its scope.
+    let pubkey_id = insert_pubkey_in_tx(&mut tx, &rec).await?;
 
     // Commit the transaction.
     tx.commit().await?;
 
-    Ok(result.rows_affected())
+    Ok(pubkey_id)
+}
+
+// ---------------------------------------------------------------------------
+// get_cert_hosts:
+// ---------------------------------------------------------------------------
+/** Get the hosts named in a certificate, which are the key's host, the other
+ * hosts in its scope and the current members of its host group.  Unlike the
+ * key itself, the certificate cannot be used on hosts that join the group 
+ * after it's signed.
+ */
+async fn get_cert_hosts(tenant: &str, host: &str, scope: &KeyScope) -> Result<Vec<String>> {
+    let mut hosts = vec!(host.to_string());
+    hosts.extend(scope.hosts.iter().cloned());
+    if let Some(group) = &scope.host_group {
+        let rows = sqlx::query(GET_HOST_GROUP_MEMBERS)
+            .bind(tenant)
+            .bind(group)
+            .fetch_all(&RUNTIME_CTX.db)
+            .await?;
+        for row in rows {
+            let member: String = row.get(0);
+            if !hosts.contains(&member) {hosts.push(member);}
+        }
+    }
+    Ok(hosts)
+}
+
+// ---------------------------------------------------------------------------
+// validate_private_key_encryption:
+// ---------------------------------------------------------------------------
+/** Check that exactly one encryption mode is specified when encryption is 
+ * requested.
+ */
+pub fn validate_private_key_encryption(encryption: &Option<ReqPrivateKeyEncryption>) -> Result<()> {
+    if let Some(encryption) = encryption {
+        if encryption.passphrase.is_some() == encryption.recipient_public_key.is_some() {
+            return Err(anyhow!("Specify either a passphrase or a recipient_public_key to encrypt the private key."));
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// private_key_encryption_mode:
+// ---------------------------------------------------------------------------
+/** Name the requested encryption mode for logging, which never shows the 
+ * passphrase.
+ */
+pub fn private_key_encryption_mode(encryption: &Option<ReqPrivateKeyEncryption>) -> &'static str {
+    match encryption {
+        Some(e) if e.passphrase.is_some() => "passphrase",
+        Some(e) if e.recipient_public_key.is_some() => "envelope",
+        _ => "None",
+    }
+}
+
+// ---------------------------------------------------------------------------
+// encrypt_private_key:
+// ---------------------------------------------------------------------------
+/** Return the private key in the form requested by the caller:  plaintext, 
+ * passphrase protected or, with an empty private key, in an envelope.
+ */
+pub async fn encrypt_private_key(private_key: &str, encryption: &Option<ReqPrivateKeyEncryption>) 
+    -> Result<(String, Option<RespPrivateKeyEnvelope>)> {
+    let encryption = match encryption {
+        Some(e) => e,
+        None => return Ok((private_key.to_string(), None)),
+    };
+    if let Some(passphrase) = &encryption.passphrase {
+        return Ok((encrypt_with_passphrase_blocking(private_key, passphrase).await?, None));
+    }
+    match &encryption.recipient_public_key {
+        Some(recipient) => {
+            let envelope = encrypt_to_recipient(private_key, recipient)?;
+            Ok((String::new(), Some(envelope.into())))
+        },
+        None => Err(anyhow!("BAD_REQUEST: No private key encryption mode specified.")),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// encrypt_with_passphrase_blocking:
+// ---------------------------------------------------------------------------
+// Passphrase key derivation is deliberately slow, so it runs on the blocking pool.
+async fn encrypt_with_passphrase_blocking(private_key: &str, passphrase: &str) -> Result<String> {
+    let (private_key, passphrase) = (private_key.to_string(), passphrase.to_string());
+    match tokio::task::spawn_blocking(move || encrypt_with_passphrase(&private_key, &passphrase)).await {
+        Ok(result) => result,
+        Err(e) => Err(anyhow!("Private key encryption task failed: {}", e)),
+    }
 }
 
 // ---------------------------------------------------------------------------
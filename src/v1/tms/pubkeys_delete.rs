@@ -6,8 +6,8 @@ use anyhow::Result;
 
 use crate::utils::errors::HttpResult;
 use crate::utils::db_statements::DELETE_PUBKEY;
-use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
-use crate::utils::authz::{authorize, AuthzTypes};
+use crate::utils::tms_utils::{self, RequestDebug, get_user_constraint, check_tenant_enabled};
+use crate::utils::authz::{authorize, AuthzTypes, AuthzResult};
 use log::{error, info};
 
 use crate::RUNTIME_CTX;
@@ -94,8 +94,8 @@ impl DeletePubkeysApi {
     #[oai(path = "/tms/pubkeys/del", method = "delete")]
     async fn delete_pubkey_api(&self, http_req: &Request, req: Json<ReqDeletePubkey>) -> TmsResponse {
         // -------------------- Authorize ----------------------------
-        // Only the client and tenant admin can access a pubkeys record.
-        let allowed = [AuthzTypes::ClientOwn, AuthzTypes::TenantAdmin];
+        // Only the client, the key's user and tenant admin can access a pubkeys record.
+        let allowed = [AuthzTypes::ClientOwn, AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to delete public key {} in tenant {}.", req.client_id, req.tenant);
@@ -104,7 +104,9 @@ impl DeletePubkeysApi {
         }
 
         // Make sure the request parms conform to the header values used for authorization.
-        if !authz_result.check_hdr_id(&req.client_id) || !authz_result.check_hdr_tenant(&req.tenant) {
+        // Users can delete their own keys no matter which client created them.
+        let user_own = authz_result.authz_type == Some(AuthzTypes::UserOwn);
+        if (!user_own && !authz_result.check_hdr_id(&req.client_id)) || !authz_result.check_hdr_tenant(&req.tenant) {
             let msg = format!("ERROR: NOT AUTHORIZED - Payload parameters ({}@{}) differ from those in the request header.", 
                                       req.client_id, req.tenant);
             error!("{}", msg);
@@ -118,7 +120,7 @@ impl DeletePubkeysApi {
 
         // -------------------- Process Request ----------------------
         // Process the request.
-        match RespDeletePubkey::process(http_req, &req).await {
+        match RespDeletePubkey::process(http_req, &req, &authz_result).await {
             Ok(r) => r,
             Err(e) => {
                 let msg = "ERROR: ".to_owned() + e.to_string().as_str();
@@ -138,12 +140,12 @@ impl RespDeletePubkey {
         Self {result_code: result_code.to_string(), result_msg, num_deleted}}
 
     /// Process the request.
-    async fn process(http_req: &Request, req: &ReqDeletePubkey) -> Result<TmsResponse, anyhow::Error> {
+    async fn process(http_req: &Request, req: &ReqDeletePubkey, authz_result: &AuthzResult) -> Result<TmsResponse, anyhow::Error> {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
         // Insert the new key record.
-        let deletes = delete_pubkey(req).await?;
+        let deletes = delete_pubkey(authz_result, req).await?;
         
         // Log result and return response.
         let msg = 
@@ -161,7 +163,10 @@ impl RespDeletePubkey {
 // ---------------------------------------------------------------------------
 // delete_pubkey:
 // ---------------------------------------------------------------------------
-async fn delete_pubkey(req: &ReqDeletePubkey) -> Result<u64> {
+async fn delete_pubkey(authz_result: &AuthzResult, req: &ReqDeletePubkey) -> Result<u64> {
+    // Restrict the deletion to the authorized user's keys when necessary.
+    let user_id = get_user_constraint(authz_result)?;
+
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
@@ -176,6 +181,7 @@ async fn delete_pubkey(req: &ReqDeletePubkey) -> Result<u64> {
         .bind(&req.tenant)
         .bind(&req.host)
         .bind(&req.public_key_fingerprint)
+        .bind(user_id)
         .execute(&mut *tx)
         .await?;
     deletes += result.rows_affected();
@@ -8,8 +8,9 @@ use sqlx::Row;
 
 use crate::utils::errors::HttpResult;
 use crate::utils::authz::{authorize, get_tenant_header, AuthzResult, AuthzTypes};
-use crate::utils::tms_utils::{self, sql_substitute_client_constraint, RequestDebug, check_tenant_enabled};
-use crate::utils::db_statements::GET_PUBKEY_TEMPLATE;
+use crate::utils::tms_utils::{self, get_client_constraint, RequestDebug, check_tenant_enabled};
+use crate::utils::db_statements::GET_PUBKEY;
+use crate::utils::query_builder::{TmsQuery, Cmp};
 use crate::utils::db_types::Pubkey;
 use log::error;
 
@@ -51,6 +52,10 @@ pub struct RespGetPubkeys
     expires_at: DateTime<Utc>,
     created: DateTime<Utc>,
     updated: DateTime<Utc>,
+    host_group: Option<String>,
+    hosts: Vec<String>,
+    sk_application: Option<String>,
+    sk_flags: Option<Vec<String>>,
 }
 
 // Implement the debug record trait for logging.
@@ -169,12 +174,16 @@ impl RespGetPubkeys {
         expires_at: DateTime<Utc>,
         created: DateTime<Utc>,
         updated: DateTime<Utc>,
+        host_group: Option<String>,
+        hosts: Vec<String>,
+        sk_application: Option<String>,
+        sk_flags: Option<Vec<String>>,
     ) 
     -> Self {
             Self {result_code: result_code.to_string(), result_msg, 
                   id, tenant, client_id, client_user_id, host, host_account, public_key_fingerprint, 
                   public_key, key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes, 
-                  expires_at, created, updated}
+                  expires_at, created, updated, host_group, hosts, sk_application, sk_flags}
         }
 
     /// Process the request.
@@ -190,7 +199,8 @@ impl RespGetPubkeys {
                     pubkey.tenant, pubkey.client_id,pubkey.client_user_id, pubkey.host, pubkey.host_account, 
                     pubkey.public_key_fingerprint, pubkey.public_key, pubkey.key_type, pubkey.key_bits, 
                     pubkey.max_uses, pubkey.remaining_uses, pubkey.initial_ttl_minutes, pubkey.expires_at, 
-                    pubkey.created, pubkey.updated))),
+                    pubkey.created, pubkey.updated, pubkey.host_group, pubkey.hosts,
+                    pubkey.sk_application, pubkey.sk_flags))),
             Err(e) => {
                 // Determine if this is a real db error or just record not found.
                 let msg = e.to_string();
@@ -208,18 +218,20 @@ impl RespGetPubkeys {
 // get_pubkey:
 // ---------------------------------------------------------------------------
 async fn get_pubkey(authz_result: &AuthzResult, req: &ReqGetPubkeys) -> Result<Pubkey> {
-    // Substitute the placeholder in the query template.  Uncommited transactions 
+    // Restrict the query to the authorized client when necessary.
+    let client_id = get_client_constraint(authz_result)?;
+
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
-    let sql_query = sql_substitute_client_constraint(GET_PUBKEY_TEMPLATE, authz_result); 
-
-    // Get a connection to the db and start a transaction.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
     // Create the select statement.
-    let result = sqlx::query(&sql_query)
-        .bind(req.seqno)
-        .bind(req.tenant.clone())
+    let mut query = TmsQuery::new(GET_PUBKEY)
+        .and("id", Cmp::Eq, req.seqno)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and_opt("client_id", Cmp::Eq, client_id);
+    let result = query.build()
         .fetch_optional(&mut *tx)
         .await?;
 
@@ -235,7 +247,9 @@ async fn get_pubkey(authz_result: &AuthzResult, req: &ReqGetPubkeys) -> Result<P
                            row.get(7), row.get(8), row.get(9), 
                            row.get(10), row.get(11), 
                            row.get(12), row.get(13), 
-                           row.get(14), row.get(15)))
+                           row.get(14), row.get(15),
+                           row.get(16), row.get(17),
+                           row.get(18), row.get(19)))
         },
         None => {
             Err(anyhow!("NOT_FOUND"))
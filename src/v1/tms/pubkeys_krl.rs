@@ -0,0 +1,255 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Query, ApiResponse };
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::Row;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::{LIST_REVOKED_PUBKEYS, LIST_REVOKED_CERT_SERIALS};
+use crate::utils::krl::{build_krl, sign_krl, KRL_NAMESPACE};
+use crate::utils::ssh_ca::get_tenant_ca;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled, timestamp_utc};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// The default and maximum periods covered by a KRL.
+const DEFAULT_KRL_WINDOW_MINUTES: u32 = 7 * 24 * 60;
+const MAX_KRL_WINDOW_MINUTES: u32 = 366 * 24 * 60;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Get an OpenSSH key revocation list (KRL) of the keys in the caller's tenant
+ * that were deleted or expired in the last window_minutes.  When a host is
+ * specified, only the keys that could be used on that host are revoked.  The
+ * certificates signed for the revoked keys are also revoked by serial number.  Hosts
+ * can only get their own KRL; tenant admins can get any host's KRL or the
+ * tenant-wide KRL.
+ *
+ * The base64 encoded KRL can be installed in the file named by sshd's
+ * RevokedKeys option.  The KRL is signed with the tenant's CA key, which can
+ * be checked with "ssh-keygen -Y verify" using the tms-krl namespace.
+ */
+pub struct GetPubkeysKrlApi;
+
+#[derive(Object)]
+struct ReqGetPubkeysKrl
+{
+    tenant: String,
+    host: Option<String>,
+    window_minutes: Option<u32>,
+}
+
+#[derive(Object, Debug)]
+pub struct RespGetPubkeysKrl
+{
+    result_code: String,
+    result_msg: String,
+    tenant: String,
+    host: Option<String>,
+    window_minutes: u32,
+    generated: DateTime<Utc>,
+    num_revoked: u32,
+    fingerprints: Vec<String>,
+    cert_serials: Vec<i64>,
+    krl: String,            // base64 encoded binary KRL
+    krl_signature: String,  // armored ssh-keygen -Y sign signature
+    signature_namespace: String,
+    ca_public_key_fingerprint: String,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqGetPubkeysKrl {
+    type Req = ReqGetPubkeysKrl;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    host: ");
+        s.push_str(&format!("{:?}", self.host));
+        s.push_str("\n    window_minutes: ");
+        s.push_str(&format!("{:?}", self.window_minutes));
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespGetPubkeysKrl>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespGetPubkeysKrl) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl GetPubkeysKrlApi {
+    #[oai(path = "/tms/pubkeys/krl", method = "get")]
+    async fn get_pubkeys_krl_api(&self, http_req: &Request, host: Query<Option<String>>,
+                                 window_minutes: Query<Option<u32>>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqGetPubkeysKrl {tenant: hdr_tenant, host: host.clone(), window_minutes: *window_minutes};
+
+        // -------------------- Authorize ----------------------------
+        // Tenant admins and registered hosts can get KRLs.
+        let allowed = [AuthzTypes::TenantAdmin, AuthzTypes::TmsctlHost];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to get the key revocation list in tenant {}.", req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // Hosts can only get their own KRL.
+        if authz_result.authz_type == Some(AuthzTypes::TmsctlHost) {
+            let authorized = match &req.host {
+                Some(h) => authz_result.check_hdr_id(h),
+                None => false,
+            };
+            if !authorized {
+                let msg = format!("ERROR: NOT AUTHORIZED - Hosts can only get their own key revocation list in tenant {}.",
+                                          req.tenant);
+                error!("{}", msg);
+                return make_http_401(msg);
+            }
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespGetPubkeysKrl::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespGetPubkeysKrl {
+    /// Create a new response.
+    #[allow(clippy::too_many_arguments)]
+    fn new(result_code: &str, result_msg: String, tenant: String, host: Option<String>, window_minutes: u32,
+           generated: DateTime<Utc>, fingerprints: Vec<String>, cert_serials: Vec<i64>, krl: String,
+           krl_signature: String, ca_public_key_fingerprint: String) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, tenant, host, window_minutes, generated,
+              num_revoked: fingerprints.len() as u32, fingerprints, cert_serials, krl, krl_signature,
+              signature_namespace: KRL_NAMESPACE.to_string(), ca_public_key_fingerprint}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqGetPubkeysKrl) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Validate the window.
+        let window_minutes = req.window_minutes.unwrap_or(DEFAULT_KRL_WINDOW_MINUTES);
+        if !(1..=MAX_KRL_WINDOW_MINUTES).contains(&window_minutes) {
+            let msg = format!("ERROR: window_minutes must be between 1 and {}.", MAX_KRL_WINDOW_MINUTES);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
+        // Get the revoked keys and certificates.
+        let generated = timestamp_utc();
+        let since = generated - TimeDelta::minutes(window_minutes as i64);
+        let (fingerprints, cert_serials) = list_revoked_pubkeys(req, since, generated).await?;
+
+        // Build and sign the KRL.
+        let comment = match &req.host {
+            Some(h) => format!("TMS tenant {} host {}", req.tenant, h),
+            None => format!("TMS tenant {}", req.tenant),
+        };
+        let ca = get_tenant_ca(&req.tenant).await?;
+        let krl = build_krl(&fingerprints, &cert_serials, &ca.public_key, generated, &comment)?;
+        let krl_signature = sign_krl(&ca, &krl)?;
+        info!("Key revocation list with {} keys and {} certificates generated for {}.", 
+              fingerprints.len(), cert_serials.len(), comment);
+
+        Ok(make_http_200(Self::new("0", "success".to_string(), req.tenant.clone(), req.host.clone(),
+                                   window_minutes, generated, fingerprints, cert_serials, BASE64.encode(&krl),
+                                   krl_signature, ca.public_key_fingerprint)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// list_revoked_pubkeys:
+// ---------------------------------------------------------------------------
+async fn list_revoked_pubkeys(req: &ReqGetPubkeysKrl, since: DateTime<Utc>, now: DateTime<Utc>) 
+    -> Result<(Vec<String>, Vec<i64>)> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Get the fingerprints in order.
+    let rows = sqlx::query(LIST_REVOKED_PUBKEYS)
+        .bind(&req.tenant)
+        .bind(since)
+        .bind(now)
+        .bind(&req.host)
+        .fetch_all(&mut *tx)
+        .await?;
+    let fingerprints = rows.iter().map(|row| row.get(0)).collect();
+
+    // Get the certificate serial numbers in order.
+    let rows = sqlx::query(LIST_REVOKED_CERT_SERIALS)
+        .bind(&req.tenant)
+        .bind(since)
+        .bind(now)
+        .bind(&req.host)
+        .fetch_all(&mut *tx)
+        .await?;
+    let cert_serials = rows.iter().map(|row| row.get(0)).collect();
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok((fingerprints, cert_serials))
+}
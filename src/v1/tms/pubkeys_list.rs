@@ -1,15 +1,17 @@
 #![forbid(unsafe_code)]
 
 use poem::Request;
-use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use poem_openapi::{ OpenApi, payload::Json, param::Query, Object, ApiResponse };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use sqlx::Row;
 
 use crate::utils::errors::HttpResult;
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, AuthzResult};
-use crate::utils::db_statements::LIST_PUBKEYS_TEMPLATE;
-use crate::utils::tms_utils::{self, RequestDebug, sql_substitute_client_constraint, check_tenant_enabled};
+use crate::utils::db_statements::LIST_PUBKEYS;
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms, parse_status_filter, get_client_constraint,
+                              get_user_constraint, check_tenant_enabled};
 use log::error;
 
 use crate::RUNTIME_CTX;
@@ -26,6 +28,13 @@ pub struct ListPubkeysApi;
 struct ReqListPubkeys
 {
     tenant: String,
+    client_user_id: Option<String>,
+    host: Option<String>,
+    status: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -35,6 +44,7 @@ pub struct RespListPubkeys
     result_msg: String,
     num_pubkeys: i32,
     pubkeys: Vec<PubkeysListElement>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -56,6 +66,10 @@ pub struct PubkeysListElement
     expires_at: DateTime<Utc>,
     created: DateTime<Utc>,
     updated: DateTime<Utc>,
+    host_group: Option<String>,
+    hosts: Vec<String>,
+    sk_application: Option<String>,
+    sk_flags: Option<Vec<String>>,
 }
 
 // Implement the debug record trait for logging.
@@ -66,6 +80,20 @@ impl RequestDebug for ReqListPubkeys {
         s.push_str("  Request body:");
         s.push_str("\n    tenant: ");
         s.push_str(&self.tenant);
+        s.push_str("\n    client_user_id: ");
+        s.push_str(&format!("{:?}", self.client_user_id));
+        s.push_str("\n    host: ");
+        s.push_str(&format!("{:?}", self.host));
+        s.push_str("\n    status: ");
+        s.push_str(&format!("{:?}", self.status));
+        s.push_str("\n    created_after: ");
+        s.push_str(&format!("{:?}", self.created_after));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
         s
     }
 }
@@ -102,7 +130,13 @@ fn make_http_500(msg: String) -> TmsResponse {
 #[OpenApi]
 impl ListPubkeysApi {
     #[oai(path = "/tms/pubkeys/list", method = "get")]
-    async fn get_pubkeys(&self, http_req: &Request) -> TmsResponse {
+    #[allow(clippy::too_many_arguments)]
+    async fn get_pubkeys(&self, http_req: &Request, 
+                                client_user_id: Query<Option<String>>, host: Query<Option<String>>,
+                                status: Query<Option<String>>, created_after: Query<Option<DateTime<Utc>>>,
+                                limit: Query<Option<u32>>, cursor: Query<Option<String>>,
+                                sort: Query<Option<String>>) 
+        -> TmsResponse {
         // -------------------- Get Tenant Header --------------------
         // Get the required tenant header value.  
         let hdr_tenant = match get_tenant_header(http_req) {
@@ -116,12 +150,15 @@ impl ListPubkeysApi {
         }
 
         // Package the request parameters.        
-        let req = ReqListPubkeys {tenant: hdr_tenant};
+        let req = ReqListPubkeys {tenant: hdr_tenant, client_user_id: client_user_id.clone(),
+                                  host: host.clone(), status: status.clone(),
+                                  created_after: *created_after, limit: *limit,
+                                  cursor: cursor.clone(), sort: sort.clone()};
         
         // -------------------- Authorize ----------------------------
         // Only the tenant admin can query all client records; 
-        // a client can query their own records.
-        let allowed = [AuthzTypes::ClientOwn, AuthzTypes::TenantAdmin];
+        // a client or user can query their own records.
+        let allowed = [AuthzTypes::ClientOwn, AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to list clients in tenant {}.", req.tenant);
@@ -152,19 +189,20 @@ impl PubkeysListElement {
            host: String, host_account: String, public_key_fingerprint: String, 
            public_key: String, key_type: String, key_bits: i32, max_uses: i32,
            remaining_uses: i32, initial_ttl_minutes: i32, expires_at: DateTime<Utc>, 
-           created: DateTime<Utc>, updated: DateTime<Utc>) -> Self {
+           created: DateTime<Utc>, updated: DateTime<Utc>, host_group: Option<String>,
+           hosts: Vec<String>, sk_application: Option<String>, sk_flags: Option<Vec<String>>) -> Self {
         Self {id, tenant, client_id, client_user_id, host, host_account, public_key_fingerprint,
               public_key, key_type, key_bits, max_uses, remaining_uses, initial_ttl_minutes,
-              expires_at, created, updated}
+              expires_at, created, updated, host_group, hosts, sk_application, sk_flags}
     }
 }
 
 impl RespListPubkeys {
     /// Create a new response.
     #[allow(clippy::too_many_arguments)]
-    fn new(result_code: &str, result_msg: String, num_keys: i32, keys: Vec<PubkeysListElement>) 
+    fn new(result_code: &str, result_msg: String, num_keys: i32, keys: Vec<PubkeysListElement>, next_cursor: Option<String>) 
     -> Self {
-        Self {result_code: result_code.to_string(), result_msg, num_pubkeys: num_keys, pubkeys: keys}
+        Self {result_code: result_code.to_string(), result_msg, num_pubkeys: num_keys, pubkeys: keys, next_cursor}
         }
 
     /// Process the request.
@@ -172,9 +210,20 @@ impl RespListPubkeys {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Validate the paging and filter parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+        let active = match parse_status_filter(&req.status) {
+            Ok(a) => a,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
         // Search for the tenant/client ids in the database.  
-        let keys = list_pubkeys(authz_result, req).await?;
-        Ok(make_http_200(Self::new("0", "success".to_string(), keys.len() as i32, keys)))
+        let mut keys = list_pubkeys(authz_result, req, &parms, active).await?;
+        let next_cursor = parms.paginate(&mut keys, |e| e.id);
+        Ok(make_http_200(Self::new("0", "success".to_string(), keys.len() as i32, keys, next_cursor)))
     }
 }
 
@@ -184,9 +233,10 @@ impl RespListPubkeys {
 // ---------------------------------------------------------------------------
 // list_pubkeys:
 // ---------------------------------------------------------------------------
-async fn list_pubkeys(authz_result: &AuthzResult, req: &ReqListPubkeys) -> Result<Vec<PubkeysListElement>> {
-    // Substitute the placeholder in the query template.
-    let sql_query = sql_substitute_client_constraint(LIST_PUBKEYS_TEMPLATE, authz_result); 
+async fn list_pubkeys(authz_result: &AuthzResult, req: &ReqListPubkeys, parms: &ListParms, active: Option<bool>) -> Result<Vec<PubkeysListElement>> {
+    // Restrict the query to the authorized client or user when necessary.
+    let client_id = get_client_constraint(authz_result)?;
+    let user_id = get_user_constraint(authz_result)?;
     
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
@@ -194,8 +244,16 @@ async fn list_pubkeys(authz_result: &AuthzResult, req: &ReqListPubkeys) -> Resul
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
     // Create the select statement.
-    let rows = sqlx::query(&sql_query)
-        .bind(req.tenant.clone())
+    let mut query = TmsQuery::new(LIST_PUBKEYS)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and_opt("client_id", Cmp::Eq, client_id)
+        .and_opt("client_user_id", Cmp::Eq, user_id)
+        .and_opt("client_user_id", Cmp::Eq, req.client_user_id.as_ref())
+        .and_opt("host", Cmp::Eq, req.host.as_ref())
+        .and_active(active)
+        .and_opt("created", Cmp::Gt, req.created_after)
+        .paginate(parms);
+    let rows = query.build()
         .fetch_all(&mut *tx)
         .await?;
 
@@ -211,7 +269,9 @@ async fn list_pubkeys(authz_result: &AuthzResult, req: &ReqListPubkeys) -> Resul
             row.get(6), row.get(7), 
             row.get(8), row.get(9),
             row.get(10), row.get(11), row.get(12), 
-            row.get(13), row.get(14), row.get(15));
+            row.get(13), row.get(14), row.get(15),
+            row.get(16), row.get(17),
+            row.get(18), row.get(19));
         element_list.push(elem);
     }
 
@@ -0,0 +1,384 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, get_client_id_header};
+use crate::utils::errors::HttpResult;
+use crate::utils::keygen::{parse_public_key, check_key_allowed, check_sk_flags, KeyType};
+use crate::utils::db_types::{PubkeyInput, SkKeyInfo};
+use crate::utils::db::{check_pubkey_dependencies, check_key_scope, get_tenant_allowed_key_types, insert_pubkey_in_tx};
+use crate::utils::tms_utils::{self, timestamp_utc, calc_expires_at, RequestDebug, check_tenant_enabled};
+use crate::utils::mvp::{MVPDependencyParms, create_pubkey_dependencies};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definitions
+// ***************************************************************************
+/** Register a public key whose private key was generated by the client and
+ * never leaves the user's device.  The key is subject to the same dependency,
+ * use count and expiration rules as keys that TMS generates.  FIDO security
+ * keys (sk-ssh-ed25519@openssh.com and sk-ecdsa-sha2-nistp256@openssh.com) can
+ * only be registered, never generated, and can have flags that are passed to
+ * sshd as authorized_keys options when the key is retrieved.
+ */
+pub struct RegisterPubkeyApi;
+
+#[derive(Object)]
+pub struct ReqRegisterPubkey
+{
+    client_user_id: String,
+    host: String,
+    host_account: String,
+    public_key: String,  // OpenSSH format
+    num_uses: i32,       // negative means i32::MAX
+    ttl_minutes: i32,    // negative means i32::MAX
+    hosts: Option<Vec<String>>,   // additional hosts on which the key can be used
+    host_group: Option<String>,   // host group on whose members the key can be used
+    sk_flags: Option<Vec<String>>,  // security keys only: no-touch-required, verify-required
+}
+
+#[derive(Object, Debug)]
+struct RespRegisterPubkey
+{
+    result_code: String,
+    result_msg: String,
+    public_key_fingerprint: String,
+    key_type: String,
+    key_bits: String,
+    max_uses: String,
+    remaining_uses: String,
+    expires_at: DateTime<Utc>,
+    sk_application: Option<String>,
+    sk_flags: Option<Vec<String>>,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqRegisterPubkey {
+    type Req = ReqRegisterPubkey;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    client_user_id: ");
+        s.push_str(&self.client_user_id);
+        s.push_str("\n    host: ");
+        s.push_str(&self.host);
+        s.push_str("\n    host_account: ");
+        s.push_str(&self.host_account);
+        s.push_str("\n    public_key: ");
+        s.push_str(&self.public_key);
+        s.push_str("\n    num_uses: ");
+        s.push_str(&self.num_uses.to_string());
+        s.push_str("\n    ttl_minutes: ");
+        s.push_str(&self.ttl_minutes.to_string());
+        s.push_str("\n    hosts: ");
+        s.push_str(&format!("{:?}", self.hosts));
+        s.push_str("\n    host_group: ");
+        s.push_str(&format!("{:?}", self.host_group));
+        s.push_str("\n    sk_flags: ");
+        s.push_str(&format!("{:?}", self.sk_flags));
+        s.push('\n');
+        s
+    }
+}
+
+// Extracted header values to complete request input
+#[derive(Debug)]
+struct RegisterPubkeyExtension
+{
+    client_id: String,
+    tenant: String,
+}
+
+impl RegisterPubkeyExtension {
+    fn new(client_id: String, tenant: String,) -> Self
+    { Self {client_id, tenant} }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 201)]
+    Http201(Json<RespRegisterPubkey>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 409)]
+    Http409(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_201(resp: RespRegisterPubkey) -> TmsResponse {
+    TmsResponse::Http201(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_409(msg: String) -> TmsResponse {
+    TmsResponse::Http409(Json(HttpResult::new(409.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl RegisterPubkeyApi {
+    #[oai(path = "/tms/pubkeys/register", method = "post")]
+    async fn register_pubkey(&self, http_req: &Request, req: Json<ReqRegisterPubkey>) -> TmsResponse {
+        match RespRegisterPubkey::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                // Assume a server fault if a raw error came through.
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespRegisterPubkey {
+    #[allow(clippy::too_many_arguments)]
+    fn new(result_code: &str, result_msg: &str, public_key_fingerprint: String, key_type: String,
+           key_bits: String, max_uses: String, remaining_uses: String, expires_at: DateTime<Utc>,
+           sk_application: Option<String>, sk_flags: Option<Vec<String>>) -> Self {
+        Self {result_code: result_code.to_string(),
+              result_msg: result_msg.to_string(),
+              public_key_fingerprint, key_type, key_bits, max_uses, remaining_uses, expires_at,
+              sk_application, sk_flags,
+            }
+    }
+
+    async fn process(http_req: &Request, req: &ReqRegisterPubkey) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // -------------------- Extract Headers ----------------------
+        // Get the headers used in this function.
+        let req_ext = match get_header_values(http_req) {
+            Ok(h) => h,
+            Err(e) => {
+                return Ok(make_http_400(e.to_string()));
+            }
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&req_ext.tenant).await {
+            return Ok(make_http_400("Tenant not enabled.".to_string()));
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only the client can register keys for its users.
+        let allowed = [AuthzTypes::ClientOwn];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED Credential mismatch for client {} in tenant {}.",
+                                      req_ext.client_id, req_ext.tenant);
+            error!("{}", msg);
+            return Ok(make_http_401(msg));
+        }
+
+        // -------------------- Validate Key -------------------------
+        // Parse the key and reject unsupported algorithms and weak keys.
+        let keyinfo = match parse_public_key(&req.public_key) {
+            Ok(k) => k,
+            Err(e) => {
+                let msg = format!("ERROR: Invalid public key: {}", e);
+                error!("{}", msg);
+                return Ok(make_http_400(msg));
+            }
+        };
+
+        // Reject key types that are not allowed in the tenant and flags on keys 
+        // that are not security keys.
+        let allowed_key_types = get_tenant_allowed_key_types(&req_ext.tenant).await?;
+        let sk_flags = keyinfo.key_type.parse::<KeyType>()
+            .and_then(|key_type| {
+                check_key_allowed(&allowed_key_types, key_type, keyinfo.key_bits)?;
+                check_sk_flags(key_type, &req.sk_flags)
+            });
+        let sk_flags = match sk_flags {
+            Ok(f) => f,
+            Err(e) => {
+                let msg = format!("ERROR: {}", e);
+                error!("{}", msg);
+                return Ok(make_http_400(msg));
+            }
+        };
+        let sk = keyinfo.sk_application.clone()
+            .map(|application| SkKeyInfo {application, flags: sk_flags});
+
+        // The key can also be used on the hosts in its scope.
+        let scope = match check_key_scope(&req_ext.tenant, &req.host, &req.hosts, &req.host_group).await {
+            Ok(s) => s,
+            Err(e) => {
+                let msg = format!("ERROR: {}", e);
+                error!("{}", msg);
+                if msg.contains("BAD_REQUEST:") {return Ok(make_http_400(msg));}
+                else {return Ok(make_http_500(msg));}
+            }
+        };
+
+        // -------------------- MVP Execution ------------------------
+        // Determine if we are running in minimal viable product mode.
+        if RUNTIME_CTX.parms.config.enable_mvp {
+            // Collect values required for dependency record insertions.
+            let mvp_inputs = MVPDependencyParms {
+                tenant: req_ext.tenant.clone(), client_id: req_ext.client_id.clone(),
+                client_user_id: req.client_user_id.clone(), host: req.host.clone(),
+                host_account: req.host_account.clone(),
+            };
+
+            // Insert records into the user_mfa, user_hosts and delegations tables
+            // that the registered key depends on.
+            match create_pubkey_dependencies(mvp_inputs).await {
+                Ok(inserts) => info!("{} MVP dependency records inserted.", inserts),
+                Err(e) => {
+                    let msg = format!("MVP ERROR: Unable to create MVP dependencies: {}", e);
+                    error!("{}", msg);
+                    return Ok(make_http_500(msg));
+                }
+            };
+        }
+
+        // --------------------- Check Expirations -----------------------
+        // The user_mfa, delegations and user_hosts records that authorize the
+        // key's use must exist and not be expired, just as when TMS generates
+        // the key.  See pubkeys_create.rs for details.
+        match check_pubkey_dependencies(&req_ext.tenant, &req_ext.client_id,
+                                        &req.client_user_id, &req.host, &req.host_account).await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                let msg = format!("Missing or expired dependency: {}", e);
+                error!("{}", msg);
+                if msg.contains("INTERNAL ERROR:") {return Ok(make_http_500(msg));}
+                else {return Ok(make_http_403(msg));}
+            }
+        };
+
+        // ------------------------ Update Database --------------------
+        // Interpret numeric input.
+        let max_uses = if req.num_uses < 0 {i32::MAX} else {req.num_uses};
+        let ttl_minutes = if req.ttl_minutes < 0 {i32::MAX} else {req.ttl_minutes};
+
+        // Use the same current UTC timestamp in all related time calculations.
+        let now  = timestamp_utc();
+        let expires_at  = calc_expires_at(now, req.ttl_minutes);
+        let remaining_uses = max_uses;
+
+        // Create the input record.
+        let input_record = PubkeyInput::new(
+            req_ext.tenant.clone(),
+            req_ext.client_id.clone(),
+            req.client_user_id.clone(),
+            req.host.clone(),
+            req.host_account.clone(),
+            keyinfo.public_key_fingerprint.clone(),
+            keyinfo.public_key.clone(),
+            keyinfo.key_type.clone(),
+            keyinfo.key_bits,
+            max_uses,
+            remaining_uses,
+            ttl_minutes,
+            expires_at,
+            now,
+            now,
+            scope,
+            sk.clone(),
+            None,
+        );
+
+        // Insert the key record unless the key is already registered.
+        match insert_registered_key(input_record).await {
+            Ok(_) => (),
+            Err(e) => {
+                let msg = e.to_string();
+                if msg.contains("DUPLICATE:") {
+                    error!("{}", msg);
+                    return Ok(make_http_409(msg));
+                } else {return Err(e);}
+            }
+        };
+        info!("A key of type '{}' registered for '{}@{}' for host '{}' expires at {} and has {} remaining uses.",
+            keyinfo.key_type, req.client_user_id, req_ext.tenant, req.host, expires_at, remaining_uses);
+
+        // Success!
+        Ok(make_http_201(Self::new("0", "success",
+                    keyinfo.public_key_fingerprint,
+                    keyinfo.key_type,
+                    keyinfo.key_bits.to_string(),
+                    max_uses.to_string(),
+                    remaining_uses.to_string(),
+                    expires_at,
+                    sk.as_ref().map(|k| k.application.clone()),
+                    sk.map(|k| k.flags),)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// insert_registered_key:
+// ---------------------------------------------------------------------------
+/** Insert a client supplied key.  A key whose fingerprint is already in the
+ * pubkeys table is rejected with a "DUPLICATE:" error.  The unique index on
+ * fingerprints rejects the key even when the same key is registered by 
+ * concurrent requests.
+ */
+async fn insert_registered_key(rec: PubkeyInput) -> Result<i32> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Insert the key and the hosts in its scope, rejecting keys that are already registered.
+    let pubkey_id = match insert_pubkey_in_tx(&mut tx, &rec).await {
+        Ok(id) => id,
+        Err(e) => {
+            if let Some(sqlx::Error::Database(db_err)) = e.downcast_ref::<sqlx::Error>() {
+                if db_err.is_unique_violation() {
+                    return Err(anyhow!("DUPLICATE: Public key {} is already registered.", rec.public_key_fingerprint));
+                }
+            }
+            return Err(e);
+        }
+    };
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok(pubkey_id)
+}
+
+// ---------------------------------------------------------------------------
+// get_header_values:
+// ---------------------------------------------------------------------------
+fn get_header_values(http_req: &Request) -> Result<RegisterPubkeyExtension> {
+    // Get the required header values.
+    let hdr_client_id = get_client_id_header(http_req)?;
+    let hdr_tenant = get_tenant_header(http_req)?;
+
+    Ok(RegisterPubkeyExtension::new(hdr_client_id, hdr_tenant))
+}
@@ -1,20 +1,35 @@
 #![forbid(unsafe_code)]
 
+use std::net::IpAddr;
+
 use poem::Request;
 use poem_openapi::{  OpenApi, payload::Json, Object, ApiResponse };
 use anyhow::{anyhow, Result};
 use sqlx::Row;
 
 use crate::utils::errors::HttpResult;
-use crate::utils::db_statements::SELECT_PUBKEY;
+use crate::utils::db_statements::{SELECT_PUBKEY, DECREMENT_REMAINING_USES, GET_HOST_ADDRS};
+use crate::utils::addr_match::any_addr_matches;
 use crate::utils::db_types::PubkeyRetrieval;
-use crate::utils::{tms_utils, tms_utils::RequestDebug};
-use log::error;
+use crate::utils::db::check_pubkey_dependencies_in_tx;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::{tms_utils, tms_utils::{RequestDebug, timestamp_utc, check_tenant_enabled}};
+use log::{error, info};
 use crate::RUNTIME_CTX;
 
 // ***************************************************************************
 //                          Request/Response Definitions
 // ***************************************************************************
+/** Retrieve a public key on behalf of a host.  Callers must authenticate as
+ * the host named in the request using the X-TMS-HOST-ID and X-TMS-HOST-SECRET
+ * headers, and only keys issued for that host in the caller's tenant are
+ * returned.  The request must also originate from an address registered for
+ * the host in the hosts table.
+ * 
+ * The authorized_key field is the line that an sshd AuthorizedKeysCommand
+ * should output.  It's the public key preceded by the key's options, such as
+ * the verify-required flag of a registered security key.
+ */
 pub struct PublicKeyApi;
 
 #[derive(Object)]
@@ -33,6 +48,7 @@ struct RespPublicKey
     result_code: String,
     result_msg: String,
     public_key: String,
+    authorized_key: String,
 }
 
 // Implement the debug record trait for logging.
@@ -69,6 +85,12 @@ impl RequestDebug for ReqPublicKey {
 enum TmsResponse {
     #[oai(status = 200)]
     Http200(Json<RespPublicKey>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
     #[oai(status = 404)]
     Http404(Json<HttpResult>),
     #[oai(status = 500)]
@@ -78,6 +100,15 @@ enum TmsResponse {
 fn make_http_200(resp: RespPublicKey) -> TmsResponse {
     TmsResponse::Http200(Json(resp))
 }
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
 fn make_http_404(msg: String) -> TmsResponse {
     TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
 }
@@ -92,7 +123,64 @@ fn make_http_500(msg: String) -> TmsResponse {
 impl PublicKeyApi {
     #[oai(path = "/tms/pubkeys/creds/retrieve", method = "post")]
     async fn get_public_key(&self, http_req: &Request, req: Json<ReqPublicKey>) -> TmsResponse {
-        match RespPublicKey::process(http_req, &req).await {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only registered hosts can retrieve public keys.
+        let allowed = [AuthzTypes::TmsctlHost];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to retrieve public keys for host {} in tenant {}.", 
+                                      req.host, hdr_tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // Hosts can only retrieve keys issued for themselves.
+        if !authz_result.check_hdr_id(&req.host) {
+            let msg = format!("ERROR: FORBIDDEN - Host {} in the request body differs from the host in the request header.", 
+                                      req.host);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
+        // -------------------- Check Source Address -----------------
+        // The peer address must be registered for the host.
+        let peer_ip = match http_req.remote_addr().as_socket_addr() {
+            Some(a) => a.ip(),
+            None => {
+                let msg = format!("ERROR: FORBIDDEN - Unable to determine the network address of host {}.", req.host);
+                error!("{}", msg);
+                return make_http_403(msg);
+            }
+        };
+        match check_host_addr(&hdr_tenant, &req.host, &peer_ip).await {
+            Ok(true) => (),
+            Ok(false) => {
+                let msg = format!("ERROR: FORBIDDEN - Address {} is not registered for host {} in tenant {}.", 
+                                          peer_ip, req.host, hdr_tenant);
+                error!("{}", msg);
+                return make_http_403(msg);
+            },
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                return make_http_500(msg);
+            },
+        }
+
+        // -------------------- Process Request ----------------------
+        match RespPublicKey::process(http_req, &req, &hdr_tenant).await {
             Ok(r) => r,
             Err(e) => {
                 let msg = "ERROR: ".to_owned() + e.to_string().as_str();
@@ -107,26 +195,38 @@ impl PublicKeyApi {
 //                          Request/Response Methods
 // ***************************************************************************
 impl RespPublicKey {
-    fn new(result_code: &str, result_msg: &str, key: &str) -> Self {
+    fn new(result_code: &str, result_msg: &str, key: &str, authorized_key: String) -> Self {
         Self {result_code: result_code.to_string(), 
               result_msg: result_msg.to_string(), 
-              public_key: key.to_string()}
+              public_key: key.to_string(),
+              authorized_key}
     }
 
-    async fn process(http_req: &Request, req: &ReqPublicKey) -> Result<TmsResponse> {
+    async fn process(http_req: &Request, req: &ReqPublicKey, tenant: &String) -> Result<TmsResponse> {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
-        // Look for the key in the database.
-        let db_result = get_public_key(req).await;
+        // Look for the key in the database, validate it and consume one use.
+        let db_result = get_public_key(req, tenant).await;
         match db_result {
             Ok(result) => {
-                Ok(make_http_200(Self::new("0", "success", result.public_key.as_str())))
+                // Security key flags are authorized_keys options.
+                let authorized_key = match &result.sk_flags {
+                    Some(flags) if !flags.is_empty() => format!("{} {}", flags.join(","), result.public_key),
+                    _ => result.public_key.clone(),
+                };
+                Ok(make_http_200(Self::new("0", "success", result.public_key.as_str(), authorized_key)))
             },
             Err(e) => {
-                // Determine if this is a real db error or just record not found.
+                // Determine if this is a real db error, a record not found or 
+                // a key that cannot be used.
                 let msg = e.to_string();
                 if msg.contains("NOT_FOUND") {Ok(make_http_404(msg))} 
+                  else if msg.contains("EXPIRED:") || msg.contains("NO_REMAINING_USES:") || 
+                          msg.contains("DEPENDENCY:") {
+                    error!("{}", msg);
+                    Ok(make_http_403(msg))
+                  }
                   else {Err(e)}
             },
         }
@@ -139,30 +239,113 @@ impl RespPublicKey {
 // ---------------------------------------------------------------------------
 // get_public_key:
 // ---------------------------------------------------------------------------
-async fn get_public_key(req: &ReqPublicKey) -> Result<PubkeyRetrieval> {
+/** Retrieve a public key on behalf of a host and consume one of its uses.  
+ * 
+ * The pubkey row is locked for the duration of the transaction so that the
+ * expiration check, the remaining uses check and the decrement happen 
+ * atomically with respect to concurrent retrievals of the same key.  The 
+ * key's user_mfa, user_hosts and delegations dependencies are also rechecked
+ * so that revoking any of them immediately prevents the key from being used.
+ * 
+ * Keys created with an unlimited number of uses (i32::MAX) are never 
+ * decremented.
+ * 
+ * Errors that should result in a 403 are prefixed with EXPIRED:, 
+ * NO_REMAINING_USES: or DEPENDENCY:.  A missing key results in a NOT_FOUND
+ * error.  
+ */
+async fn get_public_key(req: &ReqPublicKey, tenant: &String) -> Result<PubkeyRetrieval> {
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
-    // Create the insert statement.
+    // Select and lock the pubkey row.
     let result = sqlx::query(SELECT_PUBKEY)
         .bind(&req.user)
         .bind(&req.host)
         .bind(&req.public_key_fingerprint)
+        .bind(tenant)
         .fetch_optional(&mut *tx)
         .await?;
 
-    // Commit the transaction.
-    tx.commit().await?;
-
     // We found the key!
-    match result {
+    let mut pubkey = match result {
         Some(row) => {
-            Ok(PubkeyRetrieval::new(row.get(0), row.get(1), row.get(2)))
+            PubkeyRetrieval::new(row.get(0), row.get(1), row.get(2), row.get(3), 
+                                 row.get(4), row.get(5), row.get(6), row.get(7), row.get(8))
         },
         None => {
-            Err(anyhow!("NOT_FOUND"))
+            return Err(anyhow!("NOT_FOUND"));
         },
+    };
+
+    // Reject expired keys.
+    let now = timestamp_utc();
+    if pubkey.expires_at < now {
+        return Err(anyhow!("EXPIRED: Pubkey {} for account {} on host {} expired at {}.",
+                           req.public_key_fingerprint, req.user, req.host, pubkey.expires_at));
+    }
+
+    // Reject keys with no remaining uses.
+    if pubkey.remaining_uses < 1 {
+        return Err(anyhow!("NO_REMAINING_USES: Pubkey {} for account {} on host {} has no remaining uses.",
+                           req.public_key_fingerprint, req.user, req.host));
     }
+
+    // Make sure the user's MFA, user/host mapping and client delegation are still active.
+    if let Err(e) = check_pubkey_dependencies_in_tx(&mut tx, &pubkey.tenant, &pubkey.client_id, 
+                                                    &pubkey.client_user_id, &req.host, &req.user).await {
+        // Database failures are not dependency failures and result in a 500.
+        let msg = e.to_string();
+        if e.is::<sqlx::Error>() || msg.contains("INTERNAL ERROR:") {return Err(e);}
+        return Err(anyhow!("DEPENDENCY: Missing or expired dependency: {}", msg));
+    }
+
+    // Consume one use unless the key has unlimited uses.
+    if pubkey.max_uses != i32::MAX {
+        let update = sqlx::query(DECREMENT_REMAINING_USES)
+            .bind(now)
+            .bind(pubkey.id)
+            .execute(&mut *tx)
+            .await?;
+        if update.rows_affected() < 1 {
+            return Err(anyhow!("NO_REMAINING_USES: Pubkey {} for account {} on host {} has no remaining uses.",
+                               req.public_key_fingerprint, req.user, req.host));
+        }
+        pubkey.remaining_uses -= 1;
+    }
+
+    // Commit the transaction.
+    tx.commit().await?;
+    info!("Pubkey {} retrieved for account {} on host {} in tenant {}, {} uses remaining.",
+          req.public_key_fingerprint, req.user, req.host, pubkey.tenant, pubkey.remaining_uses);
+
+    Ok(pubkey)
+}
+
+// ---------------------------------------------------------------------------
+// check_host_addr:
+// ---------------------------------------------------------------------------
+/** Return true if the address matches any of the address patterns registered
+ * for the host in the tenant.  Hosts with no registered addresses never match.
+ */
+async fn check_host_addr(tenant: &String, host: &String, addr: &IpAddr) -> Result<bool> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
+    // are automatically rolled back when they go out of scope. 
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Get all address patterns for the host.
+    let rows = sqlx::query(GET_HOST_ADDRS)
+        .bind(tenant)
+        .bind(host)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    let patterns: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+    Ok(any_addr_matches(&patterns, addr))
 }
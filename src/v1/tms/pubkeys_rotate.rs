@@ -0,0 +1,487 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::{Postgres, Row, Transaction};
+
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, get_client_id_header};
+use crate::utils::errors::HttpResult;
+use crate::utils::keygen::{parse_key_type, get_key_bits, check_key_allowed, GeneratedKeyObj};
+use crate::utils::key_pool;
+use crate::utils::db_types::{KeyScope, PubkeyInput};
+use crate::utils::db_statements::{SELECT_PUBKEY_FOR_ROTATE, UPDATE_ROTATED_PUBKEY};
+use crate::utils::db::{check_pubkey_dependencies_in_tx, get_tenant_allowed_key_types, insert_pubkey_in_tx};
+use crate::utils::tms_utils::{self, timestamp_utc, RequestDebug, check_tenant_enabled};
+use crate::v1::tms::pubkeys_create::{ReqPrivateKeyEncryption, RespPrivateKeyEnvelope, encrypt_private_key,
+                                     validate_private_key_encryption, private_key_encryption_mode};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definitions
+// ***************************************************************************
+// The old key remains usable for this long after rotation when the request
+// does not specify an overlap.
+const DEFAULT_ROTATION_OVERLAP_MINUTES: u32 = 60;
+
+/** Replace an existing key with a newly generated key for the same user, host
+ * and host account.  The new key inherits the old key's scope, maximum uses 
+ * and expiration time, and the old key expires at the end of the overlap period
+ * so that clients can switch to the new key without interruption.  The old key's
+ * remaining uses are split between the two keys so that rotation never increases
+ * the number of times a key can be used.
+ */
+pub struct RotatePubkeyApi;
+
+#[derive(Object)]
+pub struct ReqRotatePubkey
+{
+    host: String,
+    public_key_fingerprint: String,
+    overlap_minutes: Option<u32>,  // default 60, zero expires the old key immediately
+    private_key_encryption: Option<ReqPrivateKeyEncryption>,
+}
+
+#[derive(Object, Debug)]
+struct RespRotatePubkey
+{
+    result_code: String,
+    result_msg: String,
+    private_key: String,
+    public_key: String,
+    public_key_fingerprint: String,
+    key_type: String,
+    key_bits: String,
+    max_uses: String,
+    remaining_uses: String,
+    expires_at: DateTime<Utc>,
+    private_key_envelope: Option<RespPrivateKeyEnvelope>,
+    old_public_key_fingerprint: String,
+    old_remaining_uses: String,
+    old_expires_at: DateTime<Utc>,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqRotatePubkey {
+    type Req = ReqRotatePubkey;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    host: ");
+        s.push_str(&self.host);
+        s.push_str("\n    public_key_fingerprint: ");
+        s.push_str(&self.public_key_fingerprint);
+        s.push_str("\n    overlap_minutes: ");
+        s.push_str(&format!("{:?}", self.overlap_minutes));
+        s.push_str("\n    private_key_encryption: ");
+        s.push_str(private_key_encryption_mode(&self.private_key_encryption));
+        s.push('\n');
+        s
+    }
+}
+
+// Extracted header values to complete request input
+#[derive(Debug)]
+struct RotatePubkeyExtension
+{
+    client_id: String,
+    tenant: String,
+}
+
+impl RotatePubkeyExtension {
+    fn new(client_id: String, tenant: String,) -> Self
+    { Self {client_id, tenant} }
+}
+
+// The old key's values that are copied to the new key.
+#[derive(Debug)]
+struct RotatedKey
+{
+    client_user_id: String,
+    host_account: String,
+    key_type: String,
+    key_bits: i32,
+    max_uses: i32,
+    remaining_uses: i32,
+    initial_ttl_minutes: i32,
+    expires_at: DateTime<Utc>,
+    scope: KeyScope,
+}
+
+// The remaining uses and expiration time assigned to each key by a rotation.
+#[derive(Debug)]
+struct RotationResult
+{
+    old_key: RotatedKey,
+    new_remaining_uses: i32,
+    old_remaining_uses: i32,
+    old_expires_at: DateTime<Utc>,
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 201)]
+    Http201(Json<RespRotatePubkey>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 404)]
+    Http404(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_201(resp: RespRotatePubkey) -> TmsResponse {
+    TmsResponse::Http201(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_404(msg: String) -> TmsResponse {
+    TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl RotatePubkeyApi {
+    #[oai(path = "/tms/pubkeys/rotate", method = "post")]
+    async fn rotate_pubkey(&self, http_req: &Request, req: Json<ReqRotatePubkey>) -> TmsResponse {
+        match RespRotatePubkey::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                // Assume a server fault if a raw error came through.
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespRotatePubkey {
+    #[allow(clippy::too_many_arguments)]
+    fn new(result_code: &str, result_msg: &str, private_key: String, public_key: String,
+           public_key_fingerprint: String, key_type: String, key_bits: String,
+           max_uses: String, remaining_uses: String, expires_at: DateTime<Utc>,
+           private_key_envelope: Option<RespPrivateKeyEnvelope>,
+           old_public_key_fingerprint: String, old_remaining_uses: String,
+           old_expires_at: DateTime<Utc>) -> Self {
+        Self {result_code: result_code.to_string(),
+              result_msg: result_msg.to_string(),
+              private_key, public_key, public_key_fingerprint,
+              key_type, key_bits, max_uses, remaining_uses, expires_at,
+              private_key_envelope, old_public_key_fingerprint, old_remaining_uses, old_expires_at,
+            }
+    }
+
+    async fn process(http_req: &Request, req: &ReqRotatePubkey) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // -------------------- Extract Headers ----------------------
+        // Get the headers used in this function.
+        let req_ext = match get_header_values(http_req) {
+            Ok(h) => h,
+            Err(e) => {
+                return Ok(make_http_400(e.to_string()));
+            }
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&req_ext.tenant).await {
+            return Ok(make_http_400("Tenant not enabled.".to_string()));
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only the client that owns the key can rotate it.
+        let allowed = [AuthzTypes::ClientOwn];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED Credential mismatch for client {} in tenant {}.",
+                                      req_ext.client_id, req_ext.tenant);
+            error!("{}", msg);
+            return Ok(make_http_401(msg));
+        }
+
+        // Exactly one encryption mode must be specified when encryption is requested.
+        if let Err(e) = validate_private_key_encryption(&req.private_key_encryption) {
+            let msg = format!("ERROR: {}", e);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
+        // -------------------- Get Old Key --------------------------
+        // The key is read again and locked when the database is updated.
+        let now = timestamp_utc();
+        let old_key = match get_rotated_key(&req_ext, req).await {
+            Ok(k) => k,
+            Err(e) => {
+                let msg = format!("ERROR: {}", e);
+                error!("{}", msg);
+                if msg.contains("NOT_FOUND") {return Ok(make_http_404(msg));}
+                else {return Ok(make_http_500(msg));}
+            }
+        };
+        if old_key.expires_at <= now {
+            let msg = format!("ERROR: Key {} for host {} in tenant {} has expired and cannot be rotated.",
+                              req.public_key_fingerprint, req.host, req_ext.tenant);
+            error!("{}", msg);
+            return Ok(make_http_403(msg));
+        }
+
+        // -------------------- Validate Key Type ---------------------
+        // The new key has the old key's type.  Keys of lengths that TMS does not
+        // generate, such as some registered rsa keys, are replaced by keys of the
+        // default length.  The tenant's current key type policy applies.
+        let allowed_key_types = get_tenant_allowed_key_types(&req_ext.tenant).await?;
+        let key_spec = parse_key_type(&Some(old_key.key_type.clone()))
+            .and_then(|key_type| {
+                let key_bits = get_key_bits(key_type, Some(old_key.key_bits))
+                    .or_else(|_| get_key_bits(key_type, None))?;
+                Ok((key_type, key_bits))
+            })
+            .and_then(|(key_type, key_bits)| {
+                check_key_allowed(&allowed_key_types, key_type, key_bits)?;
+                Ok((key_type, key_bits))
+            });
+        let (key_type, key_bits) = match key_spec {
+            Ok(spec) => spec,
+            Err(e) => {
+                let msg = format!("ERROR: Unable to rotate key {}: {}", req.public_key_fingerprint, e);
+                error!("{}", msg);
+                return Ok(make_http_400(msg));
+            }
+        };
+
+        // ------------------------ Generate Keys ------------------------
+        // Generate the new key pair.
+        let keyinfo = match key_pool::get_key(key_type, key_bits).await {
+            Ok(k) => k,
+            Err(e) => {
+                return Result::Err(anyhow!(e));
+            }
+        };
+
+        // ------------------------ Encrypt Private Key ------------------
+        // The private key is encrypted before the key is saved so that no key is
+        // created when encryption fails.  The plaintext private key is discarded.
+        let (private_key, private_key_envelope) = match encrypt_private_key(&keyinfo.private_key,
+                                                                            &req.private_key_encryption).await {
+            Ok(k) => k,
+            Err(e) => {
+                let msg = format!("ERROR: Unable to encrypt private key: {}", e);
+                error!("{}", msg);
+                if msg.contains("BAD_REQUEST:") {return Ok(make_http_400(msg));}
+                else {return Ok(make_http_500(msg));}
+            }
+        };
+
+        // ------------------------ Update Database --------------------
+        // The old key's expiration can only move earlier.
+        let overlap = req.overlap_minutes.unwrap_or(DEFAULT_ROTATION_OVERLAP_MINUTES);
+        let overlap_expires_at = now + TimeDelta::minutes(overlap as i64);
+        let rotation = match rotate_key(&req_ext, req, &keyinfo, overlap_expires_at, now).await {
+            Ok(k) => k,
+            Err(e) => {
+                let msg = format!("ERROR: Unable to rotate key {}: {}", req.public_key_fingerprint, e);
+                error!("{}", msg);
+                if msg.contains("NOT_FOUND") {return Ok(make_http_404(msg));}
+                else if msg.contains("INTERNAL ERROR:") {return Ok(make_http_500(msg));}
+                else if msg.contains("EXPIRED:") || msg.contains("Missing or expired dependency:") {
+                    return Ok(make_http_403(msg));
+                }
+                else {return Ok(make_http_500(msg));}
+            }
+        };
+        info!("Key '{}' for '{}@{}' for host '{}' rotated to a key of type '{}' that expires at {} and has {} remaining uses. The old key expires at {} and has {} remaining uses.",
+            req.public_key_fingerprint, rotation.old_key.client_user_id, req_ext.tenant, req.host, keyinfo.key_type,
+            rotation.old_key.expires_at, rotation.new_remaining_uses, rotation.old_expires_at,
+            rotation.old_remaining_uses);
+
+        // Success!
+        Ok(make_http_201(Self::new("0", "success",
+                    private_key,
+                    keyinfo.public_key,
+                    keyinfo.public_key_fingerprint,
+                    keyinfo.key_type,
+                    keyinfo.key_bits.to_string(),
+                    rotation.old_key.max_uses.to_string(),
+                    rotation.new_remaining_uses.to_string(),
+                    rotation.old_key.expires_at,
+                    private_key_envelope,
+                    req.public_key_fingerprint.clone(),
+                    rotation.old_remaining_uses.to_string(),
+                    rotation.old_expires_at,)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// get_rotated_key:
+// ---------------------------------------------------------------------------
+async fn get_rotated_key(req_ext: &RotatePubkeyExtension, req: &ReqRotatePubkey) -> Result<RotatedKey> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+    let key = select_rotated_key(&mut tx, req_ext, req).await?;
+    tx.commit().await?;
+    Ok(key)
+}
+
+// ---------------------------------------------------------------------------
+// select_rotated_key:
+// ---------------------------------------------------------------------------
+/** Read and lock the key being rotated. */
+async fn select_rotated_key(tx: &mut Transaction<'_, Postgres>,
+                            req_ext: &RotatePubkeyExtension, req: &ReqRotatePubkey)
+    -> Result<RotatedKey> {
+    let result = sqlx::query(SELECT_PUBKEY_FOR_ROTATE)
+        .bind(&req_ext.client_id)
+        .bind(&req_ext.tenant)
+        .bind(&req.host)
+        .bind(&req.public_key_fingerprint)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    match result {
+        Some(row) => Ok(RotatedKey {
+            client_user_id: row.get(0),
+            host_account: row.get(1),
+            key_type: row.get(2),
+            key_bits: row.get(3),
+            max_uses: row.get(4),
+            remaining_uses: row.get(5),
+            initial_ttl_minutes: row.get(6),
+            expires_at: row.get(7),
+            scope: KeyScope {host_group: row.get(8), hosts: row.get(9)},
+        }),
+        None => Err(anyhow!("NOT_FOUND: Key {} for host {} not found for client {} in tenant {}.",
+                            req.public_key_fingerprint, req.host, req_ext.client_id, req_ext.tenant)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// rotate_key:
+// ---------------------------------------------------------------------------
+/** Insert the new key with the old key's maximum uses and expiration time, and
+ * shorten the old key's lifetime to the overlap period, in one transaction.  The
+ * old key's values and the uses and expiration time of both keys are returned.
+ */
+async fn rotate_key(req_ext: &RotatePubkeyExtension, req: &ReqRotatePubkey, keyinfo: &GeneratedKeyObj,
+                    overlap_expires_at: DateTime<Utc>, now: DateTime<Utc>)
+    -> Result<RotationResult> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions
+    // are automatically rolled back when they go out of scope.
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Lock the old key, which may have changed since it was first read.
+    let old_key = select_rotated_key(&mut tx, req_ext, req).await?;
+    if old_key.expires_at <= now {
+        return Err(anyhow!("EXPIRED: Key {} expired at {}.", req.public_key_fingerprint, old_key.expires_at));
+    }
+    if old_key.remaining_uses < 1 {
+        return Err(anyhow!("EXPIRED: Key {} has no remaining uses.", req.public_key_fingerprint));
+    }
+
+    // The new key requires the same unexpired dependencies as the old key.
+    if let Err(e) = check_pubkey_dependencies_in_tx(&mut tx, &req_ext.tenant, &req_ext.client_id,
+                                                    &old_key.client_user_id, &req.host,
+                                                    &old_key.host_account).await {
+        return Err(anyhow!("Missing or expired dependency: {}", e));
+    }
+
+    // Split the remaining uses between the keys.
+    let old_expires_at = old_key.expires_at.min(overlap_expires_at);
+    let (new_remaining_uses, old_remaining_uses) =
+        split_remaining_uses(old_key.remaining_uses, old_expires_at > now);
+
+    // Insert the new key with the old key's scope.
+    let input_record = PubkeyInput::new(
+        req_ext.tenant.clone(),
+        req_ext.client_id.clone(),
+        old_key.client_user_id.clone(),
+        req.host.clone(),
+        old_key.host_account.clone(),
+        keyinfo.public_key_fingerprint.clone(),
+        keyinfo.public_key.clone(),
+        keyinfo.key_type.clone(),
+        keyinfo.key_bits,
+        old_key.max_uses,
+        new_remaining_uses,
+        old_key.initial_ttl_minutes,
+        old_key.expires_at,
+        now,
+        now,
+        old_key.scope.clone(),
+        None,
+        None,
+    );
+    insert_pubkey_in_tx(&mut tx, &input_record).await?;
+
+    // Expire the old key at the end of the overlap period.
+    sqlx::query(UPDATE_ROTATED_PUBKEY)
+        .bind(old_expires_at)
+        .bind(old_remaining_uses)
+        .bind(now)
+        .bind(&req_ext.client_id)
+        .bind(&req_ext.tenant)
+        .bind(&req.host)
+        .bind(&req.public_key_fingerprint)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok(RotationResult {old_key, new_remaining_uses, old_remaining_uses, old_expires_at})
+}
+
+// ---------------------------------------------------------------------------
+// split_remaining_uses:
+// ---------------------------------------------------------------------------
+/** Divide a key's remaining uses between the new key and the old key, returned
+ * in that order.  The old key keeps at most half of the uses for the overlap
+ * period and none when there is no overlap, so the two keys together never have
+ * more uses than the old key had.  Unlimited keys stay unlimited.
+ */
+fn split_remaining_uses(remaining_uses: i32, overlap: bool) -> (i32, i32) {
+    if remaining_uses == i32::MAX {return (i32::MAX, i32::MAX);}
+    let old_uses = if overlap {remaining_uses / 2} else {0};
+    (remaining_uses - old_uses, old_uses)
+}
+
+// ---------------------------------------------------------------------------
+// get_header_values:
+// ---------------------------------------------------------------------------
+fn get_header_values(http_req: &Request) -> Result<RotatePubkeyExtension> {
+    // Get the required header values.
+    let hdr_client_id = get_client_id_header(http_req)?;
+    let hdr_tenant = get_tenant_header(http_req)?;
+
+    Ok(RotatePubkeyExtension::new(hdr_client_id, hdr_tenant))
+}
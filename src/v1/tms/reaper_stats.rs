@@ -0,0 +1,158 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+use crate::utils::errors::HttpResult;
+
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::reaper::get_reaper_stats;
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use log::error;
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Report the number of expired records that the reaper removed from each table
+ * in the caller's tenant since the server started.
+ */
+pub struct GetReaperStatsApi;
+
+#[derive(Object)]
+struct ReqGetReaperStats
+{
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespGetReaperStats
+{
+    result_code: String,
+    result_msg: String,
+    enabled: bool,
+    archive: bool,
+    runs: u64,
+    last_run: Option<DateTime<Utc>>,
+    tables: Vec<ReaperStatsElement>,
+}
+
+#[derive(Object, Debug)]
+pub struct ReaperStatsElement
+{
+    table: String,
+    reaped: u64,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqGetReaperStats {
+    type Req = ReqGetReaperStats;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespGetReaperStats>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespGetReaperStats) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl GetReaperStatsApi {
+    #[oai(path = "/tms/reaper/stats", method = "get")]
+    async fn get_reaper_stats_api(&self, http_req: &Request) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqGetReaperStats {tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can view the reaper statistics.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to view reaper statistics in tenant {}.", req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespGetReaperStats::process(http_req, &req) {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespGetReaperStats {
+    /// Create a new response.
+    #[allow(clippy::too_many_arguments)]
+    fn new(result_code: &str, result_msg: String, enabled: bool, archive: bool, runs: u64,
+           last_run: Option<DateTime<Utc>>, tables: Vec<ReaperStatsElement>) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, enabled, archive, runs, last_run, tables}
+    }
+
+    /// Process the request.
+    fn process(http_req: &Request, req: &ReqGetReaperStats) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // The statistics are kept in memory.
+        let config = &RUNTIME_CTX.parms.config.reaper;
+        let stats = get_reaper_stats(&req.tenant);
+        let tables = stats.reaped.into_iter()
+            .map(|(table, reaped)| ReaperStatsElement {table: table.to_string(), reaped})
+            .collect();
+        Ok(make_http_200(Self::new("0", "success".to_string(), config.enabled, config.archive,
+                                   stats.runs, stats.last_run, tables)))
+    }
+}
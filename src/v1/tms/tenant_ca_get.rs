@@ -0,0 +1,122 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::ssh_ca::get_tenant_ca;
+use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use log::error;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Get the public key of a tenant's certificate authority.  Hosts that list
+ * this key in sshd's TrustedUserCAKeys file accept the user certificates that
+ * TMS issues in the tenant.
+ */
+pub struct GetTenantCaApi;
+
+#[derive(Object)]
+struct ReqGetTenantCa
+{
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespGetTenantCa
+{
+    result_code: String,
+    result_msg: String,
+    tenant: String,
+    ca_public_key: String,
+    ca_public_key_fingerprint: String,
+    key_type: String,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqGetTenantCa {
+    type Req = ReqGetTenantCa;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespGetTenantCa>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespGetTenantCa) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl GetTenantCaApi {
+    #[oai(path = "/tms/tenants/ca/:tenant", method = "get")]
+    async fn get_tenant_ca_api(&self, http_req: &Request, tenant: Path<String>) -> TmsResponse {
+        // Check tenant.  Like other tenant information, the CA public key
+        // is available without authentication.
+        if !check_tenant_enabled(&tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqGetTenantCa {tenant: tenant.to_string()};
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespGetTenantCa::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespGetTenantCa {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, tenant: String, ca_public_key: String,
+           ca_public_key_fingerprint: String, key_type: String) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, tenant,
+              ca_public_key, ca_public_key_fingerprint, key_type}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqGetTenantCa) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // The CA is created on first use for tenants that predate CAs.
+        // The CA private key is never part of the response.
+        let ca = get_tenant_ca(&req.tenant).await?;
+        Ok(make_http_200(Self::new("0", "success".to_string(), req.tenant.clone(),
+                                   ca.public_key, ca.public_key_fingerprint, ca.key_type)))
+    }
+}
@@ -7,8 +7,9 @@ use anyhow::Result;
 use crate::utils::errors::HttpResult;
 use crate::utils::db_statements::{INSERT_TENANT, INSERT_ADMIN};
 use crate::utils::db_types::TenantInput;
+use crate::utils::ssh_ca::insert_tenant_ca;
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header}; 
-use crate::utils::tms_utils::{self, timestamp_utc, timestamp_utc_to_str, create_hex_secret, hash_hex_secret, 
+use crate::utils::tms_utils::{self, timestamp_utc, timestamp_utc_to_str, create_hex_secret, hash_secret, 
                               RequestDebug, check_tenant_enabled};
 use crate::utils::config::{DEFAULT_TENANT, DEFAULT_ADMIN_ID, PERM_ADMIN, DB_TRUE};
 use log::{error, info};
@@ -99,7 +100,7 @@ impl CreateTenantsApi {
 
         // Check that the tenant specified in the header is the default tenant.
         if hdr_tenant != DEFAULT_TENANT {
-            let msg = "ERROR: FORBIDDEN - Only admin users in the 'default' tenant can create new tenants.".to_string();
+            let msg = "ERROR: FORBIDDEN - Only the site admin in the 'default' tenant can create new tenants.".to_string();
             error!("{}", msg);
             return make_http_403(msg);  
         }
@@ -113,10 +114,8 @@ impl CreateTenantsApi {
         let req = ReqCreateTenants {tenant: tenant.to_string()};
 
         // -------------------- Authorize ----------------------------
-        // Currently, only the tenant admin can create a user mfa record.
-        // When user authentication is implemented, we'll add user-own 
-        // authorization and any additional validation.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the site admin can create tenants.
+        let allowed = [AuthzTypes::SiteAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = "ERROR: NOT AUTHORIZED to add a new tenant.".to_string();
@@ -156,7 +155,7 @@ impl RespCreateTenants {
 
         // Generate the admin user's secret.
         let key_str = create_hex_secret();
-        let key_hash = hash_hex_secret(&key_str);
+        let key_hash = hash_secret(&key_str).await?;
     
         // Create the input record.  Note that we save the hash of
         // the hex secret, but never the secret itself.  
@@ -210,6 +209,9 @@ async fn insert_tenant(rec: TenantInput) -> Result<u64> {
         .execute(&mut *tx)
         .await?;
 
+    // Create the new tenant's certificate authority.
+    insert_tenant_ca(&mut tx, &rec.tenant, rec.created).await?;
+
     // Commit the transaction.
     tx.commit().await?;
 
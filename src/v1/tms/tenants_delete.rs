@@ -5,9 +5,10 @@ use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
 use anyhow::Result;
 
 use crate::utils::errors::HttpResult;
-use crate::utils::db_statements::{DELETE_TENANT, DELETE_ADMINS_FOR_TENANT};
+use crate::utils::db_statements::{DELETE_TENANT, DELETE_ADMINS_FOR_TENANT, DELETE_TENANT_CA_FOR_TENANT};
 use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
-use crate::utils::authz::{authorize, get_tenant_header, AuthzTypes, X_TMS_TENANT};
+use crate::utils::authz::{authorize, get_tenant_header, AuthzTypes};
+use crate::utils::config::DEFAULT_TENANT;
 use log::{error, info};
 
 use crate::RUNTIME_CTX;
@@ -91,10 +92,16 @@ impl DeleteTenantsApi {
             Err(e) => return make_http_400(e.to_string()),
         };
         
-        // Check that the tenant specified in the header is the same as the one in the request body.
-        if hdr_tenant != *tenant {
-            let msg = format!("ERROR: FORBIDDEN - The tenant in the {} header ({}) does not match the tenant in the request body ({})", 
-                                      X_TMS_TENANT, hdr_tenant, *tenant);
+        // Check that the tenant specified in the header is the default tenant.
+        if hdr_tenant != DEFAULT_TENANT {
+            let msg = format!("ERROR: FORBIDDEN - Only the site admin in the '{}' tenant can delete tenants.", DEFAULT_TENANT);
+            error!("{}", msg);
+            return make_http_403(msg);  
+        }
+
+        // The default tenant contains the site admin and is never removed.
+        if *tenant == DEFAULT_TENANT {
+            let msg = format!("ERROR: FORBIDDEN - The '{}' tenant cannot be deleted.", DEFAULT_TENANT);
             error!("{}", msg);
             return make_http_403(msg);  
         }
@@ -105,13 +112,11 @@ impl DeleteTenantsApi {
         }
 
         // Package the request parameters.
-        let req = ReqDeleteTenants { tenant: hdr_tenant};
+        let req = ReqDeleteTenants { tenant: tenant.to_string()};
 
         // -------------------- Authorize ----------------------------
-        // Currently, only the tenant admin can delete a user mfa record.
-        // When user authentication is implemented, we'll add user-own 
-        // authorization and any additional validation.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the site admin can delete tenants.
+        let allowed = [AuthzTypes::SiteAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to delete tenant {}.", req.tenant);
@@ -163,8 +168,8 @@ impl RespDeleteTenants {
 // ---------------------------------------------------------------------------
 // delete_tenant:
 // ---------------------------------------------------------------------------
-/** Delete a tenant and its admin users.  The following tables also define
- * foriegn keys and must not refer to the tenant being deleted.  If any such 
+/** Delete a tenant, its admin users and its certificate authority.  The
+ * following tables also define foriegn keys and must not refer to the tenant being deleted.  If any such 
  * reference exists, a "FOREIGN KEY constraint failed" error message will be 
  * returned.  Here is the list of tables with foreign keys into the tenants table, 
  * not counting the admin table, which is handled by this function.
@@ -193,6 +198,13 @@ async fn delete_tenant(req: &ReqDeleteTenants) -> Result<u64> {
         .await?;
     deletes += result.rows_affected();
 
+    // Delete the tenant's certificate authority.
+    let result = sqlx::query(DELETE_TENANT_CA_FOR_TENANT)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+    deletes += result.rows_affected();
+
     // Issue the tenant delete call.
     let result = sqlx::query(DELETE_TENANT)
         .bind(&req.tenant)
@@ -38,6 +38,7 @@ pub struct RespGetTenants
     enabled: bool,
     created: DateTime<Utc>,
     updated: DateTime<Utc>,
+    allowed_key_types: Option<Vec<String>>,
 }
 
 // Implement the debug record trait for logging.
@@ -103,10 +104,11 @@ impl RespGetTenants {
     /// Create a new response.
     #[allow(clippy::too_many_arguments)]
     fn new(result_code: &str, result_msg: String, id: i32, tenant: String, 
-           enabled: bool, created: DateTime<Utc>, updated: DateTime<Utc>) 
+           enabled: bool, created: DateTime<Utc>, updated: DateTime<Utc>,
+           allowed_key_types: Option<Vec<String>>) 
     -> Self {
             Self {result_code: result_code.to_string(), result_msg, 
-                  id, tenant, enabled, created, updated}
+                  id, tenant, enabled, created, updated, allowed_key_types}
         }
 
     /// Process the request.
@@ -119,7 +121,8 @@ impl RespGetTenants {
         let db_result = get_tenant_by_name(req).await;
         match db_result {
             Ok(u) => Ok(make_http_200(Self::new("0", "success".to_string(), u.id, u.tenant, 
-                                                             u.enabled, u.created, u.updated))),
+                                                             u.enabled, u.created, u.updated,
+                                                             u.allowed_key_types))),
             Err(e) => {
                 // Determine if this is a real db error or just record not found.
                 let msg = e.to_string();
@@ -155,7 +158,7 @@ async fn get_tenant_by_name(req: &ReqGetTenants) -> Result<Tenant> {
     match result {
         Some(row) => {
             Ok(Tenant::new(row.get(0), row.get(1), row.get(2), 
-                            row.get(3), row.get(4)))
+                            row.get(3), row.get(4), row.get(5)))
         },
         None => {
             Err(anyhow!("NOT_FOUND"))
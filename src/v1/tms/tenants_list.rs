@@ -1,7 +1,7 @@
 #![forbid(unsafe_code)]
 
 use poem::Request;
-use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use poem_openapi::{ OpenApi, payload::Json, param::Query, Object, ApiResponse };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use sqlx::Row;
@@ -9,7 +9,9 @@ use sqlx::Row;
 use crate::utils::errors::HttpResult;
 
 use crate::utils::db_statements::LIST_TENANTS;
-use crate::utils::tms_utils::{self, RequestDebug};
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms};
+use crate::utils::authz::{authorize, get_tenant_header, AuthzTypes};
 use log::error;
 
 use crate::RUNTIME_CTX;
@@ -17,6 +19,9 @@ use crate::RUNTIME_CTX;
 // ***************************************************************************
 //                          Request/Response Definiions
 // ***************************************************************************
+/** List tenants.  The site admin can list all tenants; tenant admins can only
+ * list their own tenant.
+ */
 pub struct ListTenantsApi;
 
 // ***************************************************************************
@@ -25,8 +30,11 @@ pub struct ListTenantsApi;
 #[derive(Object)]
 struct ReqListTenants
 {
-    // Empty for now, but kept as a placeholder for the eventual implementation
-    // of query parameters that will filter this endpoint's output.
+    tenant: String,
+    created_after: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -36,6 +44,7 @@ pub struct RespListTenants
     result_msg: String,
     num_tenants: i32,
     tenants: Vec<TenantsListElement>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -46,6 +55,7 @@ pub struct TenantsListElement
     enabled: i32,
     created: DateTime<Utc>,
     updated: DateTime<Utc>,
+    allowed_key_types: Option<Vec<String>>,
 }
 
 // Implement the debug record trait for logging.
@@ -54,6 +64,16 @@ impl RequestDebug for ReqListTenants {
     fn get_request_info(&self) -> String {
         let mut s = String::with_capacity(255);
         s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    created_after: ");
+        s.push_str(&format!("{:?}", self.created_after));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
         s
     }
 }
@@ -63,6 +83,10 @@ impl RequestDebug for ReqListTenants {
 enum TmsResponse {
     #[oai(status = 200)]
     Http200(Json<RespListTenants>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
     #[oai(status = 500)]
     Http500(Json<HttpResult>),
 }
@@ -70,6 +94,12 @@ enum TmsResponse {
 fn make_http_200(resp: RespListTenants) -> TmsResponse {
     TmsResponse::Http200(Json(resp))
 }
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
 fn make_http_500(msg: String) -> TmsResponse {
     TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
 }
@@ -80,13 +110,38 @@ fn make_http_500(msg: String) -> TmsResponse {
 #[OpenApi]
 impl ListTenantsApi {
     #[oai(path = "/tms/tenants/list", method = "get")]
-    async fn get_tenants(&self, http_req: &Request) -> TmsResponse {
+    async fn get_tenants(&self, http_req: &Request, 
+                                created_after: Query<Option<DateTime<Utc>>>, limit: Query<Option<u32>>,
+                                cursor: Query<Option<String>>, sort: Query<Option<String>>) 
+        -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.  Even the admins of disabled 
+        // tenants can see their tenant definition.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
         // Package the request parameters.        
-        let req = ReqListTenants {};
+        let req = ReqListTenants {tenant: hdr_tenant, created_after: *created_after, limit: *limit,
+                                  cursor: cursor.clone(), sort: sort.clone()};
         
+        // -------------------- Authorize ----------------------------
+        // The site admin can list all tenants, tenant admins only their own.
+        let allowed = [AuthzTypes::SiteAdmin, AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to list tenants in tenant {}.", req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+        let tenant_constraint = 
+            if authz_result.authz_type == Some(AuthzTypes::SiteAdmin) {None} 
+            else {Some(req.tenant.clone())};
+
         // -------------------- Process Request ----------------------
         // Process the request.
-        match RespListTenants::process(http_req, &req).await {
+        match RespListTenants::process(http_req, &req, tenant_constraint).await {
             Ok(r) => r,
             Err(e) => {
                 let msg = "ERROR: ".to_owned() + e.to_string().as_str();
@@ -103,29 +158,38 @@ impl ListTenantsApi {
 impl TenantsListElement {
     /// Create response elements.
     #[allow(clippy::too_many_arguments)]
-    fn new(id: i32, tenant: String, enabled: i32, created: DateTime<Utc>, updated: DateTime<Utc>) -> Self {
-        Self {id, tenant, enabled, created, updated}
+    fn new(id: i32, tenant: String, enabled: i32, created: DateTime<Utc>, updated: DateTime<Utc>,
+           allowed_key_types: Option<Vec<String>>) -> Self {
+        Self {id, tenant, enabled, created, updated, allowed_key_types}
     }
 }
 
 impl RespListTenants {
     /// Create a new response.
     #[allow(clippy::too_many_arguments)]
-    fn new(result_code: &str, result_msg: String, num_tenants: i32, tenants: Vec<TenantsListElement>) 
+    fn new(result_code: &str, result_msg: String, num_tenants: i32, tenants: Vec<TenantsListElement>, next_cursor: Option<String>) 
     -> Self {
-        Self {result_code: result_code.to_string(), result_msg, num_tenants, tenants}
+        Self {result_code: result_code.to_string(), result_msg, num_tenants, tenants, next_cursor}
         }
 
     /// Process the request.
-    async fn process(http_req: &Request, req: &ReqListTenants) -> Result<TmsResponse, anyhow::Error> {
+    async fn process(http_req: &Request, req: &ReqListTenants, tenant_constraint: Option<String>) 
+        -> Result<TmsResponse, anyhow::Error> {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Validate the paging and filter parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
         // Search for the tenant/users in the database. Note that even disabled
         // tenants can see tenant definitions. The client_secret is never part of the response.
-        let users = list_tenants(req).await?;
+        let mut users = list_tenants(req, tenant_constraint, &parms).await?;
+        let next_cursor = parms.paginate(&mut users, |e| e.id);
         Ok(make_http_200(Self::new("0", "success".to_string(), 
-                                        users.len() as i32, users)))
+                                        users.len() as i32, users, next_cursor)))
     }
 }
 
@@ -135,15 +199,19 @@ impl RespListTenants {
 // ---------------------------------------------------------------------------
 // list_tenants:
 // ---------------------------------------------------------------------------
-#[allow(unused_variables)]
-async fn list_tenants(req: &ReqListTenants) -> Result<Vec<TenantsListElement>> {
+async fn list_tenants(req: &ReqListTenants, tenant_constraint: Option<String>, parms: &ListParms) 
+    -> Result<Vec<TenantsListElement>> {
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
     // Create the select statement.
-    let rows = sqlx::query(LIST_TENANTS)
+    let mut query = TmsQuery::new(LIST_TENANTS)
+        .and_opt("tenant", Cmp::Eq, tenant_constraint)
+        .and_opt("created", Cmp::Gt, req.created_after)
+        .paginate(parms);
+    let rows = query.build()
         .fetch_all(&mut *tx)
         .await?;
 
@@ -155,7 +223,7 @@ async fn list_tenants(req: &ReqListTenants) -> Result<Vec<TenantsListElement>> {
     for row in rows {
         let elem = TenantsListElement::new(
                     row.get(0), row.get(1), row.get(2), 
-                    row.get(3), row.get(4));
+                    row.get(3), row.get(4), row.get(5));
         element_list.push(elem);
     }
 
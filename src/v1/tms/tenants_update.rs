@@ -5,7 +5,8 @@ use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
 use anyhow::Result;
 
 use crate::utils::errors::HttpResult;
-use crate::utils::db_statements::UPDATE_TENANTS_ENABLED;
+use crate::utils::db_statements::{UPDATE_TENANTS_ENABLED, UPDATE_TENANTS_KEY_TYPES};
+use crate::utils::keygen::validate_policy_key_specs;
 use crate::utils::tms_utils::{self, RequestDebug, timestamp_utc, timestamp_utc_to_str, check_tenant_enabled};
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, X_TMS_TENANT};
 use log::{error, info};
@@ -24,7 +25,8 @@ pub struct UpdateTenantsApi;
 pub struct ReqUpdateTenants
 {
     tenant: String,
-    enabled: bool,
+    enabled: Option<bool>,
+    allowed_key_types: Option<Vec<String>>,  // empty list allows all key types
 }
 
 #[derive(Object, Debug)]
@@ -41,6 +43,7 @@ impl RequestDebug for ReqUpdateTenants {
     fn get_request_info(&self) -> String {
         // Get optional values in displayable form. 
         let enabled = format!("{:#?}", &self.enabled);
+        let allowed_key_types = format!("{:?}", &self.allowed_key_types);
 
         let mut s = String::with_capacity(255);
         s.push_str("  Request body:");
@@ -48,6 +51,8 @@ impl RequestDebug for ReqUpdateTenants {
         s.push_str(&self.tenant);
         s.push_str("\n    enabled: ");
         s.push_str(enabled.as_str());
+        s.push_str("\n    allowed_key_types: ");
+        s.push_str(allowed_key_types.as_str());
         s
     }
 }
@@ -148,11 +153,25 @@ impl RespUpdateTenants {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Determine if any updates are required.
+        if req.enabled.is_none() && req.allowed_key_types.is_none() {
+            return Ok(make_http_200(RespUpdateTenants::new("0", "No updates specified".to_string(), 0)));
+        }
+
+        // Validate the key type policy.
+        if let Some(allowed_key_types) = &req.allowed_key_types {
+            if let Err(e) = validate_policy_key_specs(allowed_key_types) {
+                let msg = format!("ERROR: {}", e);
+                error!("{}", msg);
+                return Ok(make_http_400(msg));
+            }
+        }
+
         // Insert the new key record.
         let updates = update_tenant(req).await?;
         
         // Log result and return response.
-        let msg = format!("{} update(s) to tenant {} completed, enabled = {}", updates, req.tenant, req.enabled);
+        let msg = format!("{} update(s) to tenant {} completed", updates, req.tenant);
         info!("{}", msg);
         Ok(make_http_200(RespUpdateTenants::new("0", msg, updates as i32)))
     }
@@ -177,14 +196,30 @@ async fn update_tenant(req: &ReqUpdateTenants) -> Result<u64> {
     // Update count.
     let mut updates: u64 = 0;
 
-    // Issue the db update call.
-    let result = sqlx::query(UPDATE_TENANTS_ENABLED)
-        .bind(req.enabled)
-        .bind(current_ts)
-        .bind(&req.tenant)
-        .execute(&mut *tx)
-        .await?;
-    updates += result.rows_affected();
+    // Conditionally update the enabled flag.
+    if let Some(enabled) = &req.enabled {
+        // Issue the db update call.
+        let result = sqlx::query(UPDATE_TENANTS_ENABLED)
+            .bind(enabled)
+            .bind(&current_ts)
+            .bind(&req.tenant)
+            .execute(&mut *tx)
+            .await?;
+        updates += result.rows_affected();
+    }
+
+    // Conditionally update the key type policy.  An empty list removes 
+    // the policy so that all supported key types are allowed.
+    if let Some(allowed_key_types) = &req.allowed_key_types {
+        let policy = if allowed_key_types.is_empty() {None} else {Some(allowed_key_types)};
+        let result = sqlx::query(UPDATE_TENANTS_KEY_TYPES)
+            .bind(policy)
+            .bind(&current_ts)
+            .bind(&req.tenant)
+            .execute(&mut *tx)
+            .await?;
+        updates += result.rows_affected();
+    }
 
     // Commit the transaction.
     tx.commit().await?;
@@ -5,11 +5,14 @@ use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
 use anyhow::Result;
 
 use crate::utils::errors::HttpResult;
-use crate::utils::db_statements::{DELETE_TENANT, DELETE_ADMINS_FOR_TENANT, DELETE_RESERVATIONS_FOR_TENANT,
+use crate::utils::db_statements::{DELETE_TENANT, DELETE_ADMINS_FOR_TENANT, DELETE_TENANT_CA_FOR_TENANT, DELETE_RESERVATIONS_FOR_TENANT,
         DELETE_PUBKEYS_FOR_TENANT, DELETE_DELEGATIONS_FOR_TENANT, DELETE_USER_HOSTS_FOR_TENANT, 
-        DELETE_USER_MFAS_FOR_TENANT, DELETE_CLIENTS_FOR_TENANT, DELETE_HOSTS_FOR_TENANT};
+        DELETE_USER_MFAS_FOR_TENANT, DELETE_CLIENTS_FOR_TENANT, DELETE_HOSTS_FOR_TENANT, DELETE_HOST_GROUPS_FOR_TENANT,
+        DELETE_HOST_CREDS_FOR_TENANT, DELETE_CLIENT_APPROVALS_FOR_TENANT, DELETE_USER_CREDS_FOR_TENANT,
+        DELETE_CERT_MAPPINGS_FOR_TENANT};
 use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
-use crate::utils::authz::{authorize, get_tenant_header, AuthzTypes, X_TMS_TENANT};
+use crate::utils::authz::{authorize, get_tenant_header, AuthzTypes};
+use crate::utils::config::DEFAULT_TENANT;
 use log::{error, info};
 
 use crate::RUNTIME_CTX;
@@ -93,10 +96,16 @@ impl WipeTenantsApi {
             Err(e) => return make_http_400(e.to_string()),
         };
         
-        // Check that the tenant specified in the header is the same as the one in the request body.
-        if hdr_tenant != *tenant {
-            let msg = format!("ERROR: FORBIDDEN - The tenant in the {} header ({}) does not match the tenant in the request body ({})", 
-                                      X_TMS_TENANT, hdr_tenant, *tenant);
+        // Check that the tenant specified in the header is the default tenant.
+        if hdr_tenant != DEFAULT_TENANT {
+            let msg = format!("ERROR: FORBIDDEN - Only the site admin in the '{}' tenant can wipe tenants.", DEFAULT_TENANT);
+            error!("{}", msg);
+            return make_http_403(msg);  
+        }
+
+        // The default tenant contains the site admin and is never removed.
+        if *tenant == DEFAULT_TENANT {
+            let msg = format!("ERROR: FORBIDDEN - The '{}' tenant cannot be wiped.", DEFAULT_TENANT);
             error!("{}", msg);
             return make_http_403(msg);  
         }
@@ -107,11 +116,11 @@ impl WipeTenantsApi {
         }
 
         // Package the request parameters.
-        let req = ReqWipeTenants { tenant: hdr_tenant};
+        let req = ReqWipeTenants { tenant: tenant.to_string()};
 
         // -------------------- Authorize ----------------------------
-        // Currently, only the tenant admin can wipe a tenant record and all its dependencies.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the site admin can wipe a tenant and all its dependencies.
+        let allowed = [AuthzTypes::SiteAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to delete tenant {}.", req.tenant);
@@ -174,6 +183,11 @@ impl RespWipeTenants {
  *      pubkeys
  *      reservations
  *      hosts
+ *      host_creds
+ *      user_creds
+ *      cert_mappings
+ *      client_approvals
+ *      tenant_ca
  */
 async fn wipe_tenant(req: &ReqWipeTenants) -> Result<u64> {
     // Get a connection to the db and start a transaction.  Uncommited transactions 
@@ -228,6 +242,43 @@ async fn wipe_tenant(req: &ReqWipeTenants) -> Result<u64> {
         .await?;
     deletes += result.rows_affected();
 
+    let result = sqlx::query(DELETE_HOST_GROUPS_FOR_TENANT)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+    deletes += result.rows_affected();
+
+    let result = sqlx::query(DELETE_CLIENT_APPROVALS_FOR_TENANT)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+    deletes += result.rows_affected();
+
+    let result = sqlx::query(DELETE_HOST_CREDS_FOR_TENANT)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+    deletes += result.rows_affected();
+
+    let result = sqlx::query(DELETE_USER_CREDS_FOR_TENANT)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+    deletes += result.rows_affected();
+
+    let result = sqlx::query(DELETE_CERT_MAPPINGS_FOR_TENANT)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+    deletes += result.rows_affected();
+
+    // Delete the tenant's certificate authority.
+    let result = sqlx::query(DELETE_TENANT_CA_FOR_TENANT)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+    deletes += result.rows_affected();
+
     let result = sqlx::query(DELETE_ADMINS_FOR_TENANT)
         .bind(&req.tenant)
         .execute(&mut *tx)
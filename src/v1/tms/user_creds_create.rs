@@ -0,0 +1,213 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::INSERT_USER_CREDS;
+use crate::utils::db_types::UserCredInput;
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, X_TMS_TENANT}; 
+use crate::utils::config::WILDCARD;
+use crate::utils::tms_utils::{self, timestamp_utc, create_hex_secret, hash_secret, RequestDebug, check_tenant_enabled};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+/** Create the credentials a user uses to authenticate when managing their own
+ * records.  The tms_user_id is the same user id used in user_mfa, user_hosts and
+ * delegations records.  The generated secret is only returned in this response;
+ * only its hash is saved.
+ */
+pub struct CreateUserCredsApi;
+
+#[derive(Object)]
+pub struct ReqCreateUserCreds
+{
+    tenant: String,
+    tms_user_id: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespCreateUserCreds
+{
+    result_code: String,
+    result_msg: String,
+    tenant: String,
+    tms_user_id: String,
+    user_secret: String,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqCreateUserCreds {   
+    type Req = ReqCreateUserCreds;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    tms_user_id: ");
+        s.push_str(&self.tms_user_id);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 201)]
+    Http201(Json<RespCreateUserCreds>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_201(resp: RespCreateUserCreds) -> TmsResponse {
+    TmsResponse::Http201(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl CreateUserCredsApi {
+    #[oai(path = "/tms/usercreds", method = "post")]
+    async fn create_user_creds(&self, http_req: &Request, req: Json<ReqCreateUserCreds>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check that the tenant specified in the header is the same as the one in the request body.
+        if hdr_tenant != req.tenant {
+            let msg = format!("ERROR: FORBIDDEN - The tenant in the {} header ({}) does not match the tenant in the request body ({})", 
+                                      X_TMS_TENANT, hdr_tenant, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);  
+        }
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can create user credentials.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to create credentials for user {} in tenant {}.", 
+                                      req.tms_user_id, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        match RespCreateUserCreds::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespCreateUserCreds {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, tenant: String, tms_user_id: String, user_secret: String) 
+        -> Self {Self {result_code: result_code.to_string(), result_msg, tenant, tms_user_id, user_secret}}
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqCreateUserCreds) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // The wildcard user stands for all users in user_hosts and delegations 
+        // records, so it can never be used to authenticate.
+        if req.tms_user_id == WILDCARD || req.tms_user_id.is_empty() {
+            let msg = format!("ERROR: Invalid tms_user_id ({}).", req.tms_user_id);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
+        // ------------------------ Generate Secret --------------------  
+        let user_secret_str  = create_hex_secret();
+        let user_secret_hash = hash_secret(&user_secret_str).await?;
+
+        // Use the same current UTC timestamp in all related time caculations..
+        let now = timestamp_utc();
+
+        // Create the input record.  Note that we save the hash of
+        // the hex secret, but never the secret itself.  
+        let input_record = UserCredInput::new(
+            req.tenant.clone(),
+            req.tms_user_id.clone(),
+            user_secret_hash,
+            now, 
+            now,
+        );
+
+        // Insert the new record.
+        insert_user_creds(input_record).await?;
+        info!("Credentials for user '{}' created in tenant '{}'.", &req.tms_user_id, &req.tenant);
+        
+        // Return the secret represented in hex.
+        Ok(make_http_201(Self::new("0", "success".to_string(), req.tenant.clone(), 
+                         req.tms_user_id.clone(), user_secret_str)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// insert_user_creds:
+// ---------------------------------------------------------------------------
+async fn insert_user_creds(rec: UserCredInput) -> Result<u64> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
+    // are automatically rolled back when they go out of scope. 
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+    
+    // Create the insert statement.
+    let result = sqlx::query(INSERT_USER_CREDS)
+        .bind(&rec.tenant)
+        .bind(&rec.tms_user_id)
+        .bind(&rec.user_secret)
+        .bind(rec.created)
+        .bind(rec.updated)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
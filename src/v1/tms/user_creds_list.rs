@@ -0,0 +1,229 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, param::Query, Object, ApiResponse };
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::utils::errors::HttpResult;
+
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::db_statements::LIST_USER_CREDS;
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms, check_tenant_enabled};
+use log::error;
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+pub struct ListUserCredsApi;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+#[derive(Object)]
+struct ReqListUserCreds
+{
+    tenant: String,
+    tms_user_id: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
+}
+
+#[derive(Object, Debug)]
+pub struct RespListUserCreds
+{
+    result_code: String,
+    result_msg: String,
+    num_users: i32,
+    users: Vec<UserCredsListElement>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Object, Debug)]
+pub struct UserCredsListElement
+{
+    id: i32,
+    tenant: String,
+    tms_user_id: String,
+    created: DateTime<Utc>,
+    updated: DateTime<Utc>,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqListUserCreds {   
+    type Req = ReqListUserCreds;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s.push_str("\n    tms_user_id: ");
+        s.push_str(&format!("{:?}", self.tms_user_id));
+        s.push_str("\n    created_after: ");
+        s.push_str(&format!("{:?}", self.created_after));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespListUserCreds>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespListUserCreds) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl ListUserCredsApi {
+    #[oai(path = "/tms/usercreds/list", method = "get")]
+    async fn get_list_user_creds_api(&self, http_req: &Request, 
+                                            tms_user_id: Query<Option<String>>, created_after: Query<Option<DateTime<Utc>>>,
+                                            limit: Query<Option<u32>>, cursor: Query<Option<String>>,
+                                            sort: Query<Option<String>>) 
+        -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+        
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.        
+        let req = ReqListUserCreds {tenant: hdr_tenant, tms_user_id: tms_user_id.clone(),
+                                    created_after: *created_after, limit: *limit,
+                                    cursor: cursor.clone(), sort: sort.clone()};
+        
+        // -------------------- Authorize ----------------------------
+        // Only the tenant admin can list user credentials.
+        let allowed = [AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to list user credentials in tenant {}.", req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespListUserCreds::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl UserCredsListElement {
+    /// Create response elements.
+    fn new(id: i32, tenant: String, tms_user_id: String, 
+           created: DateTime<Utc>, updated: DateTime<Utc>) -> Self {
+        Self {id, tenant, tms_user_id, created, updated}
+    }
+}
+
+impl RespListUserCreds {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, num_users: i32, users: Vec<UserCredsListElement>, next_cursor: Option<String>) 
+    -> Self {
+        Self {result_code: result_code.to_string(), result_msg, num_users, users, next_cursor}
+        }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqListUserCreds) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // Validate the paging and filter parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
+        // The user_secret is never part of the response.
+        let mut users = list_user_creds(req, &parms).await?;
+        let next_cursor = parms.paginate(&mut users, |e| e.id);
+        Ok(make_http_200(Self::new("0", "success".to_string(), 
+                                        users.len() as i32, users, next_cursor)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// list_user_creds:
+// ---------------------------------------------------------------------------
+async fn list_user_creds(req: &ReqListUserCreds, parms: &ListParms) -> Result<Vec<UserCredsListElement>> {
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
+    // are automatically rolled back when they go out of scope. 
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+    
+    // Create the select statement.
+    let mut query = TmsQuery::new(LIST_USER_CREDS)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and_opt("tms_user_id", Cmp::Eq, req.tms_user_id.as_ref())
+        .and_opt("created", Cmp::Gt, req.created_after)
+        .paginate(parms);
+    let rows = query.build()
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+
+    // Collect the row data into element objects.
+    let mut element_list: Vec<UserCredsListElement> = vec!();
+    for row in rows {
+        let elem = UserCredsListElement::new(
+                 row.get(0), row.get(1), row.get(2), 
+        row.get(3), row.get(4),);
+        element_list.push(elem);
+    }
+
+    Ok(element_list)
+}
@@ -0,0 +1,207 @@
+#![forbid(unsafe_code)]
+
+use poem::Request;
+use poem_openapi::{ OpenApi, payload::Json, Object, param::Path, ApiResponse };
+use anyhow::Result;
+
+use crate::utils::errors::HttpResult;
+use crate::utils::db_statements::UPDATE_USER_SECRET;
+use crate::utils::tms_utils::{self, RequestDebug, create_hex_secret, hash_secret, 
+                              timestamp_utc, check_tenant_enabled};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use log::{error, info};
+
+use crate::RUNTIME_CTX;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+pub struct UpdateUserCredsSecretApi;
+
+// ***************************************************************************
+//                          Request/Response Definiions
+// ***************************************************************************
+#[derive(Object)]
+pub struct ReqUpdateUserCredsSecret
+{
+    tms_user_id: String,
+    tenant: String,
+}
+
+#[derive(Object, Debug)]
+pub struct RespUpdateUserCredsSecret
+{
+    result_code: String,
+    result_msg: String,
+    tms_user_id: String,
+    tenant: String,
+    user_secret: String,
+}
+
+// Implement the debug record trait for logging.
+impl RequestDebug for ReqUpdateUserCredsSecret {   
+    type Req = ReqUpdateUserCredsSecret;
+    fn get_request_info(&self) -> String {
+        let mut s = String::with_capacity(255);
+        s.push_str("  Request body:");
+        s.push_str("\n    tms_user_id: ");
+        s.push_str(&self.tms_user_id);
+        s.push_str("\n    tenant: ");
+        s.push_str(&self.tenant);
+        s
+    }
+}
+
+// ------------------- HTTP Status Codes -------------------
+#[derive(Debug, ApiResponse)]
+enum TmsResponse {
+    #[oai(status = 200)]
+    Http200(Json<RespUpdateUserCredsSecret>),
+    #[oai(status = 400)]
+    Http400(Json<HttpResult>),
+    #[oai(status = 401)]
+    Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
+    #[oai(status = 404)]
+    Http404(Json<HttpResult>),
+    #[oai(status = 500)]
+    Http500(Json<HttpResult>),
+}
+
+fn make_http_200(resp: RespUpdateUserCredsSecret) -> TmsResponse {
+    TmsResponse::Http200(Json(resp))
+}
+fn make_http_400(msg: String) -> TmsResponse {
+    TmsResponse::Http400(Json(HttpResult::new(400.to_string(), msg)))
+}
+fn make_http_401(msg: String) -> TmsResponse {
+    TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
+}
+fn make_http_404(msg: String) -> TmsResponse {
+    TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
+}
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
+fn make_http_500(msg: String) -> TmsResponse {
+    TmsResponse::Http500(Json(HttpResult::new(500.to_string(), msg)))    
+}
+
+// ***************************************************************************
+//                             OpenAPI Endpoint
+// ***************************************************************************
+#[OpenApi]
+impl UpdateUserCredsSecretApi {
+    #[oai(path = "/tms/usercreds/secret/:tms_user_id", method = "patch")]
+    async fn update_user_creds_secret(&self, http_req: &Request, tms_user_id: Path<String>) -> TmsResponse {
+        // -------------------- Get Tenant Header --------------------
+        // Get the required tenant header value.
+        let hdr_tenant = match get_tenant_header(http_req) {
+            Ok(t) => t,
+            Err(e) => return make_http_400(e.to_string()),
+        };
+
+        // Check tenant.
+        if !check_tenant_enabled(&hdr_tenant).await {
+            return make_http_400("Tenant not enabled.".to_string());
+        }
+
+        // Package the request parameters.
+        let req = ReqUpdateUserCredsSecret {tms_user_id: tms_user_id.to_string(), tenant: hdr_tenant};
+
+        // -------------------- Authorize ----------------------------
+        // Users can rotate their own secrets; the tenant admin can reset any
+        // user's secret.
+        let allowed = [AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
+        let authz_result = authorize(http_req, &allowed).await;
+        if !authz_result.is_authorized() {
+            let msg = format!("ERROR: NOT AUTHORIZED to update the secret of user {} in tenant {}.", 
+                                      req.tms_user_id, req.tenant);
+            error!("{}", msg);
+            return make_http_401(msg);
+        }
+
+        // Make sure the request parms conform to the header values used for authorization.
+        if !authz_result.check_hdr_id(&req.tms_user_id) {
+            let msg = format!("ERROR: FORBIDDEN - Payload parameters ({}@{}) differ from those in the request header.", 
+                                      req.tms_user_id, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
+        // -------------------- Process Request ----------------------
+        // Process the request.
+        match RespUpdateUserCredsSecret::process(http_req, &req).await {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = "ERROR: ".to_owned() + e.to_string().as_str();
+                error!("{}", msg);
+                make_http_500(msg)
+            }
+        }
+    }
+}
+
+// ***************************************************************************
+//                          Request/Response Methods
+// ***************************************************************************
+impl RespUpdateUserCredsSecret {
+    /// Create a new response.
+    fn new(result_code: &str, result_msg: String, tms_user_id: String, tenant: String, user_secret: String,) -> Self {
+        Self {result_code: result_code.to_string(), result_msg, tms_user_id, tenant, user_secret,}
+    }
+
+    /// Process the request.
+    async fn process(http_req: &Request, req: &ReqUpdateUserCredsSecret) -> Result<TmsResponse, anyhow::Error> {
+        // Conditional logging depending on log level.
+        tms_utils::debug_request(http_req, req);
+
+        // ------------------------ Generate Secret --------------------  
+        let user_secret_str  = create_hex_secret();
+        let user_secret_hash = hash_secret(&user_secret_str).await?;
+
+        // Replace the secret hash.
+        let updates = update_user_secret(req, user_secret_hash).await?;
+        if updates < 1 {
+            let msg = format!("Credentials for user {} NOT FOUND in tenant {}", req.tms_user_id, req.tenant);
+            error!("{}", msg);
+            return Ok(make_http_404(msg));
+        }
+        
+        // Log result and return response.
+        let msg = format!("Secret updated for user {}", req.tms_user_id);
+        info!("{}", msg);
+        Ok(make_http_200(RespUpdateUserCredsSecret::new("0", msg, req.tms_user_id.clone(), 
+                                       req.tenant.clone(), user_secret_str)))
+    }
+}
+
+// ***************************************************************************
+//                          Private Functions
+// ***************************************************************************
+// ---------------------------------------------------------------------------
+// update_user_secret:
+// ---------------------------------------------------------------------------
+async fn update_user_secret(req: &ReqUpdateUserCredsSecret, user_secret_hash: String) -> Result<u64> {
+    // Get timestamp.
+    let now = timestamp_utc();
+
+    // Get a connection to the db and start a transaction.  Uncommited transactions 
+    // are automatically rolled back when they go out of scope. 
+    // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
+    let mut tx = RUNTIME_CTX.db.begin().await?;
+
+    // Issue the db update call.
+    let result = sqlx::query(UPDATE_USER_SECRET)
+        .bind(user_secret_hash)
+        .bind(now)
+        .bind(&req.tms_user_id)
+        .bind(&req.tenant)
+        .execute(&mut *tx)
+        .await?;
+
+    // Commit the transaction.
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
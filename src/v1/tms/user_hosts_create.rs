@@ -9,6 +9,7 @@ use crate::utils::errors::HttpResult;
 use crate::utils::db_statements::{INSERT_USER_HOSTS, INSERT_USER_HOSTS_NOT_STRICT};
 use crate::utils::db_types::UserHostInput;
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, X_TMS_TENANT}; 
+use crate::utils::config::WILDCARD;
 use crate::utils::tms_utils::{self, timestamp_utc, timestamp_utc_to_str, calc_expires_at, RequestDebug, check_tenant_enabled};
 use log::{error, info};
 
@@ -125,10 +126,8 @@ impl CreateUserHostsApi {
         }
 
         // -------------------- Authorize ----------------------------
-        // Currently, only the tenant admin can create a user host record.
-        // When user authentication is implemented, we'll add user-own 
-        // authorization and any additional validation.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the tenant admin and the user can create a user host record.
+        let allowed = [AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to add a user host record in tenant {}.", req.tenant);
@@ -136,6 +135,24 @@ impl CreateUserHostsApi {
             return make_http_401(msg);
         }
 
+        // Make sure the request parms conform to the header values used for authorization.
+        if !authz_result.check_hdr_id(&req.tms_user_id) {
+            let msg = format!("ERROR: FORBIDDEN - Payload parameters ({}@{}) differ from those in the request header.", 
+                                      req.tms_user_id, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
+        // Users can only link themselves to the host account of the same name,
+        // the mapping that a wildcard record would grant.  Mappings to any other
+        // account require a tenant admin.
+        if authz_result.authz_type == Some(AuthzTypes::UserOwn) && req.host_account != req.tms_user_id {
+            let msg = format!("ERROR: FORBIDDEN - User {} cannot link to host account {}; only a tenant admin can map users to other accounts.", 
+                                      req.tms_user_id, req.host_account);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
         // -------------------- Process Request ----------------------
         match RespCreateUserHosts::process(http_req, &req).await {
             Ok(r) => r,
@@ -162,6 +179,15 @@ impl RespCreateUserHosts {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // A wildcard mapping links every user to the host account of the same
+        // name, so the user and account must either both be wildcards or neither.
+        if (req.tms_user_id == WILDCARD) != (req.host_account == WILDCARD) {
+            let msg = format!("ERROR: Wildcard user host mappings require both tms_user_id and host_account \
+                                      to be '{}' (received {} and {}).", WILDCARD, req.tms_user_id, req.host_account);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
         // ------------------------ Time Values ------------------------ 
         // The ttl can be negative, which means maximum ttl.
         let ttl_minutes = if req.ttl_minutes < 0 {i32::MAX} else {req.ttl_minutes};
@@ -114,10 +114,8 @@ impl DeleteUserHostsApi {
         }
 
         // -------------------- Authorize ----------------------------
-        // Currently, only the tenant admin can delete a user hosts record.
-        // When user authentication is implemented, we'll add user-own 
-        // authorization and any additional validation.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the tenant admin and the user can delete a user hosts record.
+        let allowed = [AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to delete host {} for user {} in tenant {}.", 
@@ -126,6 +124,14 @@ impl DeleteUserHostsApi {
             return make_http_401(msg);
         }
 
+        // Make sure the request parms conform to the header values used for authorization.
+        if !authz_result.check_hdr_id(&req.tms_user_id) {
+            let msg = format!("ERROR: FORBIDDEN - Payload parameters ({}@{}) differ from those in the request header.", 
+                                      req.tms_user_id, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
         // -------------------- Process Request ----------------------
         // Process the request.
         match RespDeleteUserHosts::process(http_req, &req).await {
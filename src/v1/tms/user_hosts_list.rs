@@ -1,16 +1,17 @@
 #![forbid(unsafe_code)]
 
 use poem::Request;
-use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use poem_openapi::{ OpenApi, payload::Json, param::Query, Object, ApiResponse };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use sqlx::Row;
 
 use crate::utils::errors::HttpResult;
 
-use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
+use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header, AuthzResult};
 use crate::utils::db_statements::LIST_USER_HOSTS;
-use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms, parse_status_filter, get_user_constraint, check_tenant_enabled};
 use log::error;
 
 use crate::RUNTIME_CTX;
@@ -27,6 +28,13 @@ pub struct ListUserHostsApi;
 struct ReqListUserHosts
 {
     tenant: String,
+    tms_user_id: Option<String>,
+    host: Option<String>,
+    status: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -36,6 +44,7 @@ pub struct RespListUserHosts
     result_msg: String,
     num_users: i32,
     users: Vec<UserHostsListElement>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -59,6 +68,20 @@ impl RequestDebug for ReqListUserHosts {
         s.push_str("  Request body:");
         s.push_str("\n    tenant: ");
         s.push_str(&self.tenant);
+        s.push_str("\n    tms_user_id: ");
+        s.push_str(&format!("{:?}", self.tms_user_id));
+        s.push_str("\n    host: ");
+        s.push_str(&format!("{:?}", self.host));
+        s.push_str("\n    status: ");
+        s.push_str(&format!("{:?}", self.status));
+        s.push_str("\n    created_after: ");
+        s.push_str(&format!("{:?}", self.created_after));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
         s
     }
 }
@@ -95,7 +118,13 @@ fn make_http_500(msg: String) -> TmsResponse {
 #[OpenApi]
 impl ListUserHostsApi {
     #[oai(path = "/tms/userhosts/list", method = "get")]
-    async fn get_user_hosts(&self, http_req: &Request) -> TmsResponse {
+    #[allow(clippy::too_many_arguments)]
+    async fn get_user_hosts(&self, http_req: &Request, 
+                                   tms_user_id: Query<Option<String>>, host: Query<Option<String>>,
+                                   status: Query<Option<String>>, created_after: Query<Option<DateTime<Utc>>>,
+                                   limit: Query<Option<u32>>, cursor: Query<Option<String>>,
+                                   sort: Query<Option<String>>) 
+        -> TmsResponse {
         // -------------------- Get Tenant Header --------------------
         // Get the required tenant header value.
         let hdr_tenant = match get_tenant_header(http_req) {
@@ -109,11 +138,15 @@ impl ListUserHostsApi {
         }
 
         // Package the request parameters.        
-        let req = ReqListUserHosts {tenant: hdr_tenant};
+        let req = ReqListUserHosts {tenant: hdr_tenant, tms_user_id: tms_user_id.clone(),
+                                    host: host.clone(), status: status.clone(),
+                                    created_after: *created_after, limit: *limit,
+                                    cursor: cursor.clone(), sort: sort.clone()};
         
         // -------------------- Authorize ----------------------------
-        // Only the tenant admin can query a user host record.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the tenant admin can query all user host records; 
+        // a user can query their own records.
+        let allowed = [AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to list user host information in tenant {}.", req.tenant);
@@ -123,7 +156,7 @@ impl ListUserHostsApi {
 
         // -------------------- Process Request ----------------------
         // Process the request.
-        match RespListUserHosts::process(http_req, &req).await {
+        match RespListUserHosts::process(http_req, &req, &authz_result).await {
             Ok(r) => r,
             Err(e) => {
                 let msg = "ERROR: ".to_owned() + e.to_string().as_str();
@@ -149,21 +182,32 @@ impl UserHostsListElement {
 impl RespListUserHosts {
     /// Create a new response.
     #[allow(clippy::too_many_arguments)]
-    fn new(result_code: &str, result_msg: String, num_users: i32, users: Vec<UserHostsListElement>) 
+    fn new(result_code: &str, result_msg: String, num_users: i32, users: Vec<UserHostsListElement>, next_cursor: Option<String>) 
     -> Self {
-        Self {result_code: result_code.to_string(), result_msg, num_users, users}
+        Self {result_code: result_code.to_string(), result_msg, num_users, users, next_cursor}
         }
 
     /// Process the request.
-    async fn process(http_req: &Request, req: &ReqListUserHosts) -> Result<TmsResponse, anyhow::Error> {
+    async fn process(http_req: &Request, req: &ReqListUserHosts, authz_result: &AuthzResult) -> Result<TmsResponse, anyhow::Error> {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Validate the paging and filter parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+        let active = match parse_status_filter(&req.status) {
+            Ok(a) => a,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
         // Search for the tenant/client id in the database.  Not found was already 
         // The client_secret is never part of the response.
-        let clients = list_hosts_users(req).await?;
+        let mut clients = list_hosts_users(authz_result, req, &parms, active).await?;
+        let next_cursor = parms.paginate(&mut clients, |e| e.id);
         Ok(make_http_200(Self::new("0", "success".to_string(), 
-                                        clients.len() as i32, clients)))
+                                        clients.len() as i32, clients, next_cursor)))
     }
 }
 
@@ -173,15 +217,25 @@ impl RespListUserHosts {
 // ---------------------------------------------------------------------------
 // list_hosts_users:
 // ---------------------------------------------------------------------------
-async fn list_hosts_users(req: &ReqListUserHosts) -> Result<Vec<UserHostsListElement>> {
+async fn list_hosts_users(authz_result: &AuthzResult, req: &ReqListUserHosts, parms: &ListParms, active: Option<bool>) -> Result<Vec<UserHostsListElement>> {
+    // Restrict the query to the authorized user when necessary.
+    let user_id = get_user_constraint(authz_result)?;
+
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
     // Create the select statement.
-    let rows = sqlx::query(LIST_USER_HOSTS)
-        .bind(req.tenant.clone())
+    let mut query = TmsQuery::new(LIST_USER_HOSTS)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and_opt("tms_user_id", Cmp::Eq, user_id)
+        .and_opt("tms_user_id", Cmp::Eq, req.tms_user_id.as_ref())
+        .and_opt("host", Cmp::Eq, req.host.as_ref())
+        .and_active(active)
+        .and_opt("created", Cmp::Gt, req.created_after)
+        .paginate(parms);
+    let rows = query.build()
         .fetch_all(&mut *tx)
         .await?;
 
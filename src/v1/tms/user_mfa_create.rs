@@ -14,7 +14,7 @@ use crate::utils::tms_utils::{self, timestamp_utc, timestamp_utc_to_str, calc_ex
 use log::{error, info};
 
 use crate::RUNTIME_CTX;
-use crate::utils::config::DB_TRUE;
+use crate::utils::config::{DB_TRUE, WILDCARD};
 
 // Insert fails on conflict.        
 const STRICT:bool = true;
@@ -156,6 +156,13 @@ impl RespCreateUserMfa {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // The wildcard is reserved for user_hosts and delegations records.
+        if req.tms_user_id == WILDCARD {
+            let msg = format!("ERROR: '{}' is not a valid tms_user_id for MFA records.", WILDCARD);
+            error!("{}", msg);
+            return Ok(make_http_400(msg));
+        }
+
         // ------------------------ Time Values ------------------------ 
         // The ttl can be negative, which means maximum ttl.
         let ttl_minutes = if req.ttl_minutes < 0 {i32::MAX} else {req.ttl_minutes};
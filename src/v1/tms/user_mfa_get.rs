@@ -67,6 +67,8 @@ enum TmsResponse {
     Http400(Json<HttpResult>),
     #[oai(status = 401)]
     Http401(Json<HttpResult>),
+    #[oai(status = 403)]
+    Http403(Json<HttpResult>),
     #[oai(status = 404)]
     Http404(Json<HttpResult>),
     #[oai(status = 500)]
@@ -82,6 +84,9 @@ fn make_http_400(msg: String) -> TmsResponse {
 fn make_http_401(msg: String) -> TmsResponse {
     TmsResponse::Http401(Json(HttpResult::new(401.to_string(), msg)))
 }
+fn make_http_403(msg: String) -> TmsResponse {
+    TmsResponse::Http403(Json(HttpResult::new(403.to_string(), msg)))
+}
 fn make_http_404(msg: String) -> TmsResponse {
     TmsResponse::Http404(Json(HttpResult::new(404.to_string(), msg)))
 }
@@ -112,10 +117,8 @@ impl GetUserMfaApi {
         let req = ReqGetUserMfa {tms_user_id: tms_user_id.to_string(), tenant: hdr_tenant};
         
         // -------------------- Authorize ----------------------------
-        // Currently, only the tenant admin can create a user mfa record.
-        // When user authentication is implemented, we'll add user-own 
-        // authorization and any additional validation.
-        let allowed = [AuthzTypes::TenantAdmin];
+        // Only the tenant admin and the user can view a user mfa record.
+        let allowed = [AuthzTypes::UserOwn, AuthzTypes::TenantAdmin];
         let authz_result = authorize(http_req, &allowed).await;
         if !authz_result.is_authorized() {
             let msg = format!("ERROR: NOT AUTHORIZED to view mfa information for record #{} in tenant {}", 
@@ -124,6 +127,14 @@ impl GetUserMfaApi {
             return make_http_401(msg);
         }
 
+        // Make sure the request parms conform to the header values used for authorization.
+        if !authz_result.check_hdr_id(&req.tms_user_id) {
+            let msg = format!("ERROR: FORBIDDEN - Payload parameters ({}@{}) differ from those in the request header.", 
+                                      req.tms_user_id, req.tenant);
+            error!("{}", msg);
+            return make_http_403(msg);
+        }
+
         // -------------------- Process Request ----------------------
         // Process the request.
         match RespGetUserMfa::process(http_req, &req).await {
@@ -1,7 +1,7 @@
 #![forbid(unsafe_code)]
 
 use poem::Request;
-use poem_openapi::{ OpenApi, payload::Json, Object, ApiResponse };
+use poem_openapi::{ OpenApi, payload::Json, param::Query, Object, ApiResponse };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use sqlx::Row;
@@ -10,7 +10,8 @@ use crate::utils::errors::HttpResult;
 
 use crate::utils::authz::{authorize, AuthzTypes, get_tenant_header};
 use crate::utils::db_statements::LIST_USER_MFA;
-use crate::utils::tms_utils::{self, RequestDebug, check_tenant_enabled};
+use crate::utils::query_builder::{TmsQuery, Cmp};
+use crate::utils::tms_utils::{self, RequestDebug, ListParms, parse_status_filter, check_tenant_enabled};
 use log::error;
 
 use crate::RUNTIME_CTX;
@@ -27,6 +28,12 @@ pub struct ListUserMfaApi;
 struct ReqListUserMfa
 {
     tenant: String,
+    tms_user_id: Option<String>,
+    status: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -36,6 +43,7 @@ pub struct RespListUserMfa
     result_msg: String,
     num_users: i32,
     users: Vec<UserMfaListElement>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Object, Debug)]
@@ -58,6 +66,18 @@ impl RequestDebug for ReqListUserMfa {
         s.push_str("  Request body:");
         s.push_str("\n    tenant: ");
         s.push_str(&self.tenant);
+        s.push_str("\n    tms_user_id: ");
+        s.push_str(&format!("{:?}", self.tms_user_id));
+        s.push_str("\n    status: ");
+        s.push_str(&format!("{:?}", self.status));
+        s.push_str("\n    created_after: ");
+        s.push_str(&format!("{:?}", self.created_after));
+        s.push_str("\n    limit: ");
+        s.push_str(&format!("{:?}", self.limit));
+        s.push_str("\n    cursor: ");
+        s.push_str(&format!("{:?}", self.cursor));
+        s.push_str("\n    sort: ");
+        s.push_str(&format!("{:?}", self.sort));
         s
     }
 }
@@ -94,7 +114,12 @@ fn make_http_500(msg: String) -> TmsResponse {
 #[OpenApi]
 impl ListUserMfaApi {
     #[oai(path = "/tms/usermfa/list", method = "get")]
-    async fn get_users(&self, http_req: &Request) -> TmsResponse {
+    #[allow(clippy::too_many_arguments)]
+    async fn get_users(&self, http_req: &Request, 
+                              tms_user_id: Query<Option<String>>, status: Query<Option<String>>,
+                              created_after: Query<Option<DateTime<Utc>>>, limit: Query<Option<u32>>,
+                              cursor: Query<Option<String>>, sort: Query<Option<String>>) 
+        -> TmsResponse {
         // -------------------- Get Tenant Header --------------------
         // Get the required tenant header value.
         let hdr_tenant = match get_tenant_header(http_req) {
@@ -108,7 +133,9 @@ impl ListUserMfaApi {
         }
 
         // Package the request parameters.        
-        let req = ReqListUserMfa {tenant: hdr_tenant};
+        let req = ReqListUserMfa {tenant: hdr_tenant, tms_user_id: tms_user_id.clone(),
+                                  status: status.clone(), created_after: *created_after,
+                                  limit: *limit, cursor: cursor.clone(), sort: sort.clone()};
         
         // -------------------- Authorize ----------------------------
         // Only the tenant admin can query user records.
@@ -148,9 +175,9 @@ impl UserMfaListElement {
 impl RespListUserMfa {
     /// Create a new response.
     #[allow(clippy::too_many_arguments)]
-    fn new(result_code: &str, result_msg: String, num_users: i32, users: Vec<UserMfaListElement>) 
+    fn new(result_code: &str, result_msg: String, num_users: i32, users: Vec<UserMfaListElement>, next_cursor: Option<String>) 
     -> Self {
-        Self {result_code: result_code.to_string(), result_msg, num_users, users}
+        Self {result_code: result_code.to_string(), result_msg, num_users, users, next_cursor}
         }
 
     /// Process the request.
@@ -158,10 +185,21 @@ impl RespListUserMfa {
         // Conditional logging depending on log level.
         tms_utils::debug_request(http_req, req);
 
+        // Validate the paging and filter parameters.
+        let parms = match ListParms::new(req.limit, &req.cursor, &req.sort) {
+            Ok(p) => p,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+        let active = match parse_status_filter(&req.status) {
+            Ok(a) => a,
+            Err(e) => return Ok(make_http_400(e.to_string())),
+        };
+
         // Search for the tenant/users in the database.  
-        let users = list_mfa_users(req).await?;
+        let mut users = list_mfa_users(req, &parms, active).await?;
+        let next_cursor = parms.paginate(&mut users, |e| e.id);
         Ok(make_http_200(Self::new("0", "success".to_string(), 
-                                        users.len() as i32, users)))
+                                        users.len() as i32, users, next_cursor)))
     }
 }
 
@@ -171,15 +209,20 @@ impl RespListUserMfa {
 // ---------------------------------------------------------------------------
 // list_mfa_users:
 // ---------------------------------------------------------------------------
-async fn list_mfa_users(req: &ReqListUserMfa) -> Result<Vec<UserMfaListElement>> {
+async fn list_mfa_users(req: &ReqListUserMfa, parms: &ListParms, active: Option<bool>) -> Result<Vec<UserMfaListElement>> {
     // Get a connection to the db and start a transaction.  Uncommited transactions 
     // are automatically rolled back when they go out of scope. 
     // See https://docs.rs/sqlx/latest/sqlx/struct.Transaction.html.
     let mut tx = RUNTIME_CTX.db.begin().await?;
     
     // Create the select statement.
-    let rows = sqlx::query(LIST_USER_MFA)
-        .bind(&req.tenant)
+    let mut query = TmsQuery::new(LIST_USER_MFA)
+        .and("tenant", Cmp::Eq, &req.tenant)
+        .and_opt("tms_user_id", Cmp::Eq, req.tms_user_id.as_ref())
+        .and_active(active)
+        .and_opt("created", Cmp::Gt, req.created_after)
+        .paginate(parms);
+    let rows = query.build()
         .fetch_all(&mut *tx)
         .await?;
 